@@ -44,7 +44,7 @@ fn main() -> anyhow::Result<()> {
 
     // 3. Show shard distribution
     println!("3. Shard distribution:");
-    let stats = db.get_shard_stats("products");
+    let stats = db.get_shard_stats("products")?;
     for stat in &stats {
         println!("  {}", stat.format());
     }
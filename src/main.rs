@@ -1,10 +1,12 @@
 // Main entry point for the RustyDB CLI
 // This provides an interactive shell to execute SQL commands
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use clap::Parser as ClapParser;
+use rustydb::storage::buffer::{BufferPoolConfig, EvictionPolicy};
 use rustydb::{QueryExecutor, QueryParser, ShardedDatabase};
 use std::io::{self, Write};
+use std::path::PathBuf;
 
 /// RustyDB - A simple SQL database implementation in Rust
 #[derive(ClapParser)]
@@ -17,6 +19,18 @@ struct Args {
     /// Execute a single SQL command and exit
     #[arg(short, long)]
     execute: Option<String>,
+
+    /// Database directory to persist tables in (default: in-memory only)
+    #[arg(long, value_name = "PATH")]
+    db: Option<PathBuf>,
+
+    /// Memory for cached pages of a --db database, in MiB
+    #[arg(long, value_name = "MIB", default_value_t = 32)]
+    buffer_pool_mb: usize,
+
+    /// How the buffer pool picks pages to evict: lru or clock
+    #[arg(long, value_name = "POLICY", default_value = "lru")]
+    eviction: EvictionPolicy,
 }
 
 fn main() -> Result<()> {
@@ -24,7 +38,13 @@ fn main() -> Result<()> {
 
     if args.shards == 1 {
         // Single database mode (no sharding)
-        run_single_db(args.execute)?;
+        let config = BufferPoolConfig {
+            memory_budget: args.buffer_pool_mb * 1024 * 1024,
+            policy: args.eviction,
+        };
+        run_single_db(args.db, config, args.execute)?;
+    } else if args.db.is_some() {
+        return Err(anyhow!("--db is not supported together with --shards"));
     } else {
         // Sharded database mode
         run_sharded_db(args.shards, args.execute)?;
@@ -34,8 +54,16 @@ fn main() -> Result<()> {
 }
 
 /// Run the database in single-instance mode (no sharding)
-fn run_single_db(execute_cmd: Option<String>) -> Result<()> {
-    let mut executor = QueryExecutor::new();
+fn run_single_db(
+    db_path: Option<PathBuf>,
+    config: BufferPoolConfig,
+    execute_cmd: Option<String>,
+) -> Result<()> {
+    // Reload tables from disk if a database directory was given
+    let mut executor = match &db_path {
+        Some(path) => QueryExecutor::open_with_buffer_pool(path, config)?,
+        None => QueryExecutor::new(),
+    };
 
     // If a command was provided, execute it and exit
     if let Some(sql) = execute_cmd {
@@ -49,11 +77,36 @@ fn run_single_db(execute_cmd: Option<String>) -> Result<()> {
     println!("║      A Simple SQL Database in Rust        ║");
     println!("╚════════════════════════════════════════════╝");
     println!();
+    match &db_path {
+        Some(path) => println!(
+            "Database: {} (buffer pool: {} MiB, {})",
+            path.display(),
+            config.memory_budget / (1024 * 1024),
+            config.policy
+        ),
+        None => println!("Database: in-memory (use --db <path> to persist)"),
+    }
     println!("Type SQL commands or '.help' for help");
     println!("Type '.exit' to quit");
     println!();
 
-    repl(|sql| execute_query(&mut executor, sql))
+    // A second session on the same database reads the buffer pool counters
+    let stats_session = executor.session();
+    repl(
+        |sql| execute_query(&mut executor, sql),
+        || {
+            stats_session.buffer_stats().map(|stats| {
+                format!(
+                    "Buffer pool: {} hits, {} misses ({:.1}% hit ratio), {} evictions, {} page writes",
+                    stats.hits,
+                    stats.misses,
+                    stats.hit_ratio() * 100.0,
+                    stats.evictions,
+                    stats.writes
+                )
+            })
+        },
+    )
 }
 
 /// Run the database in sharded mode
@@ -75,7 +128,7 @@ fn run_sharded_db(num_shards: usize, execute_cmd: Option<String>) -> Result<()>
         return Ok(());
     }
 
-    repl(|sql| execute_sharded_query(&mut sharded_db, sql))
+    repl(|sql| execute_sharded_query(&mut sharded_db, sql), || None)
 }
 
 /// REPL (Read-Eval-Print Loop) implementation
@@ -83,9 +136,11 @@ fn run_sharded_db(num_shards: usize, execute_cmd: Option<String>) -> Result<()>
 ///
 /// The 'F' is a generic type parameter - it can be any function
 /// that takes a &str and returns a Result<()>
-fn repl<F>(mut execute_fn: F) -> Result<()>
+/// 'S' describes the buffer pool for .stats (None when there is none)
+fn repl<F, S>(mut execute_fn: F, stats_fn: S) -> Result<()>
 where
     F: FnMut(&str) -> Result<()>,
+    S: Fn() -> Option<String>,
 {
     let stdin = io::stdin();
     let mut stdout = io::stdout();
@@ -118,6 +173,13 @@ where
                     print_help();
                     continue;
                 }
+                ".stats" => {
+                    match stats_fn() {
+                        Some(stats) => println!("{}", stats),
+                        None => println!("No buffer pool (in-memory database)"),
+                    }
+                    continue;
+                }
                 _ => {
                     println!("Unknown command: {}", input);
                     println!("Type '.help' for help");
@@ -158,23 +220,26 @@ fn print_help() {
     println!();
     println!("Special Commands:");
     println!("  .help              Show this help message");
+    println!("  .stats             Show buffer pool hits, misses and evictions");
     println!("  .exit, .quit       Exit the shell");
     println!();
     println!("Supported SQL Commands:");
     println!();
     println!("  CREATE TABLE:");
     println!("    CREATE TABLE users (");
-    println!("      id INTEGER PRIMARY KEY,");
+    println!("      id SERIAL PRIMARY KEY,");
     println!("      name TEXT,");
     println!("      age INTEGER");
     println!("    )");
     println!();
     println!("  INSERT:");
-    println!("    INSERT INTO users VALUES (1, 'Alice', 30)");
+    println!("    INSERT INTO users VALUES (DEFAULT, 'Alice', 30)");
+    println!("    INSERT INTO users (name, age) VALUES ('Bob', 25)   (id from its sequence)");
     println!();
     println!("  SELECT:");
     println!("    SELECT * FROM users");
     println!("    SELECT * FROM users WHERE id = 1");
+    println!("    SELECT * FROM users WHERE age > 25 AND (name = 'Bob' OR NOT age > 60)");
     println!();
     println!("  UPDATE:");
     println!("    UPDATE users SET age = 31 WHERE id = 1");
@@ -182,9 +247,21 @@ fn print_help() {
     println!("  DELETE:");
     println!("    DELETE FROM users WHERE id = 1");
     println!();
+    println!("  CREATE SEQUENCE:");
+    println!("    CREATE SEQUENCE ticket_ids START WITH 100");
+    println!("    INSERT INTO tickets VALUES (nextval('ticket_ids'), 'help')");
+    println!();
     println!("  CREATE INDEX:");
     println!("    CREATE INDEX ON users (name)");
     println!();
+    println!("  TRANSACTIONS:");
+    println!("    BEGIN");
+    println!("    UPDATE users SET age = 31 WHERE id = 1");
+    println!("    COMMIT             (or ROLLBACK to undo)");
+    println!();
+    println!("  MAINTENANCE:");
+    println!("    VACUUM             (or VACUUM users to clean up one table)");
+    println!();
     println!("Notes:");
     println!("  - All SQL keywords are case-insensitive");
     println!("  - String values must be in single quotes");
@@ -1,53 +1,594 @@
 // Query Executor
 // This module executes parsed queries against the database
+//
+// Several sessions (QueryExecutors) can share one database. Each statement
+// runs while holding the database lock, but a transaction can span many
+// statements - MVCC snapshots (see storage/mvcc.rs) keep each transaction's
+// view of the data consistent while other sessions keep writing.
 
-use super::parser::{Query, WhereClause};
-use crate::storage::{table::Table, Row};
+use super::foreign_keys;
+use super::parser::Query;
+use crate::storage::buffer::{self, BufferPool, BufferPoolConfig, BufferStats, SharedBufferPool};
+use crate::storage::catalog::{Catalog, TableMeta};
+use crate::storage::expr::Expr;
+use crate::storage::mvcc::{Snapshot, TransactionManager, TxnId};
+use crate::storage::sequence::{SequenceSession, Sequences};
+use crate::storage::wal::{LogEntry, LogRecord, SharedWal, Wal};
+use crate::storage::{table::Table, Row, Schema, Value};
 use anyhow::{anyhow, Result};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
 
-/// The query executor manages all tables and executes queries
-/// This is the main interface to the database
-pub struct QueryExecutor {
+/// Name of the write-ahead log inside a database directory
+const WAL_FILE: &str = "wal.log";
+
+/// Once the WAL grows past this many bytes we take a checkpoint
+const CHECKPOINT_THRESHOLD: u64 = 4 * 1024 * 1024;
+
+/// The state shared by every session of a database
+struct Database {
     /// HashMap storing all tables by name
     /// The String is the table name, the Table is the table itself
     tables: HashMap<String, Table>,
+    /// Directory holding the catalog and page files (None = in-memory database)
+    data_dir: Option<PathBuf>,
+    /// Write-ahead log shared by all tables (persistent databases only)
+    wal: Option<SharedWal>,
+    /// Caches the pages of all tables (persistent databases only)
+    pool: Option<SharedBufferPool>,
+    /// Hands out transaction IDs and snapshots
+    txns: TransactionManager,
+    /// The sequences numbering rows (SERIAL columns and CREATE SEQUENCE)
+    sequences: Sequences,
+}
+
+/// The query executor manages all tables and executes queries
+/// This is the main interface to the database
+///
+/// A QueryExecutor is one session: it has at most one open transaction.
+/// Use session() to open more sessions on the same database.
+pub struct QueryExecutor {
+    /// The database, shared with the other sessions
+    /// Arc = shared ownership, Mutex = one statement at a time
+    db: Arc<Mutex<Database>>,
+    /// The snapshot of the transaction opened with BEGIN, if any
+    /// Outside of BEGIN ... COMMIT every statement runs in its own transaction
+    transaction: Option<Snapshot>,
+    /// The last number this session got from each sequence (for currval)
+    last_values: HashMap<String, i64>,
+}
+
+/// A table borrowed from the database
+/// The database stays locked for as long as this reference is alive
+pub struct TableRef<'a> {
+    db: MutexGuard<'a, Database>,
+    name: String,
+}
+
+impl Deref for TableRef<'_> {
+    type Target = Table;
+
+    fn deref(&self) -> &Table {
+        &self.db.tables[&self.name]
+    }
 }
 
 impl QueryExecutor {
-    /// Create a new query executor (empty database)
+    /// Create a new query executor (empty in-memory database)
     pub fn new() -> Self {
-        Self {
+        Self::from_database(Database {
             tables: HashMap::new(),
+            data_dir: None,
+            wal: None,
+            pool: None,
+            txns: TransactionManager::new(0),
+            sequences: Sequences::default(),
+        })
+    }
+
+    /// Open a persistent database stored in a directory
+    /// The directory is created if needed; every table in its catalog is reloaded
+    ///
+    /// If the previous run crashed, the changes recorded in the write-ahead log
+    /// are replayed before the database is used (crash recovery)
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_with_buffer_pool(path, BufferPoolConfig::default())
+    }
+
+    /// Open a persistent database, choosing how much memory its buffer pool
+    /// may use and how it evicts pages
+    pub fn open_with_buffer_pool<P: AsRef<Path>>(
+        path: P,
+        config: BufferPoolConfig,
+    ) -> Result<Self> {
+        let data_dir = path.as_ref().to_path_buf();
+        fs::create_dir_all(&data_dir)
+            .map_err(|e| anyhow!("Cannot create '{}': {}", data_dir.display(), e))?;
+
+        let pool = Arc::new(Mutex::new(BufferPool::new(config)));
+        let catalog = Catalog::load(&data_dir)?;
+        let mut tables = HashMap::new();
+        for meta in catalog.tables {
+            let path = Catalog::table_path(&data_dir, &meta.name);
+            let table = Table::open(
+                meta.name.clone(),
+                meta.schema,
+                &path,
+                Arc::clone(&pool),
+                &meta.indexes,
+            )?;
+            tables.insert(meta.name, table);
+        }
+
+        // Crash recovery, part 1: replay the log
+        let mut wal = Wal::open(&data_dir.join(WAL_FILE))?;
+        let entries = wal.recover()?;
+        let last_logged = entries.iter().map(|e| e.txn_id).max().unwrap_or(0);
+        Database::redo_all(&mut tables, &entries)?;
+
+        // New transactions must get IDs above every ID already stored in a row
+        // version, or they would see (and be seen by) the wrong changes.
+        // The same goes for LSNs and the LSNs stamped on pages.
+        let mut last_txn_id = last_logged;
+        for table in tables.values() {
+            last_txn_id = last_txn_id.max(table.max_txn_id()?);
+            wal.advance_lsn(table.max_lsn());
+        }
+
+        let wal = Arc::new(Mutex::new(wal));
+        for table in tables.values_mut() {
+            table.set_wal(Arc::clone(&wal));
+        }
+
+        let mut db = Database {
+            tables,
+            data_dir: Some(data_dir),
+            wal: Some(wal),
+            pool: Some(pool),
+            txns: TransactionManager::new(last_txn_id),
+            sequences: Sequences::load(catalog.sequences),
+        };
+
+        // Part 2: roll back the transactions the crash interrupted, exactly
+        // like ROLLBACK would have
+        let unfinished: BTreeSet<TxnId> = db
+            .tables
+            .values()
+            .flat_map(|table| table.pending_transactions())
+            .collect();
+        for txn_id in unfinished {
+            db.end_transaction(txn_id, false)?;
+        }
+
+        let executor = Self::from_database(db);
+        executor.checkpoint()?;
+        Ok(executor)
+    }
+
+    fn from_database(db: Database) -> Self {
+        Self {
+            db: Arc::new(Mutex::new(db)),
+            transaction: None,
+            last_values: HashMap::new(),
         }
     }
 
+    /// Open another session on the same database
+    /// Sessions can be moved to other threads; each has its own transaction
+    pub fn session(&self) -> Self {
+        Self {
+            db: Arc::clone(&self.db),
+            transaction: None,
+            last_values: HashMap::new(),
+        }
+    }
+
+    /// Lock the shared database for the duration of one operation
+    fn lock(&self) -> Result<MutexGuard<'_, Database>> {
+        self.db
+            .lock()
+            .map_err(|_| anyhow!("Database lock poisoned"))
+    }
+
     /// Execute a query and return the result
     /// Returns a QueryResult which can be rows, a count, or a message
+    ///
+    /// Outside of BEGIN ... COMMIT each statement is its own transaction
+    /// ("autocommit"), so a statement that fails half-way changes nothing.
+    /// Inside a transaction, a failing statement rolls back the whole transaction.
     pub fn execute(&mut self, query: Query) -> Result<QueryResult> {
         match query {
-            Query::CreateTable { name, schema } => {
+            Query::Begin => return self.begin(),
+            Query::Commit => return self.commit(),
+            Query::Rollback => return self.rollback(),
+            Query::CreateTable { .. }
+            | Query::CreateIndex { .. }
+            | Query::CreateSequence { .. }
+                if self.transaction.is_some() =>
+            {
+                // The catalog is not transactional, so we keep DDL out of transactions
+                return Err(anyhow!(
+                    "CREATE statements cannot be used inside a transaction"
+                ));
+            }
+            Query::Vacuum { table_name } => return self.vacuum(table_name.as_deref()),
+            _ => {}
+        }
+
+        let shared = Arc::clone(&self.db);
+        let mut db = shared
+            .lock()
+            .map_err(|_| anyhow!("Database lock poisoned"))?;
+
+        let autocommit = self.transaction.is_none();
+        let snapshot = match &self.transaction {
+            Some(snapshot) => snapshot.clone(),
+            None => db.txns.begin(),
+        };
+
+        let result = db.execute_query(query, &snapshot, &mut self.last_values);
+
+        // Numbers handed out by a sequence are recorded before the rows
+        // using them can commit, even if the statement failed (see sequence.rs)
+        let result = db.save_sequences().and(result);
+
+        let result = match result {
+            Ok(result) => result,
+            Err(e) => {
+                self.transaction = None;
+                db.end_transaction(snapshot.txn_id, false)?;
+                if autocommit {
+                    return Err(e);
+                }
+                return Err(anyhow!("{} (transaction rolled back)", e));
+            }
+        };
+
+        if autocommit {
+            db.end_transaction(snapshot.txn_id, true)?;
+
+            // Keep the log from growing forever. While another session has a
+            // transaction open, the log still holds what we'd need to roll it
+            // back after a crash, so the checkpoint waits.
+            if db.wal_size() > CHECKPOINT_THRESHOLD && !db.txns.has_active() {
+                db.checkpoint()?;
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// BEGIN: start a multi-statement transaction
+    /// The snapshot is taken right away, so every statement of the transaction
+    /// sees the database as it was at BEGIN ("repeatable read")
+    fn begin(&mut self) -> Result<QueryResult> {
+        if self.transaction.is_some() {
+            return Err(anyhow!("A transaction is already in progress"));
+        }
+
+        let snapshot = self.lock()?.txns.begin();
+        self.transaction = Some(snapshot);
+        Ok(QueryResult::Message("Transaction started".to_string()))
+    }
+
+    /// COMMIT: make every change of the transaction permanent
+    fn commit(&mut self) -> Result<QueryResult> {
+        let snapshot = self
+            .transaction
+            .take()
+            .ok_or_else(|| anyhow!("No transaction is in progress"))?;
+
+        self.lock()?.end_transaction(snapshot.txn_id, true)?;
+        Ok(QueryResult::Message("Transaction committed".to_string()))
+    }
+
+    /// ROLLBACK: undo every change of the transaction
+    fn rollback(&mut self) -> Result<QueryResult> {
+        let snapshot = self
+            .transaction
+            .take()
+            .ok_or_else(|| anyhow!("No transaction is in progress"))?;
+
+        self.lock()?.end_transaction(snapshot.txn_id, false)?;
+        Ok(QueryResult::Message("Transaction rolled back".to_string()))
+    }
+
+    /// VACUUM: remove the row versions that no transaction can see anymore,
+    /// from the pages and from the indexes
+    fn vacuum(&mut self, table_name: Option<&str>) -> Result<QueryResult> {
+        if self.transaction.is_some() {
+            return Err(anyhow!("VACUUM cannot be used inside a transaction"));
+        }
+
+        let mut db = self.lock()?;
+        let horizon = db.txns.vacuum_horizon();
+
+        let mut removed = 0;
+        match table_name {
+            Some(name) => {
+                let table = db
+                    .tables
+                    .get_mut(name)
+                    .ok_or_else(|| anyhow!("Table '{}' not found", name))?;
+                removed += table.vacuum(horizon)?;
+            }
+            None => {
+                for table in db.tables.values_mut() {
+                    removed += table.vacuum(horizon)?;
+                }
+            }
+        }
+
+        Ok(QueryResult::Message(format!(
+            "Vacuum removed {} dead row version(s)",
+            removed
+        )))
+    }
+
+    /// Work out a constant expression, like a value of an INSERT
+    /// It can take numbers from the database's sequences: nextval('name')
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<Value> {
+        let shared = Arc::clone(&self.db);
+        let mut db = shared
+            .lock()
+            .map_err(|_| anyhow!("Database lock poisoned"))?;
+
+        let mut session = SequenceSession {
+            sequences: &mut db.sequences,
+            last_values: &mut self.last_values,
+        };
+        let value = expr.evaluate_with(
+            &Schema::new(Vec::new()),
+            &Row { values: Vec::new() },
+            &mut session,
+        );
+        db.save_sequences()?;
+        value
+    }
+
+    /// Check whether a BEGIN ... COMMIT transaction is open
+    pub fn in_transaction(&self) -> bool {
+        self.transaction.is_some()
+    }
+
+    /// Write every page to stable storage and empty the write-ahead log
+    /// After a checkpoint, recovery no longer needs the old log records
+    pub fn checkpoint(&self) -> Result<()> {
+        let mut db = self.lock()?;
+        if db.txns.has_active() {
+            // The log still holds what we need to roll the transaction back
+            return Err(anyhow!(
+                "Cannot checkpoint while a transaction is in progress"
+            ));
+        }
+        db.checkpoint()
+    }
+
+    /// Get the buffer pool's counters (None for in-memory databases)
+    pub fn buffer_stats(&self) -> Option<BufferStats> {
+        let db = self.db.lock().ok()?;
+        let pool = buffer::lock(db.pool.as_ref()?).ok()?;
+        Some(pool.stats())
+    }
+
+    /// Get a reference to a table (useful for direct access)
+    /// The whole database is locked until the reference is dropped
+    pub fn get_table(&self, name: &str) -> Option<TableRef<'_>> {
+        let db = self.db.lock().ok()?;
+        db.tables.contains_key(name).then(|| TableRef {
+            db,
+            name: name.to_string(),
+        })
+    }
+
+    /// List all tables in the database
+    pub fn list_tables(&self) -> Vec<String> {
+        match self.db.lock() {
+            Ok(db) => db.tables.keys().cloned().collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+impl Database {
+    /// Replay the write-ahead log after a crash
+    ///
+    /// We "repeat history": every change is redone in log order, on every page
+    /// that hasn't seen it yet. That includes the compensation records written
+    /// while rolling back, so a rolled-back transaction is undone again too.
+    /// Afterwards the tables know which transactions never finished.
+    fn redo_all(tables: &mut HashMap<String, Table>, entries: &[LogEntry]) -> Result<()> {
+        for entry in entries {
+            match entry.record.table() {
+                Some(name) => {
+                    let table = tables
+                        .get_mut(name)
+                        .ok_or_else(|| anyhow!("WAL refers to unknown table '{}'", name))?;
+                    table.redo(entry)?;
+                }
+                // Commit or Abort: nothing is left to roll back
+                None => {
+                    for table in tables.values_mut() {
+                        table.commit(entry.txn_id);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Commit or roll back a transaction
+    fn end_transaction(&mut self, txn_id: TxnId, commit: bool) -> Result<()> {
+        // Read-only transactions leave no trace in the log
+        let changed = self.tables.values().any(|t| t.has_pending_changes(txn_id));
+
+        if commit {
+            // The Commit record is the point of no return: once it is on disk
+            // recovery will keep the changes
+            if changed {
+                self.append_to_wal(txn_id, LogRecord::Commit)?;
+            }
+            self.txns.finish(txn_id);
+
+            // If no other transaction can still see the rows we deleted,
+            // free their slots right away instead of waiting for VACUUM
+            let horizon = self.txns.vacuum_horizon();
+            for table in self.tables.values_mut() {
+                let deleted = table.commit(txn_id);
+                table.prune(&deleted, horizon)?;
+            }
+        } else {
+            // Undo the pages first, then record that the undo happened
+            for table in self.tables.values_mut() {
+                table.rollback(txn_id)?;
+            }
+            if changed {
+                self.append_to_wal(txn_id, LogRecord::Abort)?;
+            }
+            self.txns.finish(txn_id);
+        }
+
+        Ok(())
+    }
+
+    /// Append a record to the write-ahead log (no-op for in-memory databases)
+    fn append_to_wal(&self, txn_id: TxnId, record: LogRecord) -> Result<()> {
+        if let Some(wal) = &self.wal {
+            wal.lock()
+                .map_err(|_| anyhow!("WAL lock poisoned"))?
+                .append(txn_id, &record)?;
+        }
+        Ok(())
+    }
+
+    /// Write every page to stable storage and empty the write-ahead log
+    fn checkpoint(&mut self) -> Result<()> {
+        let Some(wal) = &self.wal else {
+            return Ok(());
+        };
+
+        // Pages first, then the log - never the other way around
+        for table in self.tables.values_mut() {
+            table.sync()?;
+        }
+        wal.lock()
+            .map_err(|_| anyhow!("WAL lock poisoned"))?
+            .truncate()
+    }
+
+    /// Current size of the write-ahead log in bytes (0 for in-memory databases)
+    fn wal_size(&self) -> u64 {
+        self.wal
+            .as_ref()
+            .and_then(|wal| wal.lock().ok().map(|wal| wal.size()))
+            .unwrap_or(0)
+    }
+
+    /// Dispatch a parsed query to the right table operation
+    /// last_values is the session's last number from each sequence (currval)
+    fn execute_query(
+        &mut self,
+        query: Query,
+        snapshot: &Snapshot,
+        last_values: &mut HashMap<String, i64>,
+    ) -> Result<QueryResult> {
+        match query {
+            Query::CreateTable {
+                name,
+                mut schema,
+                sequences,
+            } => {
                 // Check if table already exists
                 if self.tables.contains_key(&name) {
                     return Err(anyhow!("Table '{}' already exists", name));
                 }
+                if let Some(sequence) = sequences.iter().find(|s| self.sequences.contains(&s.name))
+                {
+                    return Err(anyhow!("Sequence '{}' already exists", sequence.name));
+                }
+                foreign_keys::resolve(&self.tables, &name, &mut schema)?;
 
-                // Create the table
-                let table = Table::new(name.clone(), schema);
+                // Create the table (backed by its own page file if we're persistent)
+                let mut table = match (&self.data_dir, &self.pool) {
+                    (Some(dir), Some(pool)) => Table::open(
+                        name.clone(),
+                        schema,
+                        &Catalog::table_path(dir, &name),
+                        Arc::clone(pool),
+                        &[],
+                    )?,
+                    _ => Table::new(name.clone(), schema)?,
+                };
+                if let Some(wal) = &self.wal {
+                    table.set_wal(Arc::clone(wal));
+                }
                 self.tables.insert(name.clone(), table);
+                for sequence in sequences {
+                    self.sequences.create(sequence)?;
+                }
+                self.save_catalog()?;
 
                 Ok(QueryResult::Message(format!("Table '{}' created", name)))
             }
 
-            Query::Insert { table_name, values } => {
+            Query::CreateSequence {
+                sequence,
+                if_not_exists,
+            } => {
+                if if_not_exists && self.sequences.contains(&sequence.name) {
+                    return Ok(QueryResult::Message(format!(
+                        "Sequence '{}' already exists, skipping",
+                        sequence.name
+                    )));
+                }
+                let name = sequence.name.clone();
+                self.sequences.create(sequence)?;
+                self.save_catalog()?;
+
+                Ok(QueryResult::Message(format!("Sequence '{}' created", name)))
+            }
+
+            Query::Insert {
+                table_name,
+                columns,
+                values,
+            } => {
                 // Get the table (mut reference so we can modify it)
                 let table = self
                     .tables
                     .get_mut(&table_name)
                     .ok_or_else(|| anyhow!("Table '{}' not found", table_name))?;
 
-                // Insert the row
-                table.insert(values)?;
+                // Work out the values (they can take numbers from sequences),
+                // insert the row, then check the rows it references exist
+                let columns = columns.unwrap_or_else(|| {
+                    table
+                        .get_schema()
+                        .columns
+                        .iter()
+                        .map(|c| c.name.clone())
+                        .collect()
+                });
+                let mut session = SequenceSession {
+                    sequences: &mut self.sequences,
+                    last_values,
+                };
+                let no_row = Row { values: Vec::new() };
+                let values = values
+                    .into_iter()
+                    .map(|value| {
+                        value
+                            .map(|expr| {
+                                expr.evaluate_with(table.get_schema(), &no_row, &mut session)
+                            })
+                            .transpose()
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let row = table.insert_columns(&columns, values, &mut session, snapshot)?;
+                foreign_keys::check_references(&self.tables, &table_name, &[row], snapshot)?;
 
                 Ok(QueryResult::Message(format!(
                     "1 row inserted into '{}'",
@@ -57,7 +598,9 @@ impl QueryExecutor {
 
             Query::Select {
                 table_name,
+                columns,
                 where_clause,
+                order_by,
             } => {
                 // Get the table
                 let table = self
@@ -65,22 +608,40 @@ impl QueryExecutor {
                     .get(&table_name)
                     .ok_or_else(|| anyhow!("Table '{}' not found", table_name))?;
 
-                // Execute the select
-                let rows = match where_clause {
-                    Some(WhereClause { column, value }) => {
-                        table.select(Some(&column), Some(&value))?
-                    }
-                    None => table.select(None, None)?,
+                // Execute the select (checking the columns it sorts by exist,
+                // even if no row was found)
+                let schema = table.get_schema();
+                for target in order_by.iter().flat_map(|key| key.expr.columns()) {
+                    target.index_in(schema)?;
+                }
+                let rows = table.select(where_clause.as_ref(), &order_by, snapshot)?;
+
+                let Some(columns) = columns else {
+                    return Ok(QueryResult::Rows {
+                        rows,
+                        column_names: schema.columns.iter().map(|c| c.name.clone()).collect(),
+                    });
                 };
 
+                // Work out the selected columns for each row (checking the
+                // columns they use exist, even if no row was found)
+                for target in columns.iter().flat_map(|column| column.expr.columns()) {
+                    target.index_in(schema)?;
+                }
+                let rows = rows
+                    .into_iter()
+                    .map(|row| {
+                        let values = columns
+                            .iter()
+                            .map(|column| column.expr.evaluate(schema, &row))
+                            .collect::<Result<Vec<_>>>()?;
+                        Ok(Row { values })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
                 Ok(QueryResult::Rows {
                     rows,
-                    column_names: table
-                        .get_schema()
-                        .columns
-                        .iter()
-                        .map(|c| c.name.clone())
-                        .collect(),
+                    column_names: columns.into_iter().map(|column| column.name).collect(),
                 })
             }
 
@@ -95,12 +656,9 @@ impl QueryExecutor {
                     .get_mut(&table_name)
                     .ok_or_else(|| anyhow!("Table '{}' not found", table_name))?;
 
-                let count = table.update(
-                    &where_clause.column,
-                    &where_clause.value,
-                    &set_column,
-                    set_value,
-                )?;
+                let updated = table.update(&where_clause, &set_column, set_value, snapshot)?;
+                let count = updated.len();
+                foreign_keys::after_update(&mut self.tables, &table_name, &updated, snapshot)?;
 
                 Ok(QueryResult::Message(format!(
                     "{} row(s) updated in '{}'",
@@ -117,7 +675,9 @@ impl QueryExecutor {
                     .get_mut(&table_name)
                     .ok_or_else(|| anyhow!("Table '{}' not found", table_name))?;
 
-                let count = table.delete(&where_clause.column, &where_clause.value)?;
+                let deleted = table.delete(&where_clause, snapshot)?;
+                let count = deleted.len();
+                foreign_keys::after_delete(&mut self.tables, &table_name, &deleted, snapshot)?;
 
                 Ok(QueryResult::Message(format!(
                     "{} row(s) deleted from '{}'",
@@ -125,33 +685,69 @@ impl QueryExecutor {
                 )))
             }
 
+            Query::Begin | Query::Commit | Query::Rollback | Query::Vacuum { .. } => {
+                unreachable!("transaction control and VACUUM are handled by execute()")
+            }
+
             Query::CreateIndex {
                 table_name,
-                column_name,
+                columns,
+                unique,
             } => {
                 let table = self
                     .tables
                     .get_mut(&table_name)
                     .ok_or_else(|| anyhow!("Table '{}' not found", table_name))?;
 
-                table.create_index(&column_name)?;
+                // A unique index is a UNIQUE constraint: it is checked against
+                // the existing rows and kept in the schema
+                if unique {
+                    table.add_unique(&columns)?;
+                } else {
+                    table.create_index(&columns[0])?;
+                }
+                self.save_catalog()?;
 
                 Ok(QueryResult::Message(format!(
-                    "Index created on '{}.{}'",
-                    table_name, column_name
+                    "{} created on '{}.{}'",
+                    if unique { "Unique index" } else { "Index" },
+                    table_name,
+                    columns.join(", ")
                 )))
             }
         }
     }
 
-    /// Get a reference to a table (useful for direct access)
-    pub fn get_table(&self, name: &str) -> Option<&Table> {
-        self.tables.get(name)
+    /// Save the catalog if a sequence reserved new numbers since it was last saved
+    fn save_sequences(&mut self) -> Result<()> {
+        if self.sequences.take_unsaved() {
+            self.save_catalog()?;
+        }
+        Ok(())
     }
 
-    /// List all tables in the database
-    pub fn list_tables(&self) -> Vec<String> {
-        self.tables.keys().cloned().collect()
+    /// Write the catalog (table schemas, indexes and sequences) for a persistent database
+    fn save_catalog(&self) -> Result<()> {
+        let Some(dir) = &self.data_dir else {
+            return Ok(());
+        };
+
+        let mut tables: Vec<TableMeta> = self
+            .tables
+            .values()
+            .map(|table| TableMeta {
+                name: table.name.clone(),
+                schema: table.schema.clone(),
+                indexes: table.indexed_columns(),
+            })
+            .collect();
+        tables.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Catalog {
+            tables,
+            sequences: self.sequences.list(),
+        }
+        .save(dir)
     }
 }
 
@@ -190,49 +786,49 @@ impl QueryResult {
                 let mut result = String::new();
 
                 // Header row
-                result.push_str("┌");
+                result.push('┌');
                 for (i, width) in widths.iter().enumerate() {
                     result.push_str(&"─".repeat(width + 2));
                     if i < widths.len() - 1 {
-                        result.push_str("┬");
+                        result.push('┬');
                     }
                 }
                 result.push_str("┐\n");
 
                 // Column names
-                result.push_str("│");
+                result.push('│');
                 for (name, width) in column_names.iter().zip(&widths) {
                     result.push_str(&format!(" {:<width$} ", name, width = width));
-                    result.push_str("│");
+                    result.push('│');
                 }
                 result.push('\n');
 
                 // Separator
-                result.push_str("├");
+                result.push('├');
                 for (i, width) in widths.iter().enumerate() {
                     result.push_str(&"─".repeat(width + 2));
                     if i < widths.len() - 1 {
-                        result.push_str("┼");
+                        result.push('┼');
                     }
                 }
                 result.push_str("┤\n");
 
                 // Data rows
                 for row in rows {
-                    result.push_str("│");
+                    result.push('│');
                     for (value, width) in row.values.iter().zip(&widths) {
                         result.push_str(&format!(" {:<width$} ", value.to_string(), width = width));
-                        result.push_str("│");
+                        result.push('│');
                     }
                     result.push('\n');
                 }
 
                 // Bottom border
-                result.push_str("└");
+                result.push('└');
                 for (i, width) in widths.iter().enumerate() {
                     result.push_str(&"─".repeat(width + 2));
                     if i < widths.len() - 1 {
-                        result.push_str("┴");
+                        result.push('┴');
                     }
                 }
                 result.push_str("┘\n");
@@ -250,3 +846,1814 @@ impl Default for QueryExecutor {
         Self::new()
     }
 }
+
+impl Drop for QueryExecutor {
+    /// A session that goes away with a transaction still open rolls it back
+    fn drop(&mut self) {
+        if let Some(snapshot) = self.transaction.take() {
+            if let Ok(mut db) = self.db.lock() {
+                let _ = db.end_transaction(snapshot.txn_id, false);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::QueryParser;
+    use crate::storage::Value;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("rustydb-executor-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&path);
+        path
+    }
+
+    fn run(db: &mut QueryExecutor, sql: &str) -> QueryResult {
+        db.execute(QueryParser::parse(sql).unwrap()).unwrap()
+    }
+
+    fn rows(db: &mut QueryExecutor, sql: &str) -> Vec<Row> {
+        match run(db, sql) {
+            QueryResult::Rows { rows, .. } => rows,
+            other => panic!("expected rows, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_open_reloads_tables_and_indexes() {
+        let dir = temp_dir("reload");
+
+        {
+            let mut db = QueryExecutor::open(&dir).unwrap();
+            run(
+                &mut db,
+                "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)",
+            );
+            run(&mut db, "CREATE INDEX ON users (name)");
+            for i in 1..=250 {
+                run(
+                    &mut db,
+                    &format!("INSERT INTO users VALUES ({}, 'User{}')", i, i),
+                );
+            }
+            run(&mut db, "UPDATE users SET name = 'Bob' WHERE id = 2");
+        }
+
+        let mut db = QueryExecutor::open(&dir).unwrap();
+        assert_eq!(db.list_tables(), vec!["users".to_string()]);
+        assert_eq!(db.get_table("users").unwrap().row_count().unwrap(), 250);
+        assert_eq!(
+            db.get_table("users").unwrap().indexed_columns(),
+            vec!["id".to_string(), "name".to_string()]
+        );
+
+        let bob = rows(&mut db, "SELECT * FROM users WHERE name = 'Bob'");
+        assert_eq!(bob.len(), 1);
+        assert_eq!(bob[0].values[0], crate::storage::Value::Integer(2));
+
+        // The primary key index was rebuilt, so duplicates are still rejected
+        let dup = QueryParser::parse("INSERT INTO users VALUES (250, 'Again')").unwrap();
+        assert!(db.execute(dup).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_recovery_replays_wal_after_lost_page_writes() {
+        let dir = temp_dir("recovery");
+
+        {
+            let mut db = QueryExecutor::open(&dir).unwrap();
+            run(
+                &mut db,
+                "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)",
+            );
+            run(&mut db, "INSERT INTO users VALUES (1, 'Alice')");
+            run(&mut db, "INSERT INTO users VALUES (2, 'Bob')");
+            run(&mut db, "UPDATE users SET name = 'Robert' WHERE id = 2");
+            // Crash: the executor is dropped without a checkpoint
+        }
+
+        // Simulate the page writes never reaching the disk
+        fs::write(dir.join("users.tbl"), b"").unwrap();
+
+        let mut db = QueryExecutor::open(&dir).unwrap();
+        let all = rows(&mut db, "SELECT * FROM users");
+        assert_eq!(all.len(), 2);
+        let bob = rows(&mut db, "SELECT * FROM users WHERE id = 2");
+        assert_eq!(
+            bob[0].values[1],
+            crate::storage::Value::Text("Robert".into())
+        );
+
+        // Recovery took a checkpoint, so the log is empty again
+        assert_eq!(fs::metadata(dir.join(WAL_FILE)).unwrap().len(), 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_recovery_ignores_torn_wal_tail() {
+        let dir = temp_dir("torn-tail");
+
+        {
+            let mut db = QueryExecutor::open(&dir).unwrap();
+            run(&mut db, "CREATE TABLE t (id INTEGER PRIMARY KEY)");
+            run(&mut db, "INSERT INTO t VALUES (1)");
+        }
+
+        // Half a record header plus garbage, as left by a crash mid-append
+        let mut wal = fs::read(dir.join(WAL_FILE)).unwrap();
+        wal.extend_from_slice(&[42, 0, 0, 0, 7, 7]);
+        fs::write(dir.join(WAL_FILE), wal).unwrap();
+
+        let mut db = QueryExecutor::open(&dir).unwrap();
+        assert_eq!(rows(&mut db, "SELECT * FROM t").len(), 1);
+        run(&mut db, "INSERT INTO t VALUES (2)");
+        drop(db);
+
+        let mut db = QueryExecutor::open(&dir).unwrap();
+        assert_eq!(rows(&mut db, "SELECT * FROM t").len(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn try_run(db: &mut QueryExecutor, sql: &str) -> Result<QueryResult> {
+        db.execute(QueryParser::parse(sql).unwrap())
+    }
+
+    fn setup_accounts(db: &mut QueryExecutor) {
+        run(
+            db,
+            "CREATE TABLE accounts (id INTEGER PRIMARY KEY, balance INTEGER)",
+        );
+        run(db, "CREATE TABLE audit (id INTEGER PRIMARY KEY, note TEXT)");
+        run(db, "INSERT INTO accounts VALUES (1, 100)");
+        run(db, "INSERT INTO accounts VALUES (2, 50)");
+    }
+
+    #[test]
+    fn test_rollback_undoes_changes_across_tables() {
+        let mut db = QueryExecutor::new();
+        setup_accounts(&mut db);
+
+        run(&mut db, "BEGIN");
+        assert!(db.in_transaction());
+        run(&mut db, "UPDATE accounts SET balance = 70 WHERE id = 1");
+        run(&mut db, "INSERT INTO accounts VALUES (3, 30)");
+        run(&mut db, "DELETE FROM accounts WHERE id = 2");
+        run(&mut db, "INSERT INTO audit VALUES (1, 'transfer')");
+
+        // Changes are visible inside the transaction
+        assert_eq!(
+            rows(&mut db, "SELECT * FROM accounts WHERE id = 3").len(),
+            1
+        );
+
+        run(&mut db, "ROLLBACK");
+        assert!(!db.in_transaction());
+
+        let all = rows(&mut db, "SELECT * FROM accounts");
+        assert_eq!(all.len(), 2);
+        assert!(rows(&mut db, "SELECT * FROM audit").is_empty());
+
+        // The indexes were restored too
+        assert!(rows(&mut db, "SELECT * FROM accounts WHERE id = 3").is_empty());
+        assert_eq!(
+            rows(&mut db, "SELECT * FROM accounts WHERE id = 2").len(),
+            1
+        );
+        let one = rows(&mut db, "SELECT * FROM accounts WHERE id = 1");
+        assert_eq!(one[0].values[1], crate::storage::Value::Integer(100));
+        run(&mut db, "INSERT INTO accounts VALUES (3, 30)");
+    }
+
+    #[test]
+    fn test_commit_keeps_changes() {
+        let mut db = QueryExecutor::new();
+        setup_accounts(&mut db);
+
+        run(&mut db, "BEGIN TRANSACTION");
+        run(&mut db, "UPDATE accounts SET balance = 0 WHERE id = 2");
+        run(&mut db, "INSERT INTO audit VALUES (1, 'emptied')");
+        run(&mut db, "COMMIT");
+
+        let two = rows(&mut db, "SELECT * FROM accounts WHERE id = 2");
+        assert_eq!(two[0].values[1], crate::storage::Value::Integer(0));
+        assert_eq!(rows(&mut db, "SELECT * FROM audit").len(), 1);
+
+        // Nothing left to roll back
+        assert!(try_run(&mut db, "ROLLBACK").is_err());
+    }
+
+    #[test]
+    fn test_failed_statement_rolls_back_transaction() {
+        let mut db = QueryExecutor::new();
+        setup_accounts(&mut db);
+
+        run(&mut db, "BEGIN");
+        run(&mut db, "INSERT INTO audit VALUES (1, 'first')");
+        let err = try_run(&mut db, "INSERT INTO accounts VALUES (1, 999)").unwrap_err();
+        assert!(err.to_string().contains("transaction rolled back"));
+
+        assert!(!db.in_transaction());
+        assert!(rows(&mut db, "SELECT * FROM audit").is_empty());
+    }
+
+    #[test]
+    fn test_transaction_control_errors() {
+        let mut db = QueryExecutor::new();
+        assert!(try_run(&mut db, "COMMIT").is_err());
+        run(&mut db, "BEGIN");
+        assert!(try_run(&mut db, "BEGIN").is_err());
+        assert!(try_run(&mut db, "CREATE TABLE t (id INTEGER)").is_err());
+        assert!(db.in_transaction());
+        run(&mut db, "COMMIT");
+    }
+
+    #[test]
+    fn test_recovery_rolls_back_unfinished_transactions() {
+        let dir = temp_dir("txn-recovery");
+
+        {
+            let mut db = QueryExecutor::open(&dir).unwrap();
+            setup_accounts(&mut db);
+
+            // Rolled back at runtime, then followed by committed work
+            run(&mut db, "BEGIN");
+            run(&mut db, "UPDATE accounts SET balance = 1 WHERE id = 1");
+            run(&mut db, "ROLLBACK");
+            run(&mut db, "UPDATE accounts SET balance = 90 WHERE id = 1");
+
+            // Committed transaction
+            run(&mut db, "BEGIN");
+            run(&mut db, "INSERT INTO audit VALUES (1, 'kept')");
+            run(&mut db, "COMMIT");
+
+            // Still open when we "crash"
+            run(&mut db, "BEGIN");
+            run(&mut db, "INSERT INTO accounts VALUES (3, 30)");
+            run(&mut db, "UPDATE accounts SET balance = 0 WHERE id = 2");
+            run(&mut db, "INSERT INTO audit VALUES (2, 'lost')");
+
+            // Crash: skip Drop, which would roll the transaction back cleanly
+            std::mem::forget(db);
+        }
+
+        let mut db = QueryExecutor::open(&dir).unwrap();
+        assert_eq!(rows(&mut db, "SELECT * FROM accounts").len(), 2);
+        assert_eq!(rows(&mut db, "SELECT * FROM audit").len(), 1);
+        let one = rows(&mut db, "SELECT * FROM accounts WHERE id = 1");
+        assert_eq!(one[0].values[1], crate::storage::Value::Integer(90));
+        let two = rows(&mut db, "SELECT * FROM accounts WHERE id = 2");
+        assert_eq!(two[0].values[1], crate::storage::Value::Integer(50));
+        assert!(rows(&mut db, "SELECT * FROM accounts WHERE id = 3").is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_small_buffer_pool_evicts_and_recovers() {
+        let dir = temp_dir("buffer-pool");
+        let config = BufferPoolConfig {
+            memory_budget: 0,
+            policy: buffer::EvictionPolicy::Clock,
+        };
+        let body = "x".repeat(500);
+
+        {
+            let mut db = QueryExecutor::open_with_buffer_pool(&dir, config).unwrap();
+            run(
+                &mut db,
+                "CREATE TABLE logs (id INTEGER PRIMARY KEY, body TEXT)",
+            );
+            for i in 0..200 {
+                run(
+                    &mut db,
+                    &format!("INSERT INTO logs VALUES ({}, '{}')", i, body),
+                );
+            }
+
+            // Far more pages than the pool holds: scanning has to evict
+            assert_eq!(rows(&mut db, "SELECT * FROM logs").len(), 200);
+            let stats = db.buffer_stats().unwrap();
+            assert!(stats.misses > 0 && stats.evictions > 0);
+
+            // Dirty pages of an unfinished transaction get evicted to disk...
+            run(&mut db, "BEGIN");
+            for i in 200..300 {
+                run(
+                    &mut db,
+                    &format!("INSERT INTO logs VALUES ({}, '{}')", i, body),
+                );
+            }
+            run(&mut db, "DELETE FROM logs WHERE id = 5");
+            assert!(db.buffer_stats().unwrap().writes > 0);
+            std::mem::forget(db);
+        }
+
+        // ... and are rolled back after the crash
+        let mut db = QueryExecutor::open_with_buffer_pool(&dir, config).unwrap();
+        assert_eq!(rows(&mut db, "SELECT * FROM logs").len(), 200);
+        assert_eq!(rows(&mut db, "SELECT * FROM logs WHERE id = 5").len(), 1);
+        assert!(rows(&mut db, "SELECT * FROM logs WHERE id = 250").is_empty());
+        assert!(QueryExecutor::new().buffer_stats().is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn balance(db: &mut QueryExecutor, id: i64) -> Value {
+        let found = rows(db, &format!("SELECT * FROM accounts WHERE id = {}", id));
+        found[0].values[1].clone()
+    }
+
+    #[test]
+    fn test_snapshot_ignores_concurrent_writes() {
+        let mut writer = QueryExecutor::new();
+        setup_accounts(&mut writer);
+        let mut reader = writer.session();
+
+        run(&mut reader, "BEGIN");
+        assert_eq!(rows(&mut reader, "SELECT * FROM accounts").len(), 2);
+
+        // Uncommitted changes are invisible to everyone else
+        run(&mut writer, "BEGIN");
+        run(&mut writer, "UPDATE accounts SET balance = 0 WHERE id = 1");
+        run(&mut writer, "INSERT INTO accounts VALUES (3, 30)");
+        assert_eq!(balance(&mut writer, 1), Value::Integer(0));
+        assert_eq!(balance(&mut reader, 1), Value::Integer(100));
+
+        // Once committed, new statements see them - the open snapshot doesn't
+        run(&mut writer, "COMMIT");
+        run(&mut writer, "DELETE FROM accounts WHERE id = 2");
+        assert_eq!(balance(&mut reader, 1), Value::Integer(100));
+        assert_eq!(rows(&mut reader, "SELECT * FROM accounts").len(), 2);
+        assert!(rows(&mut reader, "SELECT * FROM accounts WHERE id = 3").is_empty());
+        run(&mut reader, "COMMIT");
+
+        let ids: Vec<Value> = rows(&mut reader, "SELECT * FROM accounts")
+            .into_iter()
+            .map(|row| row.values[0].clone())
+            .collect();
+        assert_eq!(ids, vec![Value::Integer(1), Value::Integer(3)]);
+    }
+
+    #[test]
+    fn test_concurrent_update_conflict() {
+        let mut first = QueryExecutor::new();
+        setup_accounts(&mut first);
+        let mut second = first.session();
+
+        run(&mut first, "BEGIN");
+        run(&mut second, "BEGIN");
+        run(&mut first, "UPDATE accounts SET balance = 1 WHERE id = 1");
+
+        // First updater wins; the second transaction is rolled back
+        let err = try_run(&mut second, "UPDATE accounts SET balance = 2 WHERE id = 1").unwrap_err();
+        assert!(err.to_string().contains("concurrent update"));
+        assert!(!second.in_transaction());
+
+        // A key inserted by a running transaction is already taken
+        run(&mut first, "INSERT INTO accounts VALUES (3, 30)");
+        assert!(try_run(&mut second, "INSERT INTO accounts VALUES (3, 31)").is_err());
+
+        // Dropping a session rolls its transaction back
+        drop(first);
+        assert_eq!(balance(&mut second, 1), Value::Integer(100));
+        run(&mut second, "INSERT INTO accounts VALUES (3, 31)");
+    }
+
+    #[test]
+    fn test_vacuum_keeps_versions_visible_to_open_snapshots() {
+        let mut db = QueryExecutor::new();
+        setup_accounts(&mut db);
+        let mut reader = db.session();
+
+        run(&mut reader, "BEGIN");
+        run(&mut db, "UPDATE accounts SET balance = 0 WHERE id = 1");
+        run(&mut db, "DELETE FROM accounts WHERE id = 2");
+        assert_eq!(db.get_table("accounts").unwrap().row_count().unwrap(), 1);
+        assert_eq!(
+            db.get_table("accounts").unwrap().version_count().unwrap(),
+            3
+        );
+
+        // The reader still needs the old versions
+        assert!(try_run(&mut reader, "VACUUM").is_err());
+        run(&mut db, "VACUUM accounts");
+        assert_eq!(
+            db.get_table("accounts").unwrap().version_count().unwrap(),
+            3
+        );
+        assert_eq!(balance(&mut reader, 1), Value::Integer(100));
+        run(&mut reader, "COMMIT");
+
+        // Now nobody can see them, so VACUUM removes them and their index entries
+        match run(&mut db, "VACUUM") {
+            QueryResult::Message(msg) => assert!(msg.contains("removed 2")),
+            other => panic!("expected a message, got {:?}", other),
+        }
+        assert_eq!(
+            db.get_table("accounts").unwrap().version_count().unwrap(),
+            1
+        );
+        assert!(rows(&mut db, "SELECT * FROM accounts WHERE id = 2").is_empty());
+        assert_eq!(balance(&mut db, 1), Value::Integer(0));
+        run(&mut db, "INSERT INTO accounts VALUES (2, 5)");
+    }
+
+    #[test]
+    fn test_sessions_across_threads() {
+        let dir = temp_dir("threads");
+        let mut db = QueryExecutor::open(&dir).unwrap();
+        run(
+            &mut db,
+            "CREATE TABLE counter (id INTEGER PRIMARY KEY, thread INTEGER)",
+        );
+
+        // A long-running reader keeps its snapshot while writers insert
+        let mut reader = db.session();
+        run(&mut reader, "BEGIN");
+
+        let writers: Vec<_> = (0..4)
+            .map(|t| {
+                let mut session = db.session();
+                std::thread::spawn(move || {
+                    for i in 0..25 {
+                        let id = t * 25 + i;
+                        run(
+                            &mut session,
+                            &format!("INSERT INTO counter VALUES ({}, {})", id, t),
+                        );
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        assert!(rows(&mut reader, "SELECT * FROM counter").is_empty());
+        run(&mut reader, "COMMIT");
+        assert_eq!(rows(&mut reader, "SELECT * FROM counter").len(), 100);
+
+        // Transaction IDs keep increasing across a restart
+        drop((db, reader));
+        let mut db = QueryExecutor::open(&dir).unwrap();
+        run(&mut db, "DELETE FROM counter WHERE thread = 0");
+        assert_eq!(rows(&mut db, "SELECT * FROM counter").len(), 75);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_delete_removes_rows_and_reuses_their_slots() {
+        let dir = temp_dir("delete");
+        let mut db = QueryExecutor::open(&dir).unwrap();
+        run(
+            &mut db,
+            "CREATE TABLE items (id INTEGER PRIMARY KEY, kind TEXT)",
+        );
+        for i in 0..250 {
+            let kind = if i % 2 == 0 { "even" } else { "odd" };
+            run(
+                &mut db,
+                &format!("INSERT INTO items VALUES ({}, '{}')", i, kind),
+            );
+        }
+        let file_size = fs::metadata(dir.join("items.tbl")).unwrap().len();
+
+        run(&mut db, "DELETE FROM items WHERE kind = 'odd'");
+
+        // Deleted rows are gone from scans, counts and the pages themselves
+        assert_eq!(rows(&mut db, "SELECT * FROM items").len(), 125);
+        assert!(rows(&mut db, "SELECT * FROM items WHERE kind = 'odd'").is_empty());
+        assert!(rows(&mut db, "SELECT * FROM items WHERE id = 7").is_empty());
+        assert_eq!(db.get_table("items").unwrap().row_count().unwrap(), 125);
+        assert_eq!(db.get_table("items").unwrap().version_count().unwrap(), 125);
+
+        // Re-inserting fills the freed slots instead of growing the file,
+        // and the old primary keys can be used again
+        for i in (1..250).step_by(2) {
+            run(
+                &mut db,
+                &format!("INSERT INTO items VALUES ({}, 'again')", i),
+            );
+        }
+        assert_eq!(rows(&mut db, "SELECT * FROM items").len(), 250);
+        let seven = rows(&mut db, "SELECT * FROM items WHERE id = 7");
+        assert_eq!(seven[0].values[1], Value::Text("again".into()));
+        assert_eq!(
+            fs::metadata(dir.join("items.tbl")).unwrap().len(),
+            file_size
+        );
+
+        // And it all survives a restart
+        drop(db);
+        let mut db = QueryExecutor::open(&dir).unwrap();
+        assert_eq!(
+            rows(&mut db, "SELECT * FROM items WHERE kind = 'again'").len(),
+            125
+        );
+        assert_eq!(db.get_table("items").unwrap().row_count().unwrap(), 250);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_pages_fill_by_bytes() {
+        let dir = temp_dir("page-bytes");
+        let mut db = QueryExecutor::open(&dir).unwrap();
+        run(
+            &mut db,
+            "CREATE TABLE narrow (id INTEGER PRIMARY KEY, body TEXT)",
+        );
+        run(
+            &mut db,
+            "CREATE TABLE wide (id INTEGER PRIMARY KEY, body TEXT)",
+        );
+        let body = "x".repeat(1000);
+        for i in 0..40 {
+            run(&mut db, &format!("INSERT INTO narrow VALUES ({}, 'x')", i));
+            run(
+                &mut db,
+                &format!("INSERT INTO wide VALUES ({}, '{}')", i, body),
+            );
+        }
+
+        // 40 small rows share one page; 40 rows of 1 KB need several
+        let pages = |name: &str| fs::metadata(dir.join(name)).unwrap().len() / 8192;
+        assert_eq!(pages("narrow.tbl"), 1);
+        assert_eq!(pages("wide.tbl"), 6);
+
+        drop(db);
+        let mut db = QueryExecutor::open(&dir).unwrap();
+        let found = rows(&mut db, "SELECT * FROM wide WHERE id = 39");
+        assert_eq!(found[0].values[1], Value::Text(body));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_large_text_values_use_overflow_pages() {
+        let dir = temp_dir("overflow");
+        let document = |n: usize| format!("{} ", n).repeat(20_000);
+
+        {
+            let mut db = QueryExecutor::open(&dir).unwrap();
+            run(
+                &mut db,
+                "CREATE TABLE docs (id INTEGER PRIMARY KEY, body TEXT)",
+            );
+            for i in 0..3 {
+                run(
+                    &mut db,
+                    &format!("INSERT INTO docs VALUES ({}, '{}')", i, document(i)),
+                );
+            }
+            run(
+                &mut db,
+                &format!("UPDATE docs SET body = '{}' WHERE id = 1", document(7)),
+            );
+
+            // Each 40 KB document lives in overflow pages; the rows share one page
+            let found = rows(&mut db, "SELECT * FROM docs WHERE id = 1");
+            assert_eq!(found[0].values[1], Value::Text(document(7)));
+            assert_eq!(fs::metadata(dir.join("docs.tbl")).unwrap().len(), 8192);
+            // Crash without a checkpoint
+        }
+
+        // Simulate the page writes never reaching the disk: the log rebuilds
+        // the rows and their overflow pages
+        fs::write(dir.join("docs.tbl"), b"").unwrap();
+        fs::write(dir.join("docs.toast"), b"").unwrap();
+
+        let mut db = QueryExecutor::open(&dir).unwrap();
+        let query = format!("SELECT * FROM docs WHERE body = '{}'", document(2));
+        let found = rows(&mut db, &query);
+        assert_eq!(found[0].values[0], Value::Integer(2));
+        let found = rows(&mut db, "SELECT * FROM docs WHERE id = 1");
+        assert_eq!(found[0].values[1], Value::Text(document(7)));
+        assert_eq!(db.get_table("docs").unwrap().row_count().unwrap(), 3);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_floats_keep_full_precision() {
+        let dir = temp_dir("floats");
+
+        {
+            let mut db = QueryExecutor::open(&dir).unwrap();
+            run(
+                &mut db,
+                "CREATE TABLE readings (id INTEGER PRIMARY KEY, x FLOAT)",
+            );
+            run(&mut db, "CREATE INDEX idx_x ON readings (x)");
+            run(&mut db, "INSERT INTO readings VALUES (1, 0.0005)");
+            run(&mut db, "INSERT INTO readings VALUES (2, 1.5e300)");
+            run(&mut db, "INSERT INTO readings VALUES (3, -0.0)");
+        }
+
+        let mut db = QueryExecutor::open(&dir).unwrap();
+        let small = rows(&mut db, "SELECT * FROM readings WHERE id = 1");
+        assert_eq!(small[0].values[1], Value::Float(0.0005));
+        assert_eq!(small[0].values[1].to_string(), "0.0005");
+        let huge = rows(&mut db, "SELECT * FROM readings WHERE x = 1.5e300");
+        assert_eq!(huge[0].values[0], Value::Integer(2));
+
+        // -0.0 and 0.0 are the same value, through the index too
+        let zero = rows(&mut db, "SELECT * FROM readings WHERE x = 0.0");
+        assert_eq!(zero[0].values[0], Value::Integer(3));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_float_nan_and_infinity() {
+        let mut db = QueryExecutor::new();
+        run(
+            &mut db,
+            "CREATE TABLE readings (id INTEGER PRIMARY KEY, x FLOAT)",
+        );
+        run(&mut db, "INSERT INTO readings VALUES (1, 'NaN')");
+        run(&mut db, "INSERT INTO readings VALUES (2, 'Infinity')");
+        run(&mut db, "INSERT INTO readings VALUES (3, '-infinity')");
+        run(&mut db, "INSERT INTO readings VALUES (4, 2.5)");
+        run(&mut db, "INSERT INTO readings VALUES (5, 'nan')");
+        run(&mut db, "INSERT INTO readings VALUES (6, NULL)");
+        assert!(db
+            .execute(QueryParser::parse("INSERT INTO readings VALUES (7, 'lots')").unwrap())
+            .is_err());
+
+        let ids = |db: &mut QueryExecutor, sql: &str| {
+            rows(db, sql)
+                .into_iter()
+                .map(|row| row.values[0].to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // NaN is greater than every other value, and NULL sorts after it
+        assert_eq!(
+            ids(&mut db, "SELECT id FROM readings ORDER BY x, id"),
+            ["3", "4", "2", "1", "5", "6"]
+        );
+        assert_eq!(
+            ids(&mut db, "SELECT id FROM readings ORDER BY x DESC, id"),
+            ["6", "1", "5", "2", "4", "3"]
+        );
+
+        // NaN equals NaN, without and with an index
+        assert_eq!(
+            ids(&mut db, "SELECT id FROM readings WHERE x = 'NaN'"),
+            ["1", "5"]
+        );
+        assert_eq!(
+            ids(&mut db, "SELECT id FROM readings WHERE x > 1e308"),
+            ["1", "2", "5"]
+        );
+        run(&mut db, "CREATE INDEX idx_x ON readings (x)");
+        assert_eq!(
+            ids(&mut db, "SELECT id FROM readings WHERE x = 'NaN'"),
+            ["1", "5"]
+        );
+        assert_eq!(
+            ids(&mut db, "SELECT id FROM readings WHERE x = '-Infinity'"),
+            ["3"]
+        );
+        assert_eq!(
+            ids(
+                &mut db,
+                "SELECT id FROM readings WHERE x IN ('infinity', 2.5)"
+            ),
+            ["2", "4"]
+        );
+
+        // They are written the way they are read, and come out of float division by zero
+        let values = rows(
+            &mut db,
+            "SELECT x, x / 0, (x - x) / 0, -x FROM readings WHERE id = 2 OR id = 4",
+        );
+        let text = |row: &Row| row.values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            text(&values[0]),
+            ["Infinity", "Infinity", "NaN", "-Infinity"]
+        );
+        assert_eq!(text(&values[1]), ["2.5", "Infinity", "NaN", "-2.5"]);
+    }
+
+    #[test]
+    fn test_decimal_columns_are_exact() {
+        let dir = temp_dir("decimals");
+
+        {
+            let mut db = QueryExecutor::open(&dir).unwrap();
+            run(
+                &mut db,
+                "CREATE TABLE invoices (id INTEGER PRIMARY KEY, total NUMERIC(10, 2))",
+            );
+            run(&mut db, "CREATE INDEX idx_total ON invoices (total)");
+            run(&mut db, "INSERT INTO invoices VALUES (1, 0.1)");
+            run(&mut db, "INSERT INTO invoices VALUES (2, 19.995)");
+            run(&mut db, "INSERT INTO invoices VALUES (3, -7)");
+
+            // Too many digits before the decimal point
+            let sql = "INSERT INTO invoices VALUES (4, 123456789.5)";
+            assert!(db.execute(QueryParser::parse(sql).unwrap()).is_err());
+            let sql = "CREATE TABLE bad (x DECIMAL(2, 5))";
+            assert!(QueryParser::parse(sql).is_err());
+        }
+
+        let mut db = QueryExecutor::open(&dir).unwrap();
+        let found = rows(&mut db, "SELECT * FROM invoices WHERE id = 1");
+        assert_eq!(found[0].values[1].to_string(), "0.10");
+
+        // Rounded half away from zero on insert, and found by value through the index
+        let found = rows(&mut db, "SELECT * FROM invoices WHERE total = 20");
+        assert_eq!(found[0].values[0], Value::Integer(2));
+        assert_eq!(found[0].values[1].to_string(), "20.00");
+        let found = rows(&mut db, "SELECT * FROM invoices WHERE total = -7.0");
+        assert_eq!(found[0].values[0], Value::Integer(3));
+
+        // A plain NUMERIC keeps each value's own scale; DECIMAL(p) rounds to whole numbers
+        run(
+            &mut db,
+            "CREATE TABLE readings (id INTEGER PRIMARY KEY, x NUMERIC, y DECIMAL(5))",
+        );
+        run(&mut db, "INSERT INTO readings VALUES (1, 1.2345, 1.2345)");
+        run(&mut db, "INSERT INTO readings VALUES (2, -0.5, -0.5)");
+        let found = rows(&mut db, "SELECT * FROM readings WHERE x = 1.2345");
+        assert_eq!(found[0].values[1].to_string(), "1.2345");
+        assert_eq!(found[0].values[2].to_string(), "1");
+        let found = rows(&mut db, "SELECT * FROM readings WHERE id = 2");
+        assert_eq!(found[0].values[1].to_string(), "-0.5");
+        assert_eq!(found[0].values[2].to_string(), "-1");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_dates_and_times() {
+        let dir = temp_dir("temporal");
+
+        {
+            let mut db = QueryExecutor::open(&dir).unwrap();
+            run(
+                &mut db,
+                "CREATE TABLE events (id INTEGER PRIMARY KEY, day DATE, at TIMESTAMP, length INTERVAL)",
+            );
+            run(&mut db, "CREATE INDEX idx_day ON events (day)");
+            run(
+                &mut db,
+                "INSERT INTO events VALUES (1, DATE '2026-01-31', TIMESTAMP '2026-01-31 18:00:00', INTERVAL '90 minutes')",
+            );
+            // Text is accepted where the column expects a date or time
+            run(
+                &mut db,
+                "INSERT INTO events VALUES (2, '2026-02-28', '2026-02-28 09:30:00', '2 hours')",
+            );
+            run(
+                &mut db,
+                "INSERT INTO events VALUES (3, CURRENT_DATE, NOW(), INTERVAL '1' DAY)",
+            );
+
+            let sql = "INSERT INTO events VALUES (4, '2026-02-30', NOW(), '1 day')";
+            assert!(db.execute(QueryParser::parse(sql).unwrap()).is_err());
+        }
+
+        let mut db = QueryExecutor::open(&dir).unwrap();
+        let found = rows(
+            &mut db,
+            "SELECT * FROM events WHERE day = DATE '2026-01-31' + INTERVAL '1 month'",
+        );
+        assert_eq!(found[0].values[0], Value::Integer(2));
+        assert_eq!(found[0].values[2].to_string(), "2026-02-28 09:30:00");
+        assert_eq!(found[0].values[3].to_string(), "02:00:00");
+
+        let found = rows(
+            &mut db,
+            "SELECT * FROM events WHERE length = INTERVAL '1 hour' + INTERVAL '30 minutes'",
+        );
+        assert_eq!(found[0].values[1].to_string(), "2026-01-31");
+
+        let found = rows(
+            &mut db,
+            "SELECT * FROM events WHERE day = DATE_TRUNC('day', NOW())",
+        );
+        assert_eq!(found[0].values[0], Value::Integer(3));
+        let found = rows(
+            &mut db,
+            "SELECT * FROM events WHERE day = TIMESTAMP '2026-01-31 18:00:00'",
+        );
+        assert!(found.is_empty());
+        let found = rows(
+            &mut db,
+            "SELECT * FROM events WHERE id = EXTRACT(MONTH FROM DATE '2026-02-28')",
+        );
+        assert_eq!(found[0].values[1].to_string(), "2026-02-28");
+
+        // EXTRACT from the stored columns of each row
+        let found = rows(
+            &mut db,
+            "SELECT id, EXTRACT(MONTH FROM day), EXTRACT(HOUR FROM at) FROM events WHERE id < 3",
+        );
+        let parts: Vec<Vec<String>> = found
+            .iter()
+            .map(|row| row.values.iter().map(|value| value.to_string()).collect())
+            .collect();
+        assert_eq!(parts, [["1", "1", "18"], ["2", "2", "9"]]);
+        let found = rows(
+            &mut db,
+            "SELECT * FROM events WHERE EXTRACT(MONTH FROM day) = 2 AND id < 3",
+        );
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].values[0], Value::Integer(2));
+        let found = rows(&mut db, "SELECT * FROM events WHERE EXTRACT(YEAR FROM at) = 2026 AND EXTRACT(HOUR FROM at) = 18");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].values[0], Value::Integer(1));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_blob_columns() {
+        let dir = temp_dir("blobs");
+        let image: Vec<u8> = (0..20_000).map(|i| (i % 256) as u8).collect();
+        let image_hex: String = image.iter().map(|b| format!("{:02x}", b)).collect();
+
+        {
+            let mut db = QueryExecutor::open(&dir).unwrap();
+            run(
+                &mut db,
+                "CREATE TABLE files (id INTEGER PRIMARY KEY, hash BYTEA, data BLOB)",
+            );
+            run(&mut db, "CREATE INDEX idx_hash ON files (hash)");
+            run(&mut db, "INSERT INTO files VALUES (1, X'DEADBEEF', X'')");
+            // Large enough to go to overflow pages
+            run(
+                &mut db,
+                &format!("INSERT INTO files VALUES (2, X'00FF', X'{}')", image_hex),
+            );
+            run(
+                &mut db,
+                "INSERT INTO files VALUES (3, '\\xc0ffee', 'plain text')",
+            );
+        }
+
+        let mut db = QueryExecutor::open(&dir).unwrap();
+        let found = rows(&mut db, "SELECT * FROM files WHERE hash = X'00ff'");
+        assert_eq!(found[0].values[2], Value::Bytes(image));
+
+        let found = rows(&mut db, "SELECT * FROM files WHERE id = LENGTH(X'C0FFEE')");
+        assert_eq!(found[0].values[1], Value::Bytes(vec![0xc0, 0xff, 0xee]));
+        assert_eq!(found[0].values[2], Value::Bytes(b"plain text".to_vec()));
+
+        // Binary data is shown as hex
+        let table = run(&mut db, "SELECT * FROM files WHERE id = 1").format();
+        assert!(table.contains("\\xdeadbeef"), "{}", table);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_json_columns() {
+        let dir = temp_dir("json");
+        let big: Vec<String> = (0..2_000).map(|i| i.to_string()).collect();
+
+        {
+            let mut db = QueryExecutor::open(&dir).unwrap();
+            run(
+                &mut db,
+                "CREATE TABLE events (id INTEGER PRIMARY KEY, data JSONB)",
+            );
+            run(&mut db, "CREATE INDEX idx_type ON events ((data->>'type'))");
+            run(
+                &mut db,
+                r#"INSERT INTO events VALUES (1, '{"type": "click", "user": {"name": "ann"}, "tags": ["a", "b"]}')"#,
+            );
+            run(
+                &mut db,
+                r#"INSERT INTO events VALUES (2, '{"type": "view", "n": 5}')"#,
+            );
+            // Large enough to go to overflow pages
+            run(
+                &mut db,
+                &format!(
+                    r#"INSERT INTO events VALUES (3, '{{"type": "click", "tags": [{}]}}')"#,
+                    big.join(",")
+                ),
+            );
+            assert!(db
+                .execute(QueryParser::parse("INSERT INTO events VALUES (4, '{oops')").unwrap())
+                .is_err());
+        }
+
+        // The index on the path is rebuilt after a restart
+        let mut db = QueryExecutor::open(&dir).unwrap();
+        assert_eq!(
+            db.get_table("events").unwrap().indexed_columns(),
+            ["data->>'type'", "id"]
+        );
+
+        let ids = |db: &mut QueryExecutor, sql: &str| -> Vec<Value> {
+            rows(db, sql)
+                .into_iter()
+                .map(|row| row.values[0].clone())
+                .collect()
+        };
+        assert_eq!(
+            ids(
+                &mut db,
+                "SELECT id FROM events WHERE data->>'type' = 'click'"
+            )
+            .len(),
+            2
+        );
+        assert_eq!(
+            ids(
+                &mut db,
+                "SELECT id FROM events WHERE data->'user'->>'name' = 'ann'"
+            ),
+            [Value::Integer(1)]
+        );
+        assert_eq!(
+            ids(&mut db, "SELECT id FROM events WHERE data->'n' = 5"),
+            [Value::Integer(2)]
+        );
+        assert_eq!(
+            ids(
+                &mut db,
+                "SELECT id FROM events WHERE json_extract(data, '$.tags[1]') = '\"b\"'"
+            ),
+            [Value::Integer(1)]
+        );
+        assert_eq!(
+            ids(
+                &mut db,
+                "SELECT id FROM events WHERE json_array_length(data->'tags') = 2000"
+            ),
+            [Value::Integer(3)]
+        );
+
+        // Paths in the SELECT list
+        let result = run(
+            &mut db,
+            "SELECT id, data->'user'->>'name', data->'tags'->0 FROM events WHERE id = 1",
+        );
+        let QueryResult::Rows { rows, column_names } = result else {
+            panic!("expected rows");
+        };
+        assert_eq!(
+            column_names,
+            ["id", "data->'user'->>'name'", "data->'tags'->0"]
+        );
+        assert_eq!(rows[0].values[1], Value::Text("ann".to_string()));
+        assert_eq!(rows[0].values[2].to_string(), "\"a\"");
+        assert_eq!(
+            ids(&mut db, "SELECT data->>'missing' FROM events WHERE id = 2"),
+            [Value::Null]
+        );
+
+        assert!(db
+            .execute(QueryParser::parse("SELECT id->>'x' FROM events").unwrap())
+            .is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_uuid_columns() {
+        let dir = temp_dir("uuid");
+        let key = "a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11";
+
+        {
+            let mut db = QueryExecutor::open(&dir).unwrap();
+            run(
+                &mut db,
+                "CREATE TABLE users (id UUID PRIMARY KEY, name TEXT)",
+            );
+            run(
+                &mut db,
+                &format!("INSERT INTO users VALUES ('{}', 'ann')", key),
+            );
+            run(
+                &mut db,
+                "INSERT INTO users VALUES (gen_random_uuid(), 'bob')",
+            );
+
+            // The same UUID in another format is the same primary key
+            let duplicate = format!("INSERT INTO users VALUES ('{}', 'eve')", key.to_uppercase());
+            assert!(db.execute(QueryParser::parse(&duplicate).unwrap()).is_err());
+            assert!(db
+                .execute(QueryParser::parse("INSERT INTO users VALUES ('nope', 'x')").unwrap())
+                .is_err());
+        }
+
+        let mut db = QueryExecutor::open(&dir).unwrap();
+        let found = rows(
+            &mut db,
+            &format!("SELECT * FROM users WHERE id = UUID '{{{}}}'", key),
+        );
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].values[0].to_string(), key);
+
+        let bob = rows(&mut db, "SELECT * FROM users WHERE name = 'bob'");
+        let Value::Uuid(id) = bob[0].values[0] else {
+            panic!("expected a UUID, got {:?}", bob[0].values[0]);
+        };
+        assert_eq!(id.version(), 4);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_column_types_and_not_null() {
+        let mut db = QueryExecutor::new();
+        run(
+            &mut db,
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT NOT NULL, score FLOAT)",
+        );
+
+        let error = |db: &mut QueryExecutor, sql: &str| {
+            db.execute(QueryParser::parse(sql).unwrap())
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error(&mut db, "INSERT INTO users VALUES ('x', 42, 1.0)"),
+            "Column 'id' is INTEGER, but got TEXT 'x'"
+        );
+        assert_eq!(
+            error(&mut db, "INSERT INTO users VALUES (1, NULL, 1.0)"),
+            "NULL value in column 'name' violates NOT NULL constraint"
+        );
+        assert!(error(&mut db, "INSERT INTO users VALUES (NULL, 'ann', 1.0)").contains("NOT NULL"));
+
+        // Integers are fine in a FLOAT column
+        run(&mut db, "INSERT INTO users VALUES (1, 'ann', 7)");
+        assert_eq!(
+            rows(&mut db, "SELECT * FROM users WHERE id = 1")[0].values[2],
+            Value::Float(7.0)
+        );
+
+        assert_eq!(
+            error(&mut db, "UPDATE users SET score = 'high' WHERE id = 1"),
+            "Column 'score' is FLOAT, but got TEXT 'high'"
+        );
+        assert!(error(&mut db, "UPDATE users SET name = NULL WHERE id = 1").contains("NOT NULL"));
+        run(&mut db, "UPDATE users SET score = NULL WHERE id = 1");
+        assert_eq!(
+            rows(&mut db, "SELECT * FROM users WHERE id = 1")[0].values[2],
+            Value::Null
+        );
+    }
+
+    #[test]
+    fn test_unique_constraints() {
+        let dir = temp_dir("unique");
+        let mut db = QueryExecutor::open(&dir).unwrap();
+        run(
+            &mut db,
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT UNIQUE, team INTEGER, number INTEGER, UNIQUE (team, number))",
+        );
+
+        let error = |db: &mut QueryExecutor, sql: &str| {
+            db.execute(QueryParser::parse(sql).unwrap())
+                .unwrap_err()
+                .to_string()
+        };
+        run(&mut db, "INSERT INTO users VALUES (1, 'ann@x.org', 1, 10)");
+        assert_eq!(
+            error(&mut db, "INSERT INTO users VALUES (2, 'ann@x.org', 1, 11)"),
+            "Unique constraint violation: (email) = (ann@x.org) already exists"
+        );
+        assert_eq!(
+            error(&mut db, "INSERT INTO users VALUES (2, 'bob@x.org', 1, 10)"),
+            "Unique constraint violation: (team, number) = (1, 10) already exists"
+        );
+
+        // NULL is never equal to NULL, so NULLs don't collide
+        run(&mut db, "INSERT INTO users VALUES (2, NULL, 1, NULL)");
+        run(&mut db, "INSERT INTO users VALUES (3, NULL, 1, NULL)");
+        run(&mut db, "INSERT INTO users VALUES (4, 'bob@x.org', 2, 10)");
+
+        // UPDATE can't make a duplicate either, of the primary key or of a UNIQUE column
+        assert_eq!(
+            error(&mut db, "UPDATE users SET id = 1 WHERE id = 2"),
+            "Primary key violation: duplicate value"
+        );
+        assert!(
+            error(&mut db, "UPDATE users SET email = 'ann@x.org' WHERE id = 4").contains("(email)")
+        );
+        // ... but a row may keep its own value
+        run(&mut db, "UPDATE users SET email = 'ann@x.org' WHERE id = 1");
+        assert_eq!(
+            rows(&mut db, "SELECT * FROM users WHERE email = 'ann@x.org'").len(),
+            1
+        );
+
+        // A unique index can't be built over duplicates
+        assert!(error(&mut db, "CREATE UNIQUE INDEX ON users (team)").contains("share a key"));
+        run(&mut db, "CREATE UNIQUE INDEX ON users (number, id)");
+        drop(db);
+
+        // The constraints survive a restart
+        let mut db = QueryExecutor::open(&dir).unwrap();
+        assert!(
+            error(&mut db, "INSERT INTO users VALUES (5, 'ann@x.org', 3, 1)").contains("(email)")
+        );
+        assert!(
+            error(&mut db, "INSERT INTO users VALUES (5, 'eve@x.org', 2, 10)")
+                .contains("(team, number)")
+        );
+        run(&mut db, "INSERT INTO users VALUES (5, 'eve@x.org', 3, 1)");
+        assert_eq!(
+            db.get_table("users").unwrap().schema.unique,
+            [vec!["email"], vec!["team", "number"], vec!["number", "id"]]
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_foreign_keys() {
+        let mut db = QueryExecutor::new();
+        let error = |db: &mut QueryExecutor, sql: &str| {
+            db.execute(QueryParser::parse(sql).unwrap())
+                .unwrap_err()
+                .to_string()
+        };
+        let count = |db: &mut QueryExecutor, table: &str| {
+            rows(db, &format!("SELECT * FROM {}", table)).len()
+        };
+
+        run(
+            &mut db,
+            "CREATE TABLE accounts (id INTEGER PRIMARY KEY, name TEXT)",
+        );
+        run(
+            &mut db,
+            "CREATE TABLE customers (id INTEGER PRIMARY KEY, account_id INTEGER REFERENCES accounts ON DELETE CASCADE ON UPDATE CASCADE)",
+        );
+        run(
+            &mut db,
+            "CREATE TABLE orders (id INTEGER PRIMARY KEY, customer_id INTEGER, note TEXT, \
+             FOREIGN KEY (customer_id) REFERENCES customers (id) ON DELETE SET NULL)",
+        );
+        assert!(error(
+            &mut db,
+            "CREATE TABLE bad (id INTEGER, x TEXT REFERENCES accounts (id))"
+        )
+        .contains("is TEXT"));
+        assert!(error(
+            &mut db,
+            "CREATE TABLE bad (id INTEGER REFERENCES accounts (name))"
+        )
+        .contains("primary key or UNIQUE"));
+
+        run(&mut db, "INSERT INTO accounts VALUES (1, 'acme')");
+        run(&mut db, "INSERT INTO customers VALUES (10, 1)");
+        run(&mut db, "INSERT INTO customers VALUES (11, NULL)");
+        run(&mut db, "INSERT INTO orders VALUES (100, 10, 'first')");
+        run(&mut db, "INSERT INTO orders VALUES (101, 11, 'second')");
+        assert_eq!(
+            error(&mut db, "INSERT INTO orders VALUES (102, 12, 'lost')"),
+            "Foreign key violation: (customer_id) = (12) is not present in table 'customers'"
+        );
+        assert!(
+            error(&mut db, "UPDATE orders SET customer_id = 12 WHERE id = 100")
+                .contains("not present")
+        );
+
+        // ON UPDATE CASCADE: the customers follow their account's new id
+        run(&mut db, "UPDATE accounts SET id = 2 WHERE id = 1");
+        assert_eq!(
+            rows(&mut db, "SELECT * FROM customers WHERE account_id = 2").len(),
+            1
+        );
+
+        // orders -> customers is RESTRICT on update (NO ACTION by default)
+        assert_eq!(
+            error(&mut db, "UPDATE customers SET id = 12 WHERE id = 10"),
+            "Foreign key violation: (id) = (10) is still referenced from table 'orders'"
+        );
+        assert_eq!(
+            rows(&mut db, "SELECT * FROM customers WHERE id = 10").len(),
+            1
+        );
+
+        // ON DELETE CASCADE deletes customer 10, whose order gets ON DELETE SET NULL
+        run(&mut db, "DELETE FROM accounts WHERE id = 2");
+        assert_eq!(count(&mut db, "customers"), 1);
+        assert_eq!(
+            rows(&mut db, "SELECT * FROM orders WHERE id = 100")[0].values[1],
+            Value::Null
+        );
+
+        // RESTRICT keeps a referenced row; a failed statement changes nothing
+        run(&mut db, "CREATE TABLE notes (id INTEGER PRIMARY KEY, order_id INTEGER REFERENCES orders ON DELETE RESTRICT)");
+        run(&mut db, "INSERT INTO notes VALUES (1, 101)");
+        assert!(error(&mut db, "DELETE FROM orders WHERE id = 101")
+            .contains("still referenced from table 'notes'"));
+        assert_eq!(count(&mut db, "orders"), 2);
+        run(&mut db, "DELETE FROM notes WHERE id = 1");
+        run(&mut db, "DELETE FROM orders WHERE id = 101");
+        assert_eq!(count(&mut db, "orders"), 1);
+    }
+
+    #[test]
+    fn test_self_referencing_foreign_key() {
+        let mut db = QueryExecutor::new();
+        run(
+            &mut db,
+            "CREATE TABLE employees (id INTEGER PRIMARY KEY, manager_id INTEGER REFERENCES employees (id) ON DELETE CASCADE)",
+        );
+        run(&mut db, "INSERT INTO employees VALUES (1, NULL)");
+        run(&mut db, "INSERT INTO employees VALUES (2, 1)");
+        run(&mut db, "INSERT INTO employees VALUES (3, 2)");
+        run(&mut db, "INSERT INTO employees VALUES (4, 4)");
+
+        // Deleting the boss deletes everyone below, level by level
+        run(&mut db, "DELETE FROM employees WHERE id = 1");
+        let left = rows(&mut db, "SELECT * FROM employees");
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].values[0], Value::Integer(4));
+    }
+
+    #[test]
+    fn test_defaults_and_checks() {
+        let dir = temp_dir("checks");
+        let mut db = QueryExecutor::open(&dir).unwrap();
+        run(
+            &mut db,
+            "CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT DEFAULT 'unnamed', \
+             price FLOAT CHECK (price > 0), discount FLOAT DEFAULT 0, \
+             added TIMESTAMP DEFAULT NOW(), \
+             CONSTRAINT cheaper CHECK (discount < price OR discount = 0))",
+        );
+
+        let error = |db: &mut QueryExecutor, sql: &str| {
+            db.execute(QueryParser::parse(sql).unwrap())
+                .unwrap_err()
+                .to_string()
+        };
+        assert!(QueryParser::parse("CREATE TABLE bad (id INTEGER DEFAULT 'x')").is_err());
+
+        // Left-out columns and DEFAULT get the default; NOW() is evaluated per INSERT
+        run(&mut db, "INSERT INTO items (id, price) VALUES (1, 9.5)");
+        run(
+            &mut db,
+            "INSERT INTO items VALUES (2, 'pen', 2, DEFAULT, DEFAULT)",
+        );
+        let item = &rows(&mut db, "SELECT * FROM items WHERE id = 1")[0];
+        assert_eq!(item.values[1], Value::Text("unnamed".into()));
+        assert_eq!(item.values[3], Value::Float(0.0));
+        assert!(matches!(item.values[4], Value::Timestamp(_)));
+        assert_eq!(
+            rows(&mut db, "SELECT * FROM items WHERE id = 2")[0].values[3],
+            Value::Float(0.0)
+        );
+
+        // A false condition fails with the constraint's name; NULL passes
+        assert_eq!(
+            error(&mut db, "INSERT INTO items (id, price) VALUES (3, -1)"),
+            "New row for table 'items' violates check constraint 'items_price_check'"
+        );
+        run(&mut db, "INSERT INTO items (id, name) VALUES (3, 'free')");
+        assert!(error(&mut db, "UPDATE items SET discount = 5 WHERE id = 2").contains("'cheaper'"));
+        run(&mut db, "UPDATE items SET discount = 1 WHERE id = 2");
+        drop(db);
+
+        // The constraints and defaults are saved in the catalog
+        let mut db = QueryExecutor::open(&dir).unwrap();
+        assert!(
+            error(&mut db, "UPDATE items SET price = 0 WHERE id = 1").contains("items_price_check")
+        );
+        run(&mut db, "INSERT INTO items (price, id) VALUES (1, 4)");
+        assert_eq!(
+            rows(&mut db, "SELECT * FROM items WHERE id = 4")[0].values[1],
+            Value::Text("unnamed".into())
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_serial_columns_and_sequences() {
+        let dir = temp_dir("sequences");
+        let mut db = QueryExecutor::open(&dir).unwrap();
+        run(
+            &mut db,
+            "CREATE TABLE users (id SERIAL PRIMARY KEY, name TEXT)",
+        );
+        run(
+            &mut db,
+            "CREATE TABLE events (id INTEGER GENERATED BY DEFAULT AS IDENTITY (INCREMENT BY 10 START WITH 100), \
+             kind TEXT)",
+        );
+        run(&mut db, "CREATE SEQUENCE tickets START WITH 50");
+
+        let error = |db: &mut QueryExecutor, sql: &str| {
+            db.execute(QueryParser::parse(sql).unwrap())
+                .unwrap_err()
+                .to_string()
+        };
+        let ids = |db: &mut QueryExecutor, sql: &str| -> Vec<Value> {
+            rows(db, sql)
+                .into_iter()
+                .map(|row| row.values[0].clone())
+                .collect()
+        };
+
+        // Left-out and DEFAULT columns are numbered
+        run(&mut db, "INSERT INTO users (name) VALUES ('ann')");
+        run(&mut db, "INSERT INTO users VALUES (DEFAULT, 'bob')");
+        run(&mut db, "INSERT INTO events (kind) VALUES ('a')");
+        run(&mut db, "INSERT INTO events (kind) VALUES ('b')");
+        assert_eq!(
+            ids(&mut db, "SELECT id FROM users WHERE name = 'bob'"),
+            vec![Value::Integer(2)]
+        );
+        assert_eq!(
+            ids(&mut db, "SELECT id FROM events WHERE kind = 'b'"),
+            vec![Value::Integer(110)]
+        );
+
+        // nextval and currval in VALUES; currval is this session's last number
+        assert!(error(
+            &mut db,
+            "INSERT INTO users VALUES (currval('tickets'), 'x')"
+        )
+        .contains("not yet defined"));
+        run(
+            &mut db,
+            "INSERT INTO users VALUES (nextval('tickets'), 'carl')",
+        );
+        run(
+            &mut db,
+            "INSERT INTO events VALUES (currval('tickets'), 'for carl')",
+        );
+        assert_eq!(
+            ids(&mut db, "SELECT id FROM events WHERE kind = 'for carl'"),
+            vec![Value::Integer(50)]
+        );
+        assert!(error(
+            &mut db,
+            "INSERT INTO users VALUES (nextval('missing'), 'x')"
+        )
+        .contains("not found"));
+        assert!(error(&mut db, "CREATE SEQUENCE tickets").contains("already exists"));
+        run(&mut db, "CREATE SEQUENCE IF NOT EXISTS tickets");
+
+        // Numbers taken by a rolled back transaction are not reused
+        run(&mut db, "BEGIN");
+        run(&mut db, "INSERT INTO users (name) VALUES ('dan')");
+        run(&mut db, "ROLLBACK");
+        run(&mut db, "INSERT INTO users (name) VALUES ('eve')");
+        assert_eq!(
+            ids(&mut db, "SELECT id FROM users WHERE name = 'eve'"),
+            vec![Value::Integer(4)]
+        );
+        assert!(QueryParser::parse("CREATE TABLE bad (id SERIAL DEFAULT 1)").is_err());
+
+        // Sessions share the sequence, but each has its own currval
+        let mut other = db.session();
+        run(&mut other, "INSERT INTO users (name) VALUES ('fay')");
+        assert_eq!(
+            ids(&mut other, "SELECT id FROM users WHERE name = 'fay'"),
+            vec![Value::Integer(5)]
+        );
+        assert!(error(
+            &mut other,
+            "INSERT INTO events VALUES (currval('tickets'), 'x')"
+        )
+        .contains("not yet defined"));
+        drop(other);
+        drop(db);
+
+        // After a restart the sequences continue after their reserved numbers
+        let mut db = QueryExecutor::open(&dir).unwrap();
+        run(&mut db, "INSERT INTO users (name) VALUES ('gus')");
+        let id = ids(&mut db, "SELECT id FROM users WHERE name = 'gus'");
+        assert!(matches!(id[0], Value::Integer(n) if n > 5));
+        run(
+            &mut db,
+            "INSERT INTO events VALUES (nextval('tickets'), 'after restart')",
+        );
+        let id = ids(
+            &mut db,
+            "SELECT id FROM events WHERE kind = 'after restart'",
+        );
+        assert!(matches!(id[0], Value::Integer(n) if n > 50));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_where_expressions() {
+        let mut db = QueryExecutor::new();
+        run(
+            &mut db,
+            "CREATE TABLE people (id INTEGER PRIMARY KEY, name TEXT, age INTEGER, city TEXT)",
+        );
+        run(&mut db, "CREATE INDEX ON people (city)");
+        for (id, name, age, city) in [
+            (1, "ann", 25, "paris"),
+            (2, "bob", 35, "rome"),
+            (3, "cy", 45, "paris"),
+            (4, "di", 30, "oslo"),
+        ] {
+            run(
+                &mut db,
+                &format!(
+                    "INSERT INTO people VALUES ({}, '{}', {}, '{}')",
+                    id, name, age, city
+                ),
+            );
+        }
+        run(&mut db, "INSERT INTO people (id, name) VALUES (5, 'ed')");
+
+        let ids = |db: &mut QueryExecutor, condition: &str| -> Vec<i64> {
+            let mut ids: Vec<i64> = rows(db, &format!("SELECT id FROM people WHERE {}", condition))
+                .into_iter()
+                .map(|row| match row.values[0] {
+                    Value::Integer(id) => id,
+                    ref other => panic!("Expected an id, got {:?}", other),
+                })
+                .collect();
+            ids.sort();
+            ids
+        };
+
+        assert_eq!(ids(&mut db, "age > 30"), vec![2, 3]);
+        assert_eq!(ids(&mut db, "age >= 30 AND age <= 35"), vec![2, 4]);
+        assert_eq!(ids(&mut db, "city = 'paris' AND age < 40"), vec![1]);
+        assert_eq!(ids(&mut db, "city = 'rome' OR age < 30"), vec![1, 2]);
+        assert_eq!(
+            ids(&mut db, "NOT (city = 'paris' OR city = 'rome')"),
+            vec![4]
+        );
+        assert_eq!(
+            ids(&mut db, "(age < 30 OR age > 40) AND city <> 'rome'"),
+            vec![1, 3]
+        );
+        assert_eq!(
+            ids(&mut db, "age != 25 AND id < LENGTH('abcd')"),
+            vec![2, 3]
+        );
+
+        // A NULL age is neither above nor below 30, so NOT doesn't find it either
+        assert_eq!(ids(&mut db, "NOT age > 30"), vec![1, 4]);
+        assert_eq!(ids(&mut db, "age IS NULL OR name = 'ann'"), vec![1, 5]);
+
+        run(
+            &mut db,
+            "UPDATE people SET city = 'nice' WHERE city = 'paris' AND age > 40",
+        );
+        assert_eq!(ids(&mut db, "city = 'paris'"), vec![1]);
+        run(&mut db, "DELETE FROM people WHERE age < 30 OR age IS NULL");
+        assert_eq!(ids(&mut db, "id > 0"), vec![2, 3, 4]);
+
+        let error = |db: &mut QueryExecutor, sql: &str| {
+            db.execute(QueryParser::parse(sql).unwrap())
+                .unwrap_err()
+                .to_string()
+        };
+        assert!(error(&mut db, "SELECT * FROM people WHERE age").contains("true or false"));
+        assert!(
+            error(&mut db, "SELECT * FROM people WHERE name > 3 AND age = 1")
+                .contains("Cannot compare")
+        );
+
+        // The same mismatch, with or without an index on the column
+        let mismatch = |db: &mut QueryExecutor, condition: &str| {
+            error(db, &format!("SELECT * FROM people WHERE {}", condition))
+        };
+        let expected = "Cannot compare INTEGER with TEXT";
+        assert_eq!(mismatch(&mut db, "age = 'a'"), expected);
+        assert_eq!(mismatch(&mut db, "id = 'a'"), expected);
+        run(&mut db, "CREATE INDEX ON people (age)");
+        assert_eq!(mismatch(&mut db, "age = 'a'"), expected);
+    }
+
+    #[test]
+    fn test_like_in_between_predicates() {
+        let mut db = QueryExecutor::new();
+        run(&mut db, "CREATE TABLE people (id INTEGER PRIMARY KEY, name TEXT, age INTEGER, status TEXT, data JSON)");
+        run(&mut db, "CREATE INDEX ON people (age)");
+        run(&mut db, "CREATE INDEX ON people (status)");
+        for (id, name, age, status) in [
+            (1, "Alice", 20, "active"),
+            (2, "Adam", 25, "banned"),
+            (3, "bob", 30, "active"),
+            (4, "Carol", 35, "away"),
+            (5, "50% off", 40, "active"),
+        ] {
+            run(
+                &mut db,
+                &format!(
+                    "INSERT INTO people VALUES ({}, '{}', {}, '{}', '{{\"tag\": \"{}\"}}')",
+                    id,
+                    name,
+                    age,
+                    status,
+                    name.to_lowercase()
+                ),
+            );
+        }
+        run(&mut db, "INSERT INTO people (id, name) VALUES (6, 'Anon')");
+
+        let ids = |db: &mut QueryExecutor, condition: &str| -> Vec<Value> {
+            let mut ids: Vec<Value> =
+                rows(db, &format!("SELECT id FROM people WHERE {}", condition))
+                    .into_iter()
+                    .map(|row| row.values[0].clone())
+                    .collect();
+            ids.sort_by(|a, b| a.compare(b).unwrap());
+            ids
+        };
+        let expect = |list: &[i64]| {
+            list.iter()
+                .map(|&id| Value::Integer(id))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(ids(&mut db, "name LIKE 'A%'"), expect(&[1, 2, 6]));
+        assert_eq!(
+            ids(&mut db, "name LIKE '_o%' OR name LIKE '%\\%%'"),
+            expect(&[3, 5])
+        );
+        assert_eq!(
+            ids(&mut db, "name ILIKE 'a%' AND name NOT LIKE '%m'"),
+            expect(&[1, 6])
+        );
+        assert_eq!(ids(&mut db, "data->>'tag' LIKE '%o%'"), expect(&[3, 4, 5]));
+
+        // IN and BETWEEN on indexed columns look up the index
+        assert_eq!(
+            ids(&mut db, "status IN ('away', 'banned', 'away')"),
+            expect(&[2, 4])
+        );
+        assert_eq!(ids(&mut db, "age BETWEEN 25 AND 35"), expect(&[2, 3, 4]));
+        assert_eq!(ids(&mut db, "age BETWEEN 35 AND 25"), expect(&[]));
+        assert_eq!(
+            ids(&mut db, "age BETWEEN 21 AND 39.5 AND status = 'active'"),
+            expect(&[3])
+        );
+        assert_eq!(ids(&mut db, "age NOT BETWEEN 25 AND 35"), expect(&[1, 5]));
+        assert_eq!(ids(&mut db, "id NOT IN (1, 2, 3)"), expect(&[4, 5, 6]));
+
+        // NULLs only answer IS [NOT] NULL
+        assert_eq!(ids(&mut db, "age IS NULL"), expect(&[6]));
+        assert_eq!(
+            ids(&mut db, "status IS NOT NULL AND age > 30"),
+            expect(&[4, 5])
+        );
+        assert_eq!(ids(&mut db, "id NOT IN (1, NULL)"), expect(&[]));
+
+        run(
+            &mut db,
+            "UPDATE people SET status = 'gone' WHERE age BETWEEN 20 AND 25",
+        );
+        run(&mut db, "DELETE FROM people WHERE status IN ('gone')");
+        assert_eq!(ids(&mut db, "age IN (20, 25, 30)"), expect(&[3]));
+
+        // A value the indexed column can't be compared with is an error, as in a scan
+        let sql = "SELECT * FROM people WHERE age IN (30, 'a')";
+        let error = db.execute(QueryParser::parse(sql).unwrap()).unwrap_err();
+        assert_eq!(error.to_string(), "Cannot compare INTEGER with TEXT");
+    }
+
+    #[test]
+    fn test_three_valued_logic() {
+        let mut db = QueryExecutor::new();
+        run(
+            &mut db,
+            "CREATE TABLE t (id INTEGER PRIMARY KEY, x INTEGER, flag BOOLEAN)",
+        );
+        run(&mut db, "CREATE INDEX ON t (x)");
+        run(&mut db, "INSERT INTO t VALUES (1, 10, true)");
+        run(&mut db, "INSERT INTO t VALUES (2, 20, false)");
+        run(&mut db, "INSERT INTO t VALUES (3, NULL, NULL)");
+
+        let ids = |db: &mut QueryExecutor, condition: &str| -> Vec<Value> {
+            let mut ids: Vec<Value> = rows(db, &format!("SELECT id FROM t WHERE {}", condition))
+                .into_iter()
+                .map(|row| row.values[0].clone())
+                .collect();
+            ids.sort_by(|a, b| a.compare(b).unwrap());
+            ids
+        };
+        let expect = |list: &[i64]| {
+            list.iter()
+                .map(|&id| Value::Integer(id))
+                .collect::<Vec<_>>()
+        };
+
+        // A comparison with NULL is unknown, and so is its negation
+        assert_eq!(ids(&mut db, "x = NULL"), expect(&[]));
+        assert_eq!(ids(&mut db, "x <> NULL"), expect(&[]));
+        assert_eq!(ids(&mut db, "NOT (x = 10)"), expect(&[2]));
+        assert_eq!(ids(&mut db, "x = 10 OR x <> 10"), expect(&[1, 2]));
+        assert_eq!(ids(&mut db, "NOT (x > 15 AND flag)"), expect(&[1, 2]));
+        assert_eq!(ids(&mut db, "x NOT IN (10, NULL)"), expect(&[]));
+
+        // IS [NOT] DISTINCT FROM treats NULL as a value (and uses the index)
+        assert_eq!(ids(&mut db, "x IS DISTINCT FROM 10"), expect(&[2, 3]));
+        assert_eq!(ids(&mut db, "x IS NOT DISTINCT FROM 20"), expect(&[2]));
+        assert_eq!(ids(&mut db, "x IS NOT DISTINCT FROM NULL"), expect(&[3]));
+        assert_eq!(ids(&mut db, "x IS NULL"), expect(&[3]));
+
+        // IS TRUE / FALSE / UNKNOWN
+        assert_eq!(ids(&mut db, "flag IS TRUE"), expect(&[1]));
+        assert_eq!(ids(&mut db, "flag IS NOT TRUE"), expect(&[2, 3]));
+        assert_eq!(ids(&mut db, "flag IS FALSE"), expect(&[2]));
+        assert_eq!(ids(&mut db, "x > 15 IS UNKNOWN"), expect(&[3]));
+        assert_eq!(
+            ids(&mut db, "(x > 15) IS NOT UNKNOWN AND flag"),
+            expect(&[1])
+        );
+
+        // UPDATE and DELETE leave the rows whose condition is unknown alone
+        run(&mut db, "UPDATE t SET flag = true WHERE x <> 10");
+        assert_eq!(ids(&mut db, "flag"), expect(&[1, 2]));
+        run(&mut db, "DELETE FROM t WHERE NOT (x < 15)");
+        assert_eq!(ids(&mut db, "true"), expect(&[1, 3]));
+    }
+
+    #[test]
+    fn test_select_list_expressions() {
+        let mut db = QueryExecutor::new();
+        run(&mut db, "CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT, price DECIMAL(10, 2), qty INTEGER, weight FLOAT)");
+        run(&mut db, "INSERT INTO items VALUES (1, 'pen', 1.25, 4, 0.5)");
+        run(
+            &mut db,
+            "INSERT INTO items VALUES (2, 'ink', 3.10, NULL, 2)",
+        );
+
+        let select = |db: &mut QueryExecutor, sql: &str| match run(db, sql) {
+            QueryResult::Rows { rows, column_names } => {
+                let values = rows.into_iter().map(|row| row.values).collect::<Vec<_>>();
+                (column_names, values)
+            }
+            other => panic!("expected rows, got {:?}", other),
+        };
+
+        // Only the selected columns, named by their alias or as written
+        let (names, values) = select(
+            &mut db,
+            "SELECT name, price * qty AS total, qty / 3, 'item #' || id, 7 FROM items WHERE id = 1",
+        );
+        assert_eq!(names, ["name", "total", "qty / 3", "'item #' || id", "7"]);
+        assert_eq!(
+            values[0],
+            [
+                Value::Text("pen".to_string()),
+                Value::Decimal("5.00".parse().unwrap()),
+                Value::Integer(1),
+                Value::Text("item #1".to_string()),
+                Value::Integer(7),
+            ]
+        );
+
+        // NULL in, NULL out; a float makes the result a float
+        let (_, values) = select(
+            &mut db,
+            "SELECT qty + 1, name || NULL, weight * 2, -price FROM items WHERE id = 2",
+        );
+        assert_eq!(
+            values[0],
+            [
+                Value::Null,
+                Value::Null,
+                Value::Float(4.0),
+                Value::Decimal("-3.10".parse().unwrap())
+            ]
+        );
+        let (_, values) = select(
+            &mut db,
+            "SELECT (price + 1) % 2, price / 3 FROM items WHERE id = 2",
+        );
+        assert_eq!(values[0][0].to_string(), "0.10");
+        assert_eq!(values[0][1].to_string(), "1.0333333333333333");
+
+        // A volatile function runs once per row, not once per statement
+        let (_, values) = select(&mut db, "SELECT id, gen_random_uuid() AS u FROM items");
+        assert_eq!(values.len(), 2);
+        assert!(matches!(values[0][1], Value::Uuid(_)));
+        assert_ne!(values[0][1], values[1][1]);
+
+        let error = |db: &mut QueryExecutor, sql: &str| {
+            db.execute(QueryParser::parse(sql).unwrap())
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error(&mut db, "SELECT id / 0 FROM items"),
+            "Division by zero"
+        );
+        assert!(error(&mut db, "SELECT missing + 1 FROM items WHERE id = 99").contains("missing"));
+        assert!(QueryParser::parse("SELECT *, id FROM items").is_err());
+    }
+
+    #[test]
+    fn test_order_by() {
+        let mut db = QueryExecutor::new();
+        // The same rows twice: "indexed" reads them in index order, "plain" sorts them
+        for table in ["indexed", "plain"] {
+            let sql = "(id INTEGER PRIMARY KEY, city TEXT, age INTEGER)";
+            run(&mut db, &format!("CREATE TABLE {} {}", table, sql));
+            for values in [
+                "3, 'Rome', 30",
+                "1, NULL, 25",
+                "4, 'Oslo', NULL",
+                "2, 'Oslo', 41",
+                "5, 'Rome', 25",
+            ] {
+                run(
+                    &mut db,
+                    &format!("INSERT INTO {} VALUES ({})", table, values),
+                );
+            }
+        }
+        run(&mut db, "CREATE INDEX ON indexed (city)");
+        run(&mut db, "CREATE UNIQUE INDEX ON indexed (age, id)");
+
+        // SELECT ... FROM each table ..., which must give the same ids
+        let ids = |db: &mut QueryExecutor, select: &str, rest: &str| {
+            let [indexed, plain] = ["indexed", "plain"].map(|table| {
+                let sql = format!("{} FROM {} {}", select, table, rest);
+                let ids: Vec<String> = rows(db, &sql)
+                    .iter()
+                    .map(|row| row.values[0].to_string())
+                    .collect();
+                ids.join(",")
+            });
+            assert_eq!(indexed, plain, "{} ... {}", select, rest);
+            indexed
+        };
+
+        // NULL is larger than any value, unless NULLS FIRST/LAST says otherwise
+        assert_eq!(ids(&mut db, "SELECT id", "ORDER BY id DESC"), "5,4,3,2,1");
+        assert_eq!(ids(&mut db, "SELECT id", "ORDER BY city, id"), "2,4,3,5,1");
+        assert_eq!(
+            ids(&mut db, "SELECT id", "ORDER BY city DESC, id DESC"),
+            "1,5,3,4,2"
+        );
+        assert_eq!(
+            ids(&mut db, "SELECT id", "ORDER BY city NULLS FIRST, id"),
+            "1,2,4,3,5"
+        );
+        assert_eq!(
+            ids(&mut db, "SELECT id", "ORDER BY city DESC NULLS LAST, id"),
+            "3,5,2,4,1"
+        );
+        assert_eq!(
+            ids(
+                &mut db,
+                "SELECT id",
+                "ORDER BY age DESC NULLS LAST, id DESC"
+            ),
+            "2,3,5,1,4"
+        );
+        assert_eq!(ids(&mut db, "SELECT id", "ORDER BY age, id"), "1,5,3,2,4");
+        assert_eq!(
+            ids(&mut db, "SELECT id", "WHERE city = 'Rome' ORDER BY age"),
+            "5,3"
+        );
+        assert_eq!(
+            ids(&mut db, "SELECT id", "WHERE age > 26 ORDER BY city DESC"),
+            "3,2"
+        );
+
+        // Expressions, output aliases and positions in the select list
+        assert_eq!(
+            ids(&mut db, "SELECT id", "ORDER BY id % 2, id DESC"),
+            "4,2,5,3,1"
+        );
+        assert_eq!(
+            ids(&mut db, "SELECT id, age * -1 AS neg", "ORDER BY neg, id"),
+            "2,3,1,5,4"
+        );
+        assert_eq!(
+            ids(&mut db, "SELECT id, city", "ORDER BY 2 DESC, 1"),
+            "1,3,5,2,4"
+        );
+
+        let error = |db: &mut QueryExecutor, sql: &str| {
+            db.execute(QueryParser::parse(sql).unwrap())
+                .unwrap_err()
+                .to_string()
+        };
+        assert!(error(
+            &mut db,
+            "SELECT * FROM plain WHERE id = 99 ORDER BY missing"
+        )
+        .contains("missing"));
+        assert!(QueryParser::parse("SELECT * FROM plain ORDER BY 1").is_err());
+        assert!(QueryParser::parse("SELECT id FROM plain ORDER BY 2").is_err());
+    }
+}
@@ -0,0 +1,267 @@
+// Foreign keys
+// A foreign key links two tables: orders (customer_id) REFERENCES customers (id)
+// says every order belongs to a customer that exists. The database keeps the
+// link intact (referential integrity) instead of leaving it to the application:
+//   - a row written to orders must name a customer that exists (or be NULL)
+//   - when a customer is deleted, or their id changes, the orders that point
+//     at them follow the constraint's action:
+//       RESTRICT (or NO ACTION)  the statement fails
+//       CASCADE                  the orders are deleted too, or get the new id
+//       SET NULL                 the orders' customer_id becomes NULL
+//
+// The checks run after the statement has changed its own table, so a DELETE
+// that removes a parent and its children at once is fine. If a check fails,
+// the statement's transaction is rolled back, undoing everything it did.
+//
+// Constraints span tables, so they live here rather than in Table: a table
+// only knows its own rows, the executor knows every table.
+
+use crate::storage::mvcc::Snapshot;
+use crate::storage::table::Table;
+use crate::storage::{ForeignKey, ReferentialAction, Row, Schema, Value};
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+
+/// Check the foreign keys of a table about to be created, and fill in the
+/// referenced columns left out of REFERENCES customers (the primary key)
+pub fn resolve(tables: &HashMap<String, Table>, name: &str, schema: &mut Schema) -> Result<()> {
+    let own_schema = schema.clone();
+
+    for fk in &mut schema.foreign_keys {
+        // A table can reference itself: employees (manager_id) REFERENCES employees (id)
+        let ref_schema = if fk.ref_table == name {
+            &own_schema
+        } else {
+            tables
+                .get(&fk.ref_table)
+                .map(|table| table.get_schema())
+                .ok_or_else(|| anyhow!("Table '{}' not found", fk.ref_table))?
+        };
+
+        if fk.ref_columns.is_empty() {
+            let pk_index = ref_schema.get_primary_key_index().ok_or_else(|| {
+                anyhow!("Table '{}' has no primary key to reference", fk.ref_table)
+            })?;
+            fk.ref_columns = vec![ref_schema.columns[pk_index].name.clone()];
+        }
+        if fk.columns.len() != fk.ref_columns.len() {
+            return Err(anyhow!(
+                "Foreign key ({}) references {} column(s) of table '{}'",
+                fk.columns.join(", "),
+                fk.ref_columns.len(),
+                fk.ref_table
+            ));
+        }
+        if !ref_schema.is_unique_key(&fk.ref_columns) {
+            return Err(anyhow!(
+                "Foreign key must reference a primary key or UNIQUE columns, but ({}) of table '{}' is neither",
+                fk.ref_columns.join(", "),
+                fk.ref_table
+            ));
+        }
+
+        // Keys are compared as they are stored, so the types have to match
+        for (column, ref_column) in fk.columns.iter().zip(&fk.ref_columns) {
+            let column_type = &own_schema.columns[column_index(&own_schema, column)?].data_type;
+            let ref_type = &ref_schema.columns[column_index(ref_schema, ref_column)?].data_type;
+            if column_type != ref_type {
+                return Err(anyhow!(
+                    "Foreign key column '{}' is {}, but '{}.{}' is {}",
+                    column,
+                    column_type,
+                    fk.ref_table,
+                    ref_column,
+                    ref_type
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check that rows written to a table only reference keys that exist
+pub fn check_references(
+    tables: &HashMap<String, Table>,
+    table_name: &str,
+    rows: &[Row],
+    snapshot: &Snapshot,
+) -> Result<()> {
+    let Some(table) = tables.get(table_name) else {
+        return Ok(());
+    };
+
+    for fk in &table.get_schema().foreign_keys {
+        let columns = column_indexes(table.get_schema(), &fk.columns)?;
+        let ref_table = tables
+            .get(&fk.ref_table)
+            .ok_or_else(|| anyhow!("Table '{}' not found", fk.ref_table))?;
+        let ref_columns = column_indexes(ref_table.get_schema(), &fk.ref_columns)?;
+
+        for row in rows {
+            // A key with a NULL references nothing, so it is always fine
+            let key = key_of(row, &columns);
+            if key.iter().any(|value| matches!(value, Value::Null)) {
+                continue;
+            }
+            if ref_table.find_key(&ref_columns, &key, snapshot)?.is_empty() {
+                return Err(anyhow!(
+                    "Foreign key violation: {} is not present in table '{}'",
+                    describe(&fk.columns, &key),
+                    fk.ref_table
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check the rows an UPDATE wrote: the ones whose foreign keys changed must
+/// reference existing keys, and the rows referencing keys that changed
+/// follow their ON UPDATE action
+pub fn after_update(
+    tables: &mut HashMap<String, Table>,
+    table_name: &str,
+    updated: &[(Row, Row)],
+    snapshot: &Snapshot,
+) -> Result<()> {
+    if let Some(table) = tables.get(table_name) {
+        let schema = table.get_schema();
+        let mut changed = Vec::new();
+        for (old_row, new_row) in updated {
+            for fk in &schema.foreign_keys {
+                let columns = column_indexes(schema, &fk.columns)?;
+                if key_of(old_row, &columns) != key_of(new_row, &columns) {
+                    changed.push(new_row.clone());
+                    break;
+                }
+            }
+        }
+        check_references(tables, table_name, &changed, snapshot)?;
+    }
+
+    for (child_name, fk) in referencing(tables, table_name) {
+        let ref_columns = column_indexes(tables[table_name].get_schema(), &fk.ref_columns)?;
+        let columns = column_indexes(tables[&child_name].get_schema(), &fk.columns)?;
+
+        for (old_row, new_row) in updated {
+            let old_key = key_of(old_row, &ref_columns);
+            let new_key = key_of(new_row, &ref_columns);
+            if old_key == new_key {
+                continue;
+            }
+
+            let child = tables
+                .get_mut(&child_name)
+                .expect("referencing table exists");
+            let row_ids = child.find_key(&columns, &old_key, snapshot)?;
+            if row_ids.is_empty() {
+                continue;
+            }
+
+            let changes = match fk.on_update {
+                ReferentialAction::Restrict => {
+                    return Err(still_referenced(&fk, &old_key, &child_name));
+                }
+                ReferentialAction::Cascade => columns.iter().copied().zip(new_key).collect(),
+                ReferentialAction::SetNull => {
+                    columns.iter().map(|&index| (index, Value::Null)).collect()
+                }
+            };
+            let child_updated = child.update_rows(&row_ids, changes, snapshot)?;
+            after_update(tables, &child_name, &child_updated, snapshot)?;
+        }
+    }
+    Ok(())
+}
+
+/// Make the rows referencing deleted rows follow their ON DELETE action
+pub fn after_delete(
+    tables: &mut HashMap<String, Table>,
+    table_name: &str,
+    deleted: &[Row],
+    snapshot: &Snapshot,
+) -> Result<()> {
+    for (child_name, fk) in referencing(tables, table_name) {
+        let ref_columns = column_indexes(tables[table_name].get_schema(), &fk.ref_columns)?;
+        let columns = column_indexes(tables[&child_name].get_schema(), &fk.columns)?;
+
+        for row in deleted {
+            let key = key_of(row, &ref_columns);
+            let child = tables
+                .get_mut(&child_name)
+                .expect("referencing table exists");
+            let row_ids = child.find_key(&columns, &key, snapshot)?;
+            if row_ids.is_empty() {
+                continue;
+            }
+
+            match fk.on_delete {
+                ReferentialAction::Restrict => {
+                    return Err(still_referenced(&fk, &key, &child_name));
+                }
+                ReferentialAction::Cascade => {
+                    let child_deleted = child.delete_rows(&row_ids, snapshot)?;
+                    after_delete(tables, &child_name, &child_deleted, snapshot)?;
+                }
+                ReferentialAction::SetNull => {
+                    let changes = columns.iter().map(|&index| (index, Value::Null)).collect();
+                    let child_updated = child.update_rows(&row_ids, changes, snapshot)?;
+                    after_update(tables, &child_name, &child_updated, snapshot)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The foreign keys (and their tables) that reference a table
+fn referencing(tables: &HashMap<String, Table>, table_name: &str) -> Vec<(String, ForeignKey)> {
+    let mut found: Vec<(String, ForeignKey)> = tables
+        .iter()
+        .flat_map(|(name, table)| {
+            table
+                .get_schema()
+                .foreign_keys
+                .iter()
+                .filter(|fk| fk.ref_table == table_name)
+                .map(move |fk| (name.clone(), fk.clone()))
+        })
+        .collect();
+    // Tables are kept in a HashMap; sort so the actions always run in the same order
+    found.sort_by(|a, b| a.0.cmp(&b.0));
+    found
+}
+
+fn column_index(schema: &Schema, name: &str) -> Result<usize> {
+    schema
+        .get_column_index(name)
+        .ok_or_else(|| anyhow!("Column not found: {}", name))
+}
+
+fn column_indexes(schema: &Schema, names: &[String]) -> Result<Vec<usize>> {
+    names
+        .iter()
+        .map(|name| column_index(schema, name))
+        .collect()
+}
+
+fn key_of(row: &Row, columns: &[usize]) -> Vec<Value> {
+    columns
+        .iter()
+        .map(|&index| row.values[index].clone())
+        .collect()
+}
+
+/// (a, b) = (1, 2)
+fn describe(columns: &[String], key: &[Value]) -> String {
+    let values: Vec<String> = key.iter().map(|value| value.to_string()).collect();
+    format!("({}) = ({})", columns.join(", "), values.join(", "))
+}
+
+fn still_referenced(fk: &ForeignKey, key: &[Value], child_name: &str) -> anyhow::Error {
+    anyhow!(
+        "Foreign key violation: {} is still referenced from table '{}'",
+        describe(&fk.ref_columns, key),
+        child_name
+    )
+}
@@ -0,0 +1,140 @@
+// Built-in SQL functions
+// A function takes values and returns a value. The parser evaluates calls
+// whose arguments are constants, like NOW() or LENGTH(X'CAFE').
+// (The JSON functions applied to a column, like json_extract(data, '$.a'),
+// are column paths instead - see storage/json.rs.)
+//
+// Functions follow SQL's rule for NULL: a NULL argument gives a NULL result.
+
+use crate::storage::json::{self, Json};
+use crate::storage::temporal::{self, Timestamp};
+use crate::storage::uuid::Uuid;
+use crate::storage::{DataType, Value};
+use anyhow::{anyhow, Result};
+
+/// Whether a function can return something different each time it is
+/// called with the same arguments, so it must not be worked out up front
+pub fn is_volatile(name: &str) -> bool {
+    matches!(
+        name.to_lowercase().as_str(),
+        "gen_random_uuid" | "uuid_generate_v4" | "nextval" | "setval"
+    )
+}
+
+/// Call a function by its (case-insensitive) name
+pub fn call(name: &str, args: &[Value]) -> Result<Value> {
+    let name = name.to_lowercase();
+
+    if args.iter().any(|arg| matches!(arg, Value::Null)) {
+        return Ok(Value::Null);
+    }
+
+    match (name.as_str(), args) {
+        // Date and time (see temporal.rs)
+        // NOW() is the time the statement was parsed
+        ("now" | "current_timestamp" | "localtimestamp", []) => {
+            Ok(Value::Timestamp(Timestamp::now()))
+        }
+        ("current_date", []) => Ok(Value::Date(Timestamp::now().date())),
+        ("date_trunc", [Value::Text(unit), value]) => temporal::date_trunc(unit, value),
+        ("extract", [Value::Text(field), value]) => temporal::extract(field, value),
+
+        // Lengths: characters for text, bytes for binary data
+        ("length" | "char_length" | "character_length", [Value::Text(s)]) => {
+            Ok(Value::Integer(s.chars().count() as i64))
+        }
+        ("length" | "octet_length", [Value::Bytes(b)]) => Ok(Value::Integer(b.len() as i64)),
+        ("octet_length", [Value::Text(s)]) => Ok(Value::Integer(s.len() as i64)),
+
+        // A new random UUID every time
+        ("gen_random_uuid" | "uuid_generate_v4", []) => Ok(Value::Uuid(Uuid::new_v4())),
+
+        // Sequences (see sequence.rs) need the database, so only INSERT can call them
+        ("nextval" | "currval" | "setval", _) => Err(anyhow!(
+            "{}() can only be used in INSERT values and column defaults",
+            name
+        )),
+
+        // JSON (see json.rs): json_extract('{"a": [1, 2]}', '$.a[1]') is 2
+        ("json_extract", [doc, Value::Text(path)]) => {
+            Ok(json_at(doc, path)?.map_or(Value::Null, Value::Json))
+        }
+        ("json_array_length", [doc]) => json_array_length(doc, "$"),
+        ("json_array_length", [doc, Value::Text(path)]) => json_array_length(doc, path),
+
+        _ => Err(anyhow!(
+            "Unsupported function: {}({})",
+            name.to_uppercase(),
+            args.iter()
+                .map(|arg| arg.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+/// Follow a JSON path ('$.a[0]') into a document, given as JSON or as text
+/// Returns None if the path leads nowhere
+fn json_at(doc: &Value, path: &str) -> Result<Option<Json>> {
+    let steps = json::parse_json_path(path)?;
+    match doc.convert_to(&DataType::Json)? {
+        Value::Json(doc) => Ok(json::follow(&doc, &steps).cloned()),
+        _ => Err(anyhow!("Expected a JSON document, got {}", doc)),
+    }
+}
+
+/// The number of elements of the array at a path (0 if it's not an array)
+fn json_array_length(doc: &Value, path: &str) -> Result<Value> {
+    Ok(json_at(doc, path)?.map_or(Value::Null, |found| {
+        Value::Integer(json::array_length(&found) as i64)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_length_functions() {
+        let text = [Value::Text("héllo".to_string())];
+        let bytes = [Value::Bytes(vec![0xca, 0xfe])];
+
+        assert_eq!(call("LENGTH", &text).unwrap(), Value::Integer(5));
+        assert_eq!(call("octet_length", &text).unwrap(), Value::Integer(6));
+        assert_eq!(call("length", &bytes).unwrap(), Value::Integer(2));
+        assert_eq!(call("octet_length", &bytes).unwrap(), Value::Integer(2));
+        assert_eq!(call("length", &[Value::Null]).unwrap(), Value::Null);
+        assert!(call("length", &[Value::Integer(5)]).is_err());
+        assert!(call("no_such_function", &[]).is_err());
+    }
+
+    #[test]
+    fn test_json_functions() {
+        let doc = Value::Text(r#"{"a": {"b": [1, 2, 3]}}"#.to_string());
+        let path = |p: &str| Value::Text(p.to_string());
+
+        let b = call("json_extract", &[doc.clone(), path("$.a.b")]).unwrap();
+        assert_eq!(b.to_string(), "[1,2,3]");
+        assert_eq!(
+            call("json_extract", &[doc.clone(), path("$.a.b[1]")])
+                .unwrap()
+                .to_string(),
+            "2"
+        );
+        assert_eq!(
+            call("json_extract", &[doc.clone(), path("$.x")]).unwrap(),
+            Value::Null
+        );
+        assert_eq!(call("json_array_length", &[b]).unwrap(), Value::Integer(3));
+        assert_eq!(
+            call("json_array_length", &[doc.clone(), path("$.a.b")]).unwrap(),
+            Value::Integer(3)
+        );
+        assert_eq!(
+            call("json_array_length", &[path("{}")]).unwrap(),
+            Value::Integer(0)
+        );
+        assert!(call("json_extract", &[path("not json"), path("$")]).is_err());
+        assert!(call("json_extract", &[doc, path("a.b")]).is_err());
+    }
+}
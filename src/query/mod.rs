@@ -1,5 +1,7 @@
 // Query module - handles SQL parsing and execution
 pub mod executor;
+pub mod foreign_keys;
+pub mod functions;
 pub mod parser;
 
 pub use executor::QueryExecutor;
@@ -2,11 +2,19 @@
 // This module converts SQL strings into structured queries
 // We use the sqlparser crate to handle the complex SQL grammar
 
-use crate::storage::{Column, DataType, Schema, Value};
+use super::functions;
+use crate::storage::decimal::{Decimal, MAX_PRECISION};
+use crate::storage::expr::{BinaryOp, Expr as RowExpr, SortKey};
+use crate::storage::hex_format;
+use crate::storage::json::{self, ColumnRef, PathOutput, PathStep};
+use crate::storage::sequence::Sequence;
+use crate::storage::temporal::{self, Interval};
+use crate::storage::{Check, Column, DataType, ForeignKey, ReferentialAction, Row, Schema, Value};
 use anyhow::{anyhow, Result};
 use sqlparser::ast::{
-    BinaryOperator, DataType as SqlDataType, Expr, Select, SetExpr, Statement,
-    TableFactor, Value as SqlValue,
+    BinaryOperator, DataType as SqlDataType, ExactNumberInfo, Expr, Function, FunctionArg,
+    FunctionArgExpr, FunctionArguments, OrderByExpr, Select, SelectItem, SequenceOptions, SetExpr,
+    Statement, TableConstraint, TableFactor, UnaryOperator, Value as SqlValue,
 };
 use sqlparser::dialect::GenericDialect;
 use sqlparser::parser::Parser;
@@ -18,41 +26,70 @@ pub enum Query {
     CreateTable {
         name: String,
         schema: Schema,
+        /// The sequences of its SERIAL and identity columns
+        sequences: Vec<Sequence>,
     },
-    /// INSERT INTO tablename VALUES (val1, val2, ...)
+    /// CREATE SEQUENCE [IF NOT EXISTS] name [INCREMENT BY n] [START WITH n]
+    CreateSequence {
+        sequence: Sequence,
+        if_not_exists: bool,
+    },
+    /// INSERT INTO tablename [(col1, col2, ...)] VALUES (val1, val2, ...)
     Insert {
         table_name: String,
-        values: Vec<Value>,
+        /// The columns the values are for; None for every column, in order
+        columns: Option<Vec<String>>,
+        /// None stands for DEFAULT
+        /// Values are expressions, so they can call nextval('s')
+        values: Vec<Option<RowExpr>>,
     },
-    /// SELECT * FROM tablename [WHERE column = value]
+    /// SELECT * FROM tablename [WHERE condition] [ORDER BY keys]
+    /// SELECT name, data->>'path', price * 2 AS double, ... FROM tablename [WHERE condition]
     Select {
         table_name: String,
-        where_clause: Option<WhereClause>,
+        /// The columns to return; None for *
+        columns: Option<Vec<SelectColumn>>,
+        where_clause: Option<RowExpr>,
+        /// ORDER BY price DESC, name ASC NULLS FIRST; empty for any order
+        order_by: Vec<SortKey>,
     },
-    /// UPDATE tablename SET column = value WHERE column = value
+    /// UPDATE tablename SET column = value WHERE condition
     Update {
         table_name: String,
         set_column: String,
         set_value: Value,
-        where_clause: WhereClause,
+        where_clause: RowExpr,
     },
-    /// DELETE FROM tablename WHERE column = value
+    /// DELETE FROM tablename WHERE condition
     Delete {
         table_name: String,
-        where_clause: WhereClause,
+        where_clause: RowExpr,
     },
-    /// CREATE INDEX ON tablename (column)
+    /// CREATE [UNIQUE] INDEX ON tablename (column, ...)
+    /// Only unique indexes can span several columns
     CreateIndex {
         table_name: String,
-        column_name: String,
+        columns: Vec<String>,
+        unique: bool,
     },
+    /// BEGIN [TRANSACTION] - start a multi-statement transaction
+    Begin,
+    /// COMMIT - make the transaction's changes permanent
+    Commit,
+    /// ROLLBACK - undo the transaction's changes
+    Rollback,
+    /// VACUUM [tablename] - remove row versions no transaction can see anymore
+    Vacuum { table_name: Option<String> },
 }
 
-/// Represents a WHERE clause (simplified - only supports single conditions)
-#[derive(Debug, Clone)]
-pub struct WhereClause {
-    pub column: String,
-    pub value: Value,
+/// One column of a SELECT list: price * quantity AS total
+#[derive(Debug)]
+pub struct SelectColumn {
+    /// What to work out for each row
+    pub expr: RowExpr,
+    /// The name of the column in the result: its alias, or the expression
+    /// as written (price * quantity)
+    pub name: String,
 }
 
 /// The query parser
@@ -62,6 +99,11 @@ impl QueryParser {
     /// Parse a SQL string into a Query
     /// This is the main entry point for parsing SQL
     pub fn parse(sql: &str) -> Result<Query> {
+        // VACUUM is not standard SQL, so sqlparser doesn't know about it
+        if let Some(query) = Self::parse_vacuum(sql)? {
+            return Ok(query);
+        }
+
         // The sqlparser crate handles the complex SQL grammar
         let dialect = GenericDialect {};
         let ast = Parser::parse_sql(&dialect, sql)
@@ -89,20 +131,80 @@ impl QueryParser {
             Statement::CreateIndex(create_index) => {
                 Self::parse_create_index(create_index)
             }
+            Statement::CreateSequence {
+                temporary: false,
+                if_not_exists,
+                name,
+                data_type: None,
+                sequence_options,
+                owned_by: None,
+            } => Ok(Query::CreateSequence {
+                sequence: Self::parse_sequence(&name.to_string(), sequence_options)?,
+                if_not_exists: *if_not_exists,
+            }),
+            Statement::StartTransaction { .. } => Ok(Query::Begin),
+            Statement::Commit { .. } => Ok(Query::Commit),
+            Statement::Rollback {
+                savepoint: None, ..
+            } => Ok(Query::Rollback),
+            Statement::Rollback {
+                savepoint: Some(_), ..
+            } => Err(anyhow!("Savepoints are not supported")),
             _ => Err(anyhow!("Unsupported SQL statement")),
         }
     }
 
+    /// Parse VACUUM [tablename]
+    /// Returns None if the statement is not a VACUUM
+    fn parse_vacuum(sql: &str) -> Result<Option<Query>> {
+        let mut words = sql.trim().trim_end_matches(';').split_whitespace();
+        match words.next() {
+            Some(word) if word.eq_ignore_ascii_case("VACUUM") => {}
+            _ => return Ok(None),
+        }
+
+        let table_name = words.next().map(|name| name.to_string());
+        if words.next().is_some() {
+            return Err(anyhow!("Expected VACUUM [table_name]"));
+        }
+
+        Ok(Some(Query::Vacuum { table_name }))
+    }
+
     /// Parse CREATE TABLE statement
     fn parse_create_table(
         create_table: &sqlparser::ast::CreateTable,
     ) -> Result<Query> {
         let table_name = create_table.name.to_string();
         let mut columns = Vec::new();
+        let mut unique = Vec::new();
+        let mut foreign_keys = Vec::new();
+        let mut checks = Vec::new();
+        let mut sequences = Vec::new();
 
         for column_def in &create_table.columns {
             let name = column_def.name.to_string();
-            let data_type = Self::parse_data_type(&column_def.data_type)?;
+
+            // SERIAL is an INTEGER column numbered by a sequence of its own
+            // (see storage/sequence.rs), and so are identity columns:
+            //   id INTEGER GENERATED BY DEFAULT AS IDENTITY (START WITH 100)
+            //   id INTEGER AUTO_INCREMENT       (MySQL; SQLite's AUTOINCREMENT too)
+            let serial = matches!(
+                &column_def.data_type,
+                SqlDataType::Custom(type_name, args) if args.is_empty() && matches!(
+                    type_name.to_string().to_uppercase().as_str(),
+                    "SERIAL" | "SERIAL4" | "BIGSERIAL" | "SERIAL8" | "SMALLSERIAL" | "SERIAL2"
+                )
+            );
+            let data_type = if serial {
+                DataType::Integer
+            } else {
+                Self::parse_data_type(&column_def.data_type)?
+            };
+            let sequence_name = format!("{}_{}_seq", table_name, name);
+            let mut identity = serial
+                .then(|| Self::parse_sequence(&sequence_name, &[]))
+                .transpose()?;
 
             // Check for PRIMARY KEY constraint
             let primary_key = column_def
@@ -111,25 +213,250 @@ impl QueryParser {
                 .any(|opt| matches!(opt.option, sqlparser::ast::ColumnOption::Unique { is_primary: true, .. }));
 
             // Check for NOT NULL constraint
-            let nullable = !column_def
+            let mut nullable = !column_def
                 .options
                 .iter()
                 .any(|opt| matches!(opt.option, sqlparser::ast::ColumnOption::NotNull));
 
-            columns.push(Column {
+            // Check for UNIQUE constraint (the primary key is unique anyway)
+            let is_unique = column_def.options.iter().any(|opt| {
+                matches!(
+                    opt.option,
+                    sqlparser::ast::ColumnOption::Unique {
+                        is_primary: false,
+                        ..
+                    }
+                )
+            });
+            if is_unique && !primary_key {
+                unique.push(vec![name.clone()]);
+            }
+
+            // Check for REFERENCES table (column), DEFAULT value and CHECK (condition)
+            let mut default = None;
+            for opt in &column_def.options {
+                match &opt.option {
+                    sqlparser::ast::ColumnOption::ForeignKey {
+                        foreign_table,
+                        referred_columns,
+                        on_delete,
+                        on_update,
+                        ..
+                    } => {
+                        foreign_keys.push(ForeignKey {
+                            columns: vec![name.clone()],
+                            ref_table: foreign_table.to_string(),
+                            ref_columns: referred_columns.iter().map(|c| c.to_string()).collect(),
+                            on_delete: Self::parse_referential_action(on_delete)?,
+                            on_update: Self::parse_referential_action(on_update)?,
+                        });
+                    }
+                    sqlparser::ast::ColumnOption::Default(expr) => {
+                        let expr = Self::parse_expr(expr)?;
+                        if !expr.columns().is_empty() {
+                            return Err(anyhow!(
+                                "The default of column '{}' cannot use columns",
+                                name
+                            ));
+                        }
+                        default = Some(expr);
+                    }
+                    sqlparser::ast::ColumnOption::Check(expr) => {
+                        let constraint_name = match &opt.name {
+                            Some(constraint_name) => constraint_name.value.clone(),
+                            None => format!("{}_{}_check", table_name, name),
+                        };
+                        checks.push((constraint_name, Self::parse_expr(expr)?));
+                    }
+                    // GENERATED { ALWAYS | BY DEFAULT } AS IDENTITY [(options)]
+                    // ALWAYS is treated like BY DEFAULT: explicit values are accepted
+                    sqlparser::ast::ColumnOption::Generated {
+                        generated_as:
+                            sqlparser::ast::GeneratedAs::Always | sqlparser::ast::GeneratedAs::ByDefault,
+                        sequence_options,
+                        generation_expr: None,
+                        ..
+                    } => {
+                        let options = sequence_options.as_deref().unwrap_or_default();
+                        identity = Some(Self::parse_sequence(&sequence_name, options)?);
+                    }
+                    sqlparser::ast::ColumnOption::Generated { .. } => {
+                        return Err(anyhow!("Generated column '{}' is not supported", name));
+                    }
+                    sqlparser::ast::ColumnOption::DialectSpecific(tokens)
+                        if tokens.len() == 1
+                            && matches!(
+                                tokens[0].to_string().as_str(),
+                                "AUTO_INCREMENT" | "AUTOINCREMENT"
+                            ) =>
+                    {
+                        identity = Some(Self::parse_sequence(&sequence_name, &[])?);
+                    }
+                    _ => {}
+                }
+            }
+
+            // The column's default takes the next number of its sequence
+            if let Some(sequence) = identity {
+                if data_type != DataType::Integer {
+                    return Err(anyhow!(
+                        "Identity column '{}' must be an INTEGER, not {}",
+                        name,
+                        data_type
+                    ));
+                }
+                if default.is_some() {
+                    return Err(anyhow!("Identity column '{}' cannot have a DEFAULT", name));
+                }
+                default = Some(RowExpr::Function {
+                    name: "nextval".to_string(),
+                    args: vec![RowExpr::Literal(Value::Text(sequence.name.clone()))],
+                });
+                nullable = false;
+                sequences.push(sequence);
+            }
+
+            let column = Column {
                 name,
                 data_type,
                 primary_key,
                 nullable,
-            });
+                default,
+            };
+
+            // Try the default once, so a default that doesn't fit the
+            // column is reported now rather than on every INSERT
+            // (numbers from a sequence are always integers)
+            if let Some(default) = column
+                .default
+                .as_ref()
+                .filter(|default| default.sequence().is_none())
+            {
+                column.coerce(
+                    default.evaluate(&Schema::new(Vec::new()), &Row { values: Vec::new() })?,
+                )?;
+            }
+            columns.push(column);
+        }
+
+        // Table constraints: UNIQUE (a, b), PRIMARY KEY (id) and
+        // FOREIGN KEY (a, b) REFERENCES t (x, y)
+        for constraint in &create_table.constraints {
+            match constraint {
+                TableConstraint::ForeignKey {
+                    columns: names,
+                    foreign_table,
+                    referred_columns,
+                    on_delete,
+                    on_update,
+                    ..
+                } => {
+                    foreign_keys.push(ForeignKey {
+                        columns: names.iter().map(|name| name.to_string()).collect(),
+                        ref_table: foreign_table.to_string(),
+                        ref_columns: referred_columns.iter().map(|c| c.to_string()).collect(),
+                        on_delete: Self::parse_referential_action(on_delete)?,
+                        on_update: Self::parse_referential_action(on_update)?,
+                    });
+                }
+                TableConstraint::Unique { columns: names, .. } => {
+                    unique.push(names.iter().map(|name| name.to_string()).collect());
+                }
+                TableConstraint::PrimaryKey { columns: names, .. } => {
+                    let [name] = names.as_slice() else {
+                        return Err(anyhow!("Only single column primary keys are supported"));
+                    };
+                    let column = columns
+                        .iter_mut()
+                        .find(|column| column.name == name.to_string())
+                        .ok_or_else(|| anyhow!("Column not found: {}", name))?;
+                    column.primary_key = true;
+                }
+                TableConstraint::Check { name, expr } => {
+                    let constraint_name = match name {
+                        Some(name) => name.value.clone(),
+                        None => format!("{}_check", table_name),
+                    };
+                    checks.push((constraint_name, Self::parse_expr(expr)?));
+                }
+                _ => return Err(anyhow!("Unsupported table constraint: {}", constraint)),
+            }
         }
+        if columns.iter().filter(|column| column.primary_key).count() > 1 {
+            return Err(anyhow!("A table can only have one primary key"));
+        }
+
+        let mut schema = Schema::new(columns);
+        schema.unique = unique;
+        schema.foreign_keys = foreign_keys;
 
+        // Like PostgreSQL, a generated name that is taken gets a number:
+        // orders_check, orders_check1, ...
+        for (name, condition) in checks {
+            for column in condition.columns() {
+                column.index_in(&schema)?;
+            }
+            let mut unique_name = name.clone();
+            let mut n = 0;
+            while schema.checks.iter().any(|check| check.name == unique_name) {
+                n += 1;
+                unique_name = format!("{}{}", name, n);
+            }
+            schema.checks.push(Check {
+                name: unique_name,
+                condition,
+            });
+        }
         Ok(Query::CreateTable {
             name: table_name,
-            schema: Schema::new(columns),
+            schema,
+            sequences,
         })
     }
 
+    /// Parse the options of CREATE SEQUENCE, or of an identity column
+    /// Like PostgreSQL, a sequence counting down starts at -1
+    fn parse_sequence(name: &str, options: &[SequenceOptions]) -> Result<Sequence> {
+        let number = |expr: &Expr| match Self::parse_value(expr)? {
+            Value::Integer(n) => Ok(n),
+            other => Err(anyhow!(
+                "Sequence '{}' expects an integer, got {}",
+                name,
+                other
+            )),
+        };
+
+        let mut start = None;
+        let mut increment = 1;
+        for option in options {
+            match option {
+                SequenceOptions::StartWith(expr, _) => start = Some(number(expr)?),
+                SequenceOptions::IncrementBy(expr, _) => increment = number(expr)?,
+                _ => return Err(anyhow!("Unsupported sequence option:{}", option)),
+            }
+        }
+        let start = start.unwrap_or(if increment < 0 { -1 } else { 1 });
+        Sequence::new(name, start, increment)
+    }
+
+    /// Parse the ON DELETE / ON UPDATE action of a foreign key
+    /// NO ACTION (the default) behaves like RESTRICT, since we check
+    /// constraints at the end of each statement anyway
+    fn parse_referential_action(
+        action: &Option<sqlparser::ast::ReferentialAction>,
+    ) -> Result<ReferentialAction> {
+        use sqlparser::ast::ReferentialAction as Action;
+
+        match action {
+            None | Some(Action::NoAction) | Some(Action::Restrict) => {
+                Ok(ReferentialAction::Restrict)
+            }
+            Some(Action::Cascade) => Ok(ReferentialAction::Cascade),
+            Some(Action::SetNull) => Ok(ReferentialAction::SetNull),
+            Some(other) => Err(anyhow!("Unsupported foreign key action: {}", other)),
+        }
+    }
+
     /// Parse INSERT statement
     fn parse_insert(insert: &sqlparser::ast::Insert) -> Result<Query> {
         let table_name = match &insert.table_name {
@@ -147,14 +474,36 @@ impl QueryParser {
                     }
 
                     // Take the first row (we only support single row inserts)
-                    Self::parse_values(&values.rows[0])?
+                    // DEFAULT asks for the column's default value
+                    values.rows[0]
+                        .iter()
+                        .map(|expr| match expr {
+                            Expr::Identifier(ident)
+                                if ident.value.eq_ignore_ascii_case("default") =>
+                            {
+                                Ok(None)
+                            }
+                            _ => Self::parse_expr(expr).map(Some),
+                        })
+                        .collect::<Result<Vec<_>>>()?
                 }
                 _ => return Err(anyhow!("Unsupported INSERT format")),
             },
             None => return Err(anyhow!("No values provided")),
         };
 
-        Ok(Query::Insert { table_name, values })
+        // INSERT INTO t (a, b) VALUES ... - the other columns get their defaults
+        let columns = if insert.columns.is_empty() {
+            None
+        } else {
+            Some(insert.columns.iter().map(|c| c.value.clone()).collect())
+        };
+
+        Ok(Query::Insert {
+            table_name,
+            columns,
+            values,
+        })
     }
 
     /// Parse SELECT statement
@@ -167,6 +516,31 @@ impl QueryParser {
         // Extract table name
         let table_name = Self::extract_table_name(select)?;
 
+        let columns = match select.projection.as_slice() {
+            [SelectItem::Wildcard(_)] => None,
+            items => Some(
+                items
+                    .iter()
+                    .map(|item| {
+                        let (expr, name) = match item {
+                            SelectItem::UnnamedExpr(expr) => (expr, Self::column_name(expr)),
+                            SelectItem::ExprWithAlias { expr, alias } => {
+                                (expr, alias.value.clone())
+                            }
+                            SelectItem::Wildcard(_) => {
+                                return Err(anyhow!("* cannot be combined with other columns"))
+                            }
+                            _ => return Err(anyhow!("Unsupported select item: {}", item)),
+                        };
+                        Ok(SelectColumn {
+                            expr: Self::parse_expr(expr)?.fold_constants()?,
+                            name,
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?,
+            ),
+        };
+
         // Parse WHERE clause if present
         let where_clause = if let Some(selection) = &select.selection {
             Some(Self::parse_where_clause(selection)?)
@@ -174,12 +548,72 @@ impl QueryParser {
             None
         };
 
+        let order_by = match &query.order_by {
+            Some(order_by) => order_by
+                .exprs
+                .iter()
+                .map(|key| Self::parse_sort_key(key, columns.as_deref()))
+                .collect::<Result<Vec<_>>>()?,
+            None => Vec::new(),
+        };
+
         Ok(Query::Select {
             table_name,
+            columns,
             where_clause,
+            order_by,
         })
     }
 
+    /// Helper: Parse a key of an ORDER BY. Besides expressions, it can name a
+    /// column of the SELECT list by its alias (ORDER BY total) or by its
+    /// position, counting from 1 (ORDER BY 2)
+    fn parse_sort_key(key: &OrderByExpr, columns: Option<&[SelectColumn]>) -> Result<SortKey> {
+        let selected = match (&key.expr, columns) {
+            (Expr::Value(SqlValue::Number(position, _)), _) => {
+                let columns = columns.ok_or_else(|| {
+                    anyhow!("ORDER BY {} needs a list of columns, not *", position)
+                })?;
+                let column = position
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|position| columns.get(position.checked_sub(1)?))
+                    .ok_or_else(|| {
+                        anyhow!("ORDER BY position {} is not in the select list", position)
+                    })?;
+                Some(column)
+            }
+            (Expr::Identifier(ident), Some(columns)) => {
+                columns.iter().find(|column| column.name == ident.value)
+            }
+            _ => None,
+        };
+        let (expr, name) = match selected {
+            Some(column) => (column.expr.clone(), column.name.clone()),
+            None => (
+                Self::parse_expr(&key.expr)?.fold_constants()?,
+                Self::column_name(&key.expr),
+            ),
+        };
+
+        let descending = key.asc == Some(false);
+        Ok(SortKey {
+            expr,
+            descending,
+            nulls_first: key.nulls_first.unwrap_or(descending),
+            name,
+        })
+    }
+
+    /// Helper: The name of an unaliased SELECT column: a column (or JSON
+    /// path) is named after itself, any other expression is named as written
+    fn column_name(expr: &Expr) -> String {
+        match Self::parse_column_ref(expr) {
+            Ok(column) => column.to_string(),
+            Err(_) => expr.to_string(),
+        }
+    }
+
     /// Parse UPDATE statement
     fn parse_update(
         table: &sqlparser::ast::TableWithJoins,
@@ -224,11 +658,19 @@ impl QueryParser {
 
     /// Parse DELETE statement
     fn parse_delete(delete: &sqlparser::ast::Delete) -> Result<Query> {
-        // Extract table name
-        let table_name = match &delete.tables.first() {
-            Some(table) => {
-                table.0.iter().map(|i| i.value.clone()).collect::<Vec<_>>().join(".")
-            }
+        // Extract table name from the FROM clause
+        let from = match &delete.from {
+            sqlparser::ast::FromTable::WithFromKeyword(from)
+            | sqlparser::ast::FromTable::WithoutKeyword(from) => from,
+        };
+        let table_name = match from.first().map(|table| &table.relation) {
+            Some(TableFactor::Table { name, .. }) => name
+                .0
+                .iter()
+                .map(|i| i.value.clone())
+                .collect::<Vec<_>>()
+                .join("."),
+            Some(_) => return Err(anyhow!("Unsupported table reference")),
             None => return Err(anyhow!("No table specified")),
         };
 
@@ -250,16 +692,24 @@ impl QueryParser {
     ) -> Result<Query> {
         let table_name = create_index.table_name.to_string();
 
-        // We only support single column indexes
-        if create_index.columns.len() != 1 {
+        // We only support single column indexes, except for UNIQUE (a, b)
+        if create_index.columns.is_empty()
+            || (create_index.columns.len() > 1 && !create_index.unique)
+        {
             return Err(anyhow!("Only single column indexes are supported"));
         }
 
-        let column_name = create_index.columns[0].to_string();
+        // A column can also be a JSON path: CREATE INDEX ON t ((data->>'type'))
+        let columns = create_index
+            .columns
+            .iter()
+            .map(|column| Ok(Self::parse_column_ref(&column.expr)?.to_string()))
+            .collect::<Result<Vec<_>>>()?;
 
         Ok(Query::CreateIndex {
             table_name,
-            column_name,
+            columns,
+            unique: create_index.unique,
         })
     }
 
@@ -272,39 +722,506 @@ impl QueryParser {
             SqlDataType::Float(_) | SqlDataType::Double | SqlDataType::Real => {
                 Ok(DataType::Float)
             }
+            SqlDataType::Decimal(info) | SqlDataType::Numeric(info) | SqlDataType::Dec(info) => {
+                // Like the SQL standard: DECIMAL(p) means DECIMAL(p, 0). Like
+                // PostgreSQL, DECIMAL alone takes any scale, up to the largest
+                // precision we support
+                let (precision, scale) = match info {
+                    ExactNumberInfo::None => (MAX_PRECISION as u64, None),
+                    ExactNumberInfo::Precision(p) => (*p, Some(0)),
+                    ExactNumberInfo::PrecisionAndScale(p, s) => (*p, Some(*s)),
+                };
+                if precision == 0 || precision > MAX_PRECISION as u64 {
+                    return Err(anyhow!(
+                        "DECIMAL precision must be between 1 and {}, got {}",
+                        MAX_PRECISION,
+                        precision
+                    ));
+                }
+                if let Some(scale) = scale.filter(|&scale| scale > precision) {
+                    return Err(anyhow!(
+                        "DECIMAL scale {} is larger than its precision {}",
+                        scale,
+                        precision
+                    ));
+                }
+                Ok(DataType::Decimal {
+                    precision: precision as u8,
+                    scale: scale.map(|scale| scale as u8),
+                })
+            }
             SqlDataType::Text | SqlDataType::Varchar(_) | SqlDataType::String(_) => {
                 Ok(DataType::Text)
             }
             SqlDataType::Boolean => Ok(DataType::Boolean),
+            SqlDataType::Date => Ok(DataType::Date),
+            SqlDataType::Time(..) => Ok(DataType::Time),
+            SqlDataType::Timestamp(..) | SqlDataType::Datetime(_) => Ok(DataType::Timestamp),
+            SqlDataType::Interval => Ok(DataType::Interval),
+            SqlDataType::Blob(_)
+            | SqlDataType::Bytea
+            | SqlDataType::Binary(_)
+            | SqlDataType::Varbinary(_) => Ok(DataType::Blob),
+            SqlDataType::JSON | SqlDataType::JSONB => Ok(DataType::Json),
+            SqlDataType::Uuid => Ok(DataType::Uuid),
             _ => Err(anyhow!("Unsupported data type: {:?}", sql_type)),
         }
     }
 
-    /// Helper: Parse a list of SQL values
-    fn parse_values(exprs: &[Expr]) -> Result<Vec<Value>> {
-        exprs.iter().map(Self::parse_value).collect()
-    }
-
     /// Helper: Parse a single SQL value
     fn parse_value(expr: &Expr) -> Result<Value> {
         match expr {
             Expr::Value(SqlValue::Number(n, _)) => {
-                if n.contains('.') {
-                    // Parse as float and store as integer (multiply by 1000 for precision)
-                    let float_val: f64 = n.parse()?;
-                    Ok(Value::Float((float_val * 1000.0) as i64))
+                // Like in PostgreSQL, 1.5 is an exact decimal and 1.5e3 a float
+                // A decimal too long to store exactly falls back to a float
+                if n.contains(['e', 'E']) {
+                    Ok(Value::Float(n.parse()?))
+                } else if n.contains('.') {
+                    match n.parse::<Decimal>() {
+                        Ok(d) => Ok(Value::Decimal(d)),
+                        Err(_) => Ok(Value::Float(n.parse()?)),
+                    }
                 } else {
                     Ok(Value::Integer(n.parse()?))
                 }
             }
+            // Negative numbers arrive as a minus sign applied to a number
+            Expr::UnaryOp {
+                op: UnaryOperator::Minus,
+                expr,
+            } => match Self::parse_value(expr)? {
+                Value::Integer(i) => Ok(Value::Integer(-i)),
+                Value::Float(f) => Ok(Value::Float(-f)),
+                Value::Decimal(d) => Ok(Value::Decimal(Decimal::new(-d.digits(), d.scale())?)),
+                _ => Err(anyhow!("Cannot negate {}", expr)),
+            },
             Expr::Value(SqlValue::SingleQuotedString(s))
             | Expr::Value(SqlValue::DoubleQuotedString(s)) => Ok(Value::Text(s.clone())),
             Expr::Value(SqlValue::Boolean(b)) => Ok(Value::Boolean(*b)),
+            // X'DEADBEEF'
+            Expr::Value(SqlValue::HexStringLiteral(hex)) => {
+                Ok(Value::Bytes(hex_format::decode(hex)?))
+            }
             Expr::Value(SqlValue::Null) => Ok(Value::Null),
+            Expr::Nested(inner) => Self::parse_value(inner),
+            // DATE '2026-01-01', TIMESTAMP '2026-01-01 12:00:00', UUID '...', ...
+            Expr::TypedString { data_type, value } => {
+                let data_type = Self::parse_data_type(data_type)?;
+                if !matches!(
+                    data_type,
+                    DataType::Date
+                        | DataType::Time
+                        | DataType::Timestamp
+                        | DataType::Interval
+                        | DataType::Uuid
+                ) {
+                    return Err(anyhow!("Unsupported typed literal: {}", expr));
+                }
+                Value::Text(value.clone()).convert_to(&data_type)
+            }
+            // INTERVAL '1 day', or INTERVAL '3' HOUR
+            Expr::Interval(interval) => {
+                let text = match interval.value.as_ref() {
+                    Expr::Value(SqlValue::SingleQuotedString(s)) => s.clone(),
+                    Expr::Value(SqlValue::Number(n, _)) => n.clone(),
+                    _ => return Err(anyhow!("Unsupported interval: {}", expr)),
+                };
+                let unit = interval
+                    .leading_field
+                    .as_ref()
+                    .map(|field| field.to_string());
+                Ok(Value::Interval(Interval::parse(&text, unit.as_deref())?))
+            }
+            // Arithmetic on constants: 2 * 3, DATE '2026-01-01' + INTERVAL '1 month'
+            Expr::BinaryOp {
+                op:
+                    BinaryOperator::Plus
+                    | BinaryOperator::Minus
+                    | BinaryOperator::Multiply
+                    | BinaryOperator::Divide
+                    | BinaryOperator::Modulo
+                    | BinaryOperator::StringConcat,
+                ..
+            } => match Self::parse_expr(expr)?.fold_constants()? {
+                RowExpr::Literal(value) => Ok(value),
+                _ => Err(anyhow!("Unsupported value expression: {}", expr)),
+            },
+            Expr::Extract { field, expr, .. } => {
+                temporal::extract(&field.to_string(), &Self::parse_value(expr)?)
+            }
+            Expr::Function(function) => Self::parse_function(function),
             _ => Err(anyhow!("Unsupported value expression: {:?}", expr)),
         }
     }
 
+    /// Helper: Parse an expression evaluated later, for every row (see storage/expr.rs):
+    /// a WHERE or CHECK condition, the default of a column, or an INSERT value
+    fn parse_expr(expr: &Expr) -> Result<RowExpr> {
+        let expr = &Self::rebind_json_arrows(expr);
+        match expr {
+            Expr::Identifier(_)
+            | Expr::BinaryOp {
+                op: BinaryOperator::Arrow | BinaryOperator::LongArrow,
+                ..
+            } => Ok(RowExpr::Column(Self::parse_column_ref(expr)?)),
+            Expr::Nested(inner) => Self::parse_expr(inner),
+            Expr::BinaryOp { left, op, right } => {
+                let op = match op {
+                    BinaryOperator::Eq => BinaryOp::Eq,
+                    BinaryOperator::NotEq => BinaryOp::NotEq,
+                    BinaryOperator::Lt => BinaryOp::Lt,
+                    BinaryOperator::LtEq => BinaryOp::LtEq,
+                    BinaryOperator::Gt => BinaryOp::Gt,
+                    BinaryOperator::GtEq => BinaryOp::GtEq,
+                    BinaryOperator::And => BinaryOp::And,
+                    BinaryOperator::Or => BinaryOp::Or,
+                    BinaryOperator::Plus => BinaryOp::Plus,
+                    BinaryOperator::Minus => BinaryOp::Minus,
+                    BinaryOperator::Multiply => BinaryOp::Multiply,
+                    BinaryOperator::Divide => BinaryOp::Divide,
+                    BinaryOperator::Modulo => BinaryOp::Modulo,
+                    BinaryOperator::StringConcat => BinaryOp::Concat,
+                    _ => return Err(anyhow!("Unsupported operator in {}", expr)),
+                };
+                Ok(RowExpr::Binary {
+                    left: Box::new(Self::parse_expr(left)?),
+                    op,
+                    right: Box::new(Self::parse_expr(right)?),
+                })
+            }
+            Expr::UnaryOp {
+                op: UnaryOperator::Not,
+                expr,
+            } => Ok(RowExpr::Not(Box::new(Self::parse_expr(expr)?))),
+            Expr::UnaryOp {
+                op: UnaryOperator::Minus,
+                expr: inner,
+            } => match Self::parse_expr(inner)? {
+                RowExpr::Literal(_) => Ok(RowExpr::Literal(Self::parse_value(expr)?)),
+                // -price is 0 - price
+                inner => Ok(RowExpr::Binary {
+                    left: Box::new(RowExpr::Literal(Value::Integer(0))),
+                    op: BinaryOp::Minus,
+                    right: Box::new(inner),
+                }),
+            },
+            Expr::IsNull(inner) | Expr::IsNotNull(inner) => Ok(RowExpr::IsNull {
+                expr: Box::new(Self::parse_expr(inner)?),
+                negated: matches!(expr, Expr::IsNotNull(_)),
+            }),
+            Expr::Like {
+                negated,
+                any: false,
+                expr: inner,
+                pattern,
+                escape_char,
+            }
+            | Expr::ILike {
+                negated,
+                any: false,
+                expr: inner,
+                pattern,
+                escape_char,
+            } => {
+                // Like PostgreSQL: backslash escapes unless ESCAPE says
+                // otherwise, and ESCAPE '' turns escaping off
+                let escape = match escape_char.as_deref() {
+                    None => Some('\\'),
+                    Some("") => None,
+                    Some(c) if c.chars().count() == 1 => c.chars().next(),
+                    Some(c) => {
+                        return Err(anyhow!(
+                            "LIKE escape must be a single character, got '{}'",
+                            c
+                        ))
+                    }
+                };
+                Ok(RowExpr::Like {
+                    expr: Box::new(Self::parse_expr(inner)?),
+                    pattern: Box::new(Self::parse_expr(pattern)?),
+                    negated: *negated,
+                    case_insensitive: matches!(expr, Expr::ILike { .. }),
+                    escape,
+                })
+            }
+            Expr::InList {
+                expr: inner,
+                list,
+                negated,
+            } => Ok(RowExpr::InList {
+                expr: Box::new(Self::parse_expr(inner)?),
+                list: list.iter().map(Self::parse_expr).collect::<Result<_>>()?,
+                negated: *negated,
+            }),
+            Expr::IsDistinctFrom(left, right) | Expr::IsNotDistinctFrom(left, right) => {
+                Ok(RowExpr::IsDistinctFrom {
+                    left: Box::new(Self::parse_expr(left)?),
+                    right: Box::new(Self::parse_expr(right)?),
+                    negated: matches!(expr, Expr::IsNotDistinctFrom(..)),
+                })
+            }
+            Expr::IsTrue(inner)
+            | Expr::IsNotTrue(inner)
+            | Expr::IsFalse(inner)
+            | Expr::IsNotFalse(inner)
+            | Expr::IsUnknown(inner)
+            | Expr::IsNotUnknown(inner) => Ok(RowExpr::IsTruth {
+                expr: Box::new(Self::parse_expr(inner)?),
+                truth: match expr {
+                    Expr::IsTrue(_) | Expr::IsNotTrue(_) => Some(true),
+                    Expr::IsFalse(_) | Expr::IsNotFalse(_) => Some(false),
+                    _ => None,
+                },
+                negated: matches!(
+                    expr,
+                    Expr::IsNotTrue(_) | Expr::IsNotFalse(_) | Expr::IsNotUnknown(_)
+                ),
+            }),
+            Expr::Between {
+                expr: inner,
+                negated,
+                low,
+                high,
+            } => Ok(RowExpr::Between {
+                expr: Box::new(Self::parse_expr(inner)?),
+                low: Box::new(Self::parse_expr(low)?),
+                high: Box::new(Self::parse_expr(high)?),
+                negated: *negated,
+            }),
+            // EXTRACT(YEAR FROM day) is extract('YEAR', day), for each row
+            Expr::Extract {
+                field, expr: inner, ..
+            } => Ok(RowExpr::Function {
+                name: "extract".to_string(),
+                args: vec![
+                    RowExpr::Literal(Value::Text(field.to_string())),
+                    Self::parse_expr(inner)?,
+                ],
+            }),
+            // json_extract(data, '$.a') is a column path, NOW() is called later
+            Expr::Function(function) => {
+                if let Ok(column) = Self::parse_column_ref(expr) {
+                    return Ok(RowExpr::Column(column));
+                }
+                let args = match &function.args {
+                    FunctionArguments::None => Vec::new(),
+                    FunctionArguments::List(list) => list
+                        .args
+                        .iter()
+                        .map(|arg| match arg {
+                            FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) => {
+                                Self::parse_expr(expr)
+                            }
+                            _ => Err(anyhow!("Unsupported argument in {}", function)),
+                        })
+                        .collect::<Result<Vec<_>>>()?,
+                    FunctionArguments::Subquery(_) => {
+                        return Err(anyhow!("Unsupported argument in {}", function))
+                    }
+                };
+                Ok(RowExpr::Function {
+                    name: function.name.to_string(),
+                    args,
+                })
+            }
+            // Anything else has to be a constant
+            _ => Ok(RowExpr::Literal(Self::parse_value(expr)?)),
+        }
+    }
+
+    /// Helper: Evaluate a function call with constant arguments (see functions.rs)
+    fn parse_function(function: &Function) -> Result<Value> {
+        let name = function.name.to_string();
+        let args = match &function.args {
+            FunctionArguments::None => Vec::new(),
+            FunctionArguments::List(list) => list
+                .args
+                .iter()
+                .map(|arg| match arg {
+                    FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) => Self::parse_value(expr),
+                    _ => Err(anyhow!("Unsupported argument in {}", function)),
+                })
+                .collect::<Result<Vec<Value>>>()?,
+            FunctionArguments::Subquery(_) => {
+                return Err(anyhow!("Unsupported argument in {}", function))
+            }
+        };
+
+        functions::call(&name, &args)
+    }
+
+    /// Helper: Parse a column, or a path into a JSON column:
+    /// data, data->'user'->>'name', json_extract(data, '$.user.name'),
+    /// json_array_length(data->'tags')
+    fn parse_column_ref(expr: &Expr) -> Result<ColumnRef> {
+        match expr {
+            Expr::Identifier(ident) => Ok(ColumnRef::column(&ident.value)),
+            Expr::Nested(inner) => Self::parse_column_ref(inner),
+            Expr::BinaryOp {
+                left,
+                op: op @ (BinaryOperator::Arrow | BinaryOperator::LongArrow),
+                right,
+            } => {
+                let mut column_ref = Self::parse_column_ref(left)?;
+                if column_ref.output != PathOutput::Value {
+                    return Err(anyhow!("Cannot follow a JSON path from {}", left));
+                }
+                let step = match Self::parse_value(right)? {
+                    Value::Text(key) => PathStep::Key(key),
+                    Value::Integer(index) => PathStep::Index(index),
+                    _ => return Err(anyhow!("Expected a key or an array index in {}", expr)),
+                };
+                column_ref.path.push(step);
+                if *op == BinaryOperator::LongArrow {
+                    column_ref.output = PathOutput::Text;
+                }
+                Ok(column_ref)
+            }
+            Expr::Function(function) => {
+                let name = function.name.to_string().to_lowercase();
+                let args = match &function.args {
+                    FunctionArguments::List(list) => list
+                        .args
+                        .iter()
+                        .map(|arg| match arg {
+                            FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) => Ok(expr),
+                            _ => Err(anyhow!("Unsupported argument in {}", function)),
+                        })
+                        .collect::<Result<Vec<&Expr>>>()?,
+                    _ => return Err(anyhow!("Expected a column in {}", function)),
+                };
+
+                // json_extract(doc, '$.a.b') is doc->'a'->'b'
+                let (column, path) = match (name.as_str(), args.as_slice()) {
+                    ("json_extract", [column, path]) => (column, Some(path)),
+                    ("json_array_length", [column]) => (column, None),
+                    ("json_array_length", [column, path]) => (column, Some(path)),
+                    _ => return Err(anyhow!("Expected a column in {}", function)),
+                };
+                let mut column_ref = Self::parse_column_ref(column)?;
+                if column_ref.output != PathOutput::Value {
+                    return Err(anyhow!("Cannot follow a JSON path from {}", column));
+                }
+                if let Some(path) = path {
+                    match Self::parse_value(path)? {
+                        Value::Text(path) => column_ref.path.extend(json::parse_json_path(&path)?),
+                        _ => return Err(anyhow!("Expected a JSON path in {}", function)),
+                    }
+                }
+                if name == "json_array_length" {
+                    column_ref.output = PathOutput::ArrayLength;
+                }
+                Ok(column_ref)
+            }
+            _ => Err(anyhow!("Expected column name, got {}", expr)),
+        }
+    }
+
+    /// Helper: Undo a quirk of sqlparser's generic dialect, which binds -> and
+    /// ->> more loosely than comparisons: data->>'type' = 'click' comes out
+    /// as data->>('type' = 'click'), and data->>'name' LIKE 'A%' as
+    /// data->>('name' LIKE 'A%'). Like PostgreSQL, we want the path first.
+    fn rebind_json_arrows(expr: &Expr) -> Expr {
+        match expr {
+            Expr::BinaryOp {
+                left,
+                op: op @ (BinaryOperator::Arrow | BinaryOperator::LongArrow),
+                right,
+            } => {
+                let left = Self::rebind_json_arrows(left);
+                // The path from the JSON document to the key
+                let path = |key: Box<Expr>| {
+                    Box::new(Expr::BinaryOp {
+                        left: Box::new(left.clone()),
+                        op: op.clone(),
+                        right: key,
+                    })
+                };
+                match Self::rebind_json_arrows(right) {
+                    Expr::BinaryOp {
+                        left: key,
+                        op:
+                            comparison @ (BinaryOperator::Eq
+                            | BinaryOperator::NotEq
+                            | BinaryOperator::Lt
+                            | BinaryOperator::LtEq
+                            | BinaryOperator::Gt
+                            | BinaryOperator::GtEq),
+                        right: value,
+                    } => Expr::BinaryOp {
+                        left: path(key),
+                        op: comparison,
+                        right: value,
+                    },
+                    Expr::Like {
+                        negated,
+                        any,
+                        expr: key,
+                        pattern,
+                        escape_char,
+                    } => Expr::Like {
+                        negated,
+                        any,
+                        expr: path(key),
+                        pattern,
+                        escape_char,
+                    },
+                    Expr::ILike {
+                        negated,
+                        any,
+                        expr: key,
+                        pattern,
+                        escape_char,
+                    } => Expr::ILike {
+                        negated,
+                        any,
+                        expr: path(key),
+                        pattern,
+                        escape_char,
+                    },
+                    Expr::InList {
+                        expr: key,
+                        list,
+                        negated,
+                    } => Expr::InList {
+                        expr: path(key),
+                        list,
+                        negated,
+                    },
+                    Expr::Between {
+                        expr: key,
+                        negated,
+                        low,
+                        high,
+                    } => Expr::Between {
+                        expr: path(key),
+                        negated,
+                        low,
+                        high,
+                    },
+                    Expr::IsDistinctFrom(key, value) => Expr::IsDistinctFrom(path(key), value),
+                    Expr::IsNotDistinctFrom(key, value) => {
+                        Expr::IsNotDistinctFrom(path(key), value)
+                    }
+                    Expr::IsNull(key) => Expr::IsNull(path(key)),
+                    Expr::IsNotNull(key) => Expr::IsNotNull(path(key)),
+                    right => Expr::BinaryOp {
+                        left: Box::new(left),
+                        op: op.clone(),
+                        right: Box::new(right),
+                    },
+                }
+            }
+            Expr::BinaryOp { left, op, right } => Expr::BinaryOp {
+                left: Box::new(Self::rebind_json_arrows(left)),
+                op: op.clone(),
+                right: Box::new(Self::rebind_json_arrows(right)),
+            },
+            Expr::Nested(inner) => Expr::Nested(Box::new(Self::rebind_json_arrows(inner))),
+            _ => expr.clone(),
+        }
+    }
+
     /// Helper: Extract table name from SELECT
     fn extract_table_name(select: &Select) -> Result<String> {
         if select.from.is_empty() {
@@ -320,24 +1237,11 @@ impl QueryParser {
     }
 
     /// Helper: Parse WHERE clause
-    /// We only support simple equality conditions: column = value
-    fn parse_where_clause(expr: &Expr) -> Result<WhereClause> {
-        match expr {
-            Expr::BinaryOp { left, op, right } => {
-                if !matches!(op, BinaryOperator::Eq) {
-                    return Err(anyhow!("Only = operator is supported in WHERE clause"));
-                }
-
-                let column = match left.as_ref() {
-                    Expr::Identifier(ident) => ident.value.clone(),
-                    _ => return Err(anyhow!("Expected column name in WHERE clause")),
-                };
-
-                let value = Self::parse_value(right)?;
-
-                Ok(WhereClause { column, value })
-            }
-            _ => Err(anyhow!("Unsupported WHERE clause format")),
-        }
+    /// The condition is an expression tree (see storage/expr.rs), evaluated
+    /// for every row: comparisons (=, <>, <, <=, >, >=), LIKE / ILIKE, IN,
+    /// BETWEEN, IS [NOT] NULL, IS [NOT] DISTINCT FROM and IS [NOT] TRUE /
+    /// FALSE / UNKNOWN, combined with AND, OR, NOT and parentheses
+    fn parse_where_clause(expr: &Expr) -> Result<RowExpr> {
+        Self::parse_expr(expr)?.fold_constants()
     }
 }
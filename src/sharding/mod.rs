@@ -11,11 +11,19 @@
 // 1. Hash-based: hash(key) % num_shards (what we implement)
 // 2. Range-based: shard based on value ranges (e.g., A-M on shard1, N-Z on shard2)
 // 3. Geographic: shard by location (e.g., US users on shard1, EU users on shard2)
+//
+// Sequences (SERIAL columns, nextval) must not hand out the same number on two
+// shards, so shard 0 numbers every row: the router works out the row's values
+// there, then sends the complete row to the shard its key hashes to. In a real
+// cluster that would be a separate ID service; Snowflake-style IDs (time +
+// machine + counter) avoid the round trip at the cost of ordered, dense numbers.
 
 use crate::query::{executor::QueryResult, parser::Query, QueryExecutor, QueryParser};
+use crate::storage::expr::Expr;
 use crate::storage::Value;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use seahash::hash;
+use std::cmp::Ordering;
 
 /// A sharded database that distributes data across multiple query executors
 /// Each shard is an independent database instance
@@ -47,11 +55,20 @@ impl ShardedDatabase {
     /// Execute a SQL query against the sharded database
     pub fn execute(&mut self, sql: &str) -> Result<QueryResult> {
         // Parse the SQL query
-        let query = QueryParser::parse(sql)?;
+        let query = match QueryParser::parse(sql)? {
+            Query::Insert {
+                table_name,
+                columns,
+                values,
+            } => self.complete_insert(table_name, columns, values)?,
+            query => query,
+        };
 
         match &query {
             // For CREATE TABLE, we need to create the table on ALL shards
             // This ensures every shard has the same schema
+            // (foreign keys are checked within each shard, so a row and the
+            // row it references have to live on the same shard)
             Query::CreateTable { .. } => {
                 for shard in &mut self.shards {
                     shard.execute(QueryParser::parse(sql)?)?;
@@ -59,7 +76,19 @@ impl ShardedDatabase {
                 Ok(QueryResult::Message("Table created on all shards".to_string()))
             }
 
+            // Every shard knows the sequence, but only shard 0 hands out numbers
+            Query::CreateSequence { .. } => {
+                for shard in &mut self.shards {
+                    shard.execute(QueryParser::parse(sql)?)?;
+                }
+                Ok(QueryResult::Message(
+                    "Sequence created on all shards".to_string(),
+                ))
+            }
+
             // For CREATE INDEX, apply to all shards
+            // (a unique index is checked within each shard, so it only holds
+            // across shards for the shard key)
             Query::CreateIndex { .. } => {
                 for shard in &mut self.shards {
                     shard.execute(QueryParser::parse(sql)?)?;
@@ -68,57 +97,240 @@ impl ShardedDatabase {
             }
 
             // For INSERT, we route to a specific shard based on the primary key
-            Query::Insert { values, .. } => {
-                // Use the first value (usually the primary key) for sharding
+            // (complete_insert has given every column a value)
+            Query::Insert {
+                table_name,
+                columns,
+                values,
+            } => {
+                // Use the first column's value (usually the primary key) for sharding
                 // In a real system, you'd explicitly specify the shard key
-                let shard_id = self.get_shard_id(&values[0]);
+                let position = match columns {
+                    Some(columns) => self.shards[0]
+                        .get_table(table_name)
+                        .and_then(|table| table.schema.columns.first().map(|c| c.name.clone()))
+                        .and_then(|first| columns.iter().position(|c| *c == first)),
+                    None => Some(0),
+                };
+                let value = match position.and_then(|i| values.get(i)) {
+                    Some(Some(Expr::Literal(value))) => value.clone(),
+                    _ => Value::Null,
+                };
+                let shard_id = self.get_shard_id(&self.shard_key(table_name, &value));
                 self.shards[shard_id].execute(query)
             }
 
-            // For SELECT with WHERE clause, we can route to a specific shard
             Query::Select {
-                where_clause: Some(where_clause),
+                table_name,
+                where_clause,
+                order_by,
                 ..
             } => {
-                let shard_id = self.get_shard_id(&where_clause.value);
-                self.shards[shard_id].execute(query)
-            }
+                // For SELECT with WHERE shard_key = value, we can route to a specific shard
+                if let Some(shard_id) = where_clause
+                    .as_ref()
+                    .and_then(|w| self.shard_for(table_name, w))
+                {
+                    return self.shards[shard_id].execute(query);
+                }
 
-            // For SELECT without WHERE, we need to query ALL shards and merge results
-            // This is called a "scatter-gather" query
-            Query::Select {
-                where_clause: None,
-                table_name: _,
-            } => {
+                // Otherwise we need to query ALL shards and merge results
+                // This is called a "scatter-gather" query
                 let mut all_rows = Vec::new();
                 let mut column_names = Vec::new();
 
                 // Query each shard
                 for shard in &mut self.shards {
                     let result = shard.execute(QueryParser::parse(sql)?)?;
-                    match result {
-                        QueryResult::Rows { rows, column_names: cols } => {
-                            if column_names.is_empty() {
-                                column_names = cols;
-                            }
-                            all_rows.extend(rows);
+                    if let QueryResult::Rows {
+                        rows,
+                        column_names: cols,
+                    } = result
+                    {
+                        if column_names.is_empty() {
+                            column_names = cols;
                         }
-                        _ => {}
+                        all_rows.extend(rows);
                     }
                 }
 
+                // Each shard sorted its own rows; sort them all by the
+                // result columns the ORDER BY keys are
+                if !order_by.is_empty() {
+                    let positions = order_by
+                        .iter()
+                        .map(|key| {
+                            column_names
+                                .iter()
+                                .position(|name| *name == key.name)
+                                .ok_or_else(|| {
+                                    anyhow!(
+                                        "ORDER BY {} across shards needs it in the select list",
+                                        key.name
+                                    )
+                                })
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    all_rows.sort_by(|a, b| {
+                        order_by
+                            .iter()
+                            .zip(&positions)
+                            .map(|(key, &i)| key.compare(&a.values[i], &b.values[i]))
+                            .find(|order| order.is_ne())
+                            .unwrap_or(Ordering::Equal)
+                    });
+                }
+
                 Ok(QueryResult::Rows {
                     rows: all_rows,
                     column_names,
                 })
             }
 
-            // For UPDATE/DELETE with WHERE, route to specific shard
-            Query::Update { where_clause, .. } | Query::Delete { where_clause, .. } => {
-                let shard_id = self.get_shard_id(&where_clause.value);
-                self.shards[shard_id].execute(query)
+            // For UPDATE/DELETE with WHERE shard_key = value, route to specific shard;
+            // otherwise every shard applies it to its own rows
+            // (without a distributed transaction, a failure on one shard
+            // doesn't undo the changes already made on the others)
+            Query::Update {
+                table_name,
+                where_clause,
+                ..
+            }
+            | Query::Delete {
+                table_name,
+                where_clause,
+            } => match self.shard_for(table_name, where_clause) {
+                Some(shard_id) => self.shards[shard_id].execute(query),
+                None => {
+                    let mut total = 0;
+                    let mut message = String::new();
+                    for shard in &mut self.shards {
+                        if let QueryResult::Message(text) =
+                            shard.execute(QueryParser::parse(sql)?)?
+                        {
+                            // "3 row(s) updated in 'users'"
+                            let (count, rest) = text.split_once(' ').unwrap_or(("0", &text));
+                            total += count.parse::<usize>().unwrap_or(0);
+                            message = rest.to_string();
+                        }
+                    }
+                    Ok(QueryResult::Message(format!("{} {}", total, message)))
+                }
+            },
+
+            // VACUUM cleans up every shard
+            Query::Vacuum { .. } => {
+                for shard in &mut self.shards {
+                    shard.execute(QueryParser::parse(sql)?)?;
+                }
+                Ok(QueryResult::Message(
+                    "Vacuum completed on all shards".to_string(),
+                ))
             }
+
+            // A transaction spanning shards would need a distributed commit
+            // protocol (two-phase commit), which we don't implement
+            Query::Begin | Query::Commit | Query::Rollback => Err(anyhow!(
+                "Transactions are not supported on a sharded database"
+            )),
+        }
+    }
+
+    /// Work out every value of an INSERT on shard 0, including the defaults
+    /// of the columns left out, so that sequence numbers are unique across
+    /// shards and the row can be routed by its key
+    fn complete_insert(
+        &mut self,
+        table_name: String,
+        columns: Option<Vec<String>>,
+        values: Vec<Option<Expr>>,
+    ) -> Result<Query> {
+        let Some(schema) = self.shards[0]
+            .get_table(&table_name)
+            .map(|table| table.schema.clone())
+        else {
+            // The shard reports the missing table
+            return Ok(Query::Insert {
+                table_name,
+                columns,
+                values,
+            });
+        };
+
+        let names =
+            columns.unwrap_or_else(|| schema.columns.iter().map(|c| c.name.clone()).collect());
+        if names.len() != values.len() {
+            return Err(anyhow!(
+                "Expected {} values, got {}",
+                names.len(),
+                values.len()
+            ));
         }
+
+        let mut given: Vec<(String, Option<Expr>)> = names.into_iter().zip(values).collect();
+        for column in &schema.columns {
+            if !given.iter().any(|(name, _)| *name == column.name) {
+                given.push((column.name.clone(), None));
+            }
+        }
+
+        let mut columns = Vec::new();
+        let mut values = Vec::new();
+        for (name, value) in given {
+            // DEFAULT, or a column left out: use the column's default here
+            let expr = match value {
+                Some(expr) => Some(expr),
+                None => schema
+                    .columns
+                    .iter()
+                    .find(|c| c.name == name)
+                    .and_then(|c| c.default.clone()),
+            };
+            let value = match expr {
+                Some(expr) => self.shards[0].evaluate(&expr)?,
+                None => Value::Null,
+            };
+            columns.push(name);
+            values.push(Some(Expr::Literal(value)));
+        }
+
+        Ok(Query::Insert {
+            table_name,
+            columns: Some(columns),
+            values,
+        })
+    }
+
+    /// The shard holding every row that can meet a condition, if the
+    /// condition pins the shard key (the table's first column) to one value:
+    /// WHERE id = 5 AND name = 'Ann'
+    fn shard_for(&self, table_name: &str, condition: &Expr) -> Option<usize> {
+        let shard_key = self.shards[0]
+            .get_table(table_name)?
+            .schema
+            .columns
+            .first()?
+            .name
+            .clone();
+        let (_, value) = condition
+            .equalities()
+            .into_iter()
+            .find(|(column, _)| column.is_column() && column.column == shard_key)?;
+        Some(self.get_shard_id(&self.shard_key(table_name, value)))
+    }
+
+    /// Convert a shard key to the type of the table's first column, the way
+    /// the shard will store it - so that X'00FF' and '\x00ff' in a BLOB
+    /// column, or 5 and 5.0 in a FLOAT column, land on the same shard
+    fn shard_key(&self, table_name: &str, value: &Value) -> Value {
+        self.shards[0]
+            .get_table(table_name)
+            .and_then(|table| {
+                value
+                    .convert_to(&table.schema.columns.first()?.data_type)
+                    .ok()
+            })
+            .unwrap_or_else(|| value.clone())
     }
 
     /// Determine which shard a value belongs to
@@ -130,9 +342,19 @@ impl ShardedDatabase {
         // Convert the value to bytes for hashing
         let bytes = match value {
             Value::Integer(i) => i.to_string().into_bytes(),
+            // Equal floats must land on the same shard, so -0.0 hashes like 0.0
+            Value::Float(f) if *f == 0.0 => b"0".to_vec(),
             Value::Float(f) => f.to_string().into_bytes(),
+            // 1.50 and 1.5 are equal, so trailing zeros don't count
+            Value::Decimal(d) => d.normalize().to_string().into_bytes(),
             Value::Text(s) => s.as_bytes().to_vec(),
+            Value::Bytes(b) => b.clone(),
+            Value::Json(doc) => doc.to_string().into_bytes(),
+            Value::Uuid(u) => u.to_bytes().to_vec(),
             Value::Boolean(b) => b.to_string().into_bytes(),
+            Value::Date(_) | Value::Time(_) | Value::Timestamp(_) => value.to_string().into_bytes(),
+            // '1 day' and '24 hours' are equal, so hash their length
+            Value::Interval(i) => i.total_micros().to_string().into_bytes(),
             Value::Null => b"null".to_vec(),
         };
 
@@ -151,14 +373,14 @@ impl ShardedDatabase {
 
     /// Get statistics about data distribution across shards
     /// This is useful for monitoring shard balance
-    pub fn get_shard_stats(&self, table_name: &str) -> Vec<ShardStats> {
+    pub fn get_shard_stats(&self, table_name: &str) -> Result<Vec<ShardStats>> {
         let mut stats = Vec::new();
 
         for (i, shard) in self.shards.iter().enumerate() {
-            let row_count = shard
-                .get_table(table_name)
-                .map(|t| t.row_count())
-                .unwrap_or(0);
+            let row_count = match shard.get_table(table_name) {
+                Some(table) => table.row_count()?,
+                None => 0,
+            };
 
             stats.push(ShardStats {
                 shard_id: i,
@@ -166,7 +388,7 @@ impl ShardedDatabase {
             });
         }
 
-        stats
+        Ok(stats)
     }
 }
 
@@ -205,7 +427,7 @@ mod tests {
         }
 
         // Check shard distribution
-        let stats = db.get_shard_stats("users");
+        let stats = db.get_shard_stats("users").unwrap();
         let total: usize = stats.iter().map(|s| s.row_count).sum();
         assert_eq!(total, 10);
 
@@ -214,4 +436,176 @@ mod tests {
             println!("{}", stat.format());
         }
     }
+
+    #[test]
+    fn test_blob_shard_keys() {
+        let mut db = ShardedDatabase::new(4);
+        db.execute("CREATE TABLE blobs (hash BLOB PRIMARY KEY, note TEXT)")
+            .unwrap();
+
+        for i in 0..8 {
+            db.execute(&format!(
+                "INSERT INTO blobs VALUES (X'CAFE{:02X}', 'blob {}')",
+                i, i
+            ))
+            .unwrap();
+        }
+
+        // The same key written as hex text routes to the same shard
+        let found = db
+            .execute("SELECT * FROM blobs WHERE hash = '\\xcafe05'")
+            .unwrap();
+        match found {
+            QueryResult::Rows { rows, .. } => {
+                assert_eq!(rows.len(), 1);
+                assert_eq!(rows[0].values[1], Value::Text("blob 5".to_string()));
+            }
+            other => panic!("Expected rows, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_uuid_shard_keys() {
+        let mut db = ShardedDatabase::new(4);
+        db.execute("CREATE TABLE users (id UUID PRIMARY KEY, name TEXT)")
+            .unwrap();
+
+        for i in 0..8 {
+            db.execute(&format!(
+                "INSERT INTO users VALUES (gen_random_uuid(), 'user {}')",
+                i
+            ))
+            .unwrap();
+        }
+        db.execute("INSERT INTO users VALUES ('a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11', 'ann')")
+            .unwrap();
+
+        let total: usize = db
+            .get_shard_stats("users")
+            .unwrap()
+            .iter()
+            .map(|s| s.row_count)
+            .sum();
+        assert_eq!(total, 9);
+
+        // Any way of writing the UUID finds the row
+        for key in [
+            "A0EEBC99-9C0B-4EF8-BB6D-6BB9BD380A11",
+            "{a0eebc999c0b4ef8bb6d6bb9bd380a11}",
+        ] {
+            match db
+                .execute(&format!("SELECT * FROM users WHERE id = '{}'", key))
+                .unwrap()
+            {
+                QueryResult::Rows { rows, .. } => {
+                    assert_eq!(rows.len(), 1, "{}", key);
+                    assert_eq!(rows[0].values[1], Value::Text("ann".to_string()));
+                }
+                other => panic!("Expected rows, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_conditions_without_the_shard_key() {
+        let mut db = ShardedDatabase::new(3);
+        db.execute("CREATE TABLE users (id INTEGER PRIMARY KEY, age INTEGER)")
+            .unwrap();
+        for i in 1..=12 {
+            db.execute(&format!("INSERT INTO users VALUES ({}, {})", i, i * 10))
+                .unwrap();
+        }
+
+        let count = |db: &mut ShardedDatabase, sql: &str| match db.execute(sql).unwrap() {
+            QueryResult::Rows { rows, .. } => rows.len(),
+            other => panic!("Expected rows, got {:?}", other),
+        };
+        // WHERE id = 3 AND ... goes to one shard; anything else asks them all
+        assert_eq!(
+            count(&mut db, "SELECT * FROM users WHERE id = 3 AND age > 20"),
+            1
+        );
+        assert_eq!(
+            count(&mut db, "SELECT * FROM users WHERE age > 50 AND age <= 100"),
+            5
+        );
+
+        match db
+            .execute("UPDATE users SET age = 0 WHERE age < 40 OR id = 12")
+            .unwrap()
+        {
+            QueryResult::Message(message) => assert_eq!(message, "4 row(s) updated in 'users'"),
+            other => panic!("Expected a message, got {:?}", other),
+        }
+        db.execute("DELETE FROM users WHERE age = 0").unwrap();
+        assert_eq!(count(&mut db, "SELECT * FROM users"), 8);
+
+        // Rows from every shard come back in one order
+        let ids = |db: &mut ShardedDatabase, sql: &str| match db.execute(sql).unwrap() {
+            QueryResult::Rows { rows, .. } => rows
+                .iter()
+                .map(|row| row.values[0].to_string())
+                .collect::<Vec<_>>(),
+            other => panic!("Expected rows, got {:?}", other),
+        };
+        assert_eq!(
+            ids(&mut db, "SELECT * FROM users ORDER BY age DESC"),
+            ["11", "10", "9", "8", "7", "6", "5", "4"]
+        );
+        assert_eq!(
+            ids(
+                &mut db,
+                "SELECT id, age % 30 AS r FROM users ORDER BY r, id"
+            ),
+            ["6", "9", "4", "7", "10", "5", "8", "11"]
+        );
+        assert!(db.execute("SELECT id FROM users ORDER BY age").is_err());
+    }
+
+    #[test]
+    fn test_serial_ids_are_unique_across_shards() {
+        let mut db = ShardedDatabase::new(4);
+        db.execute("CREATE TABLE users (id SERIAL PRIMARY KEY, name TEXT)")
+            .unwrap();
+
+        for i in 1..=20 {
+            db.execute(&format!("INSERT INTO users (name) VALUES ('user {}')", i))
+                .unwrap();
+        }
+
+        // Every row went to the shard its ID hashes to, so WHERE id = finds it
+        let stats = db.get_shard_stats("users").unwrap();
+        assert_eq!(stats.iter().map(|s| s.row_count).sum::<usize>(), 20);
+        assert!(stats.iter().filter(|s| s.row_count > 0).count() > 1);
+        for id in 1..=20 {
+            match db
+                .execute(&format!("SELECT * FROM users WHERE id = {}", id))
+                .unwrap()
+            {
+                QueryResult::Rows { rows, .. } => {
+                    assert_eq!(rows.len(), 1, "id {}", id);
+                    assert_eq!(rows[0].values[1], Value::Text(format!("user {}", id)));
+                }
+                other => panic!("Expected rows, got {:?}", other),
+            }
+        }
+
+        // nextval and currval are cluster-wide too
+        db.execute("CREATE SEQUENCE ticket_ids START WITH 1000")
+            .unwrap();
+        db.execute("INSERT INTO users VALUES (nextval('ticket_ids'), 'a')")
+            .unwrap();
+        db.execute("INSERT INTO users VALUES (nextval('ticket_ids'), 'b')")
+            .unwrap();
+        db.execute("INSERT INTO users (name) VALUES ('c')").unwrap();
+        assert!(db
+            .execute("INSERT INTO users VALUES (currval('ticket_ids'), 'again')")
+            .is_err());
+        match db.execute("SELECT * FROM users WHERE id = 21").unwrap() {
+            QueryResult::Rows { rows, .. } => {
+                assert_eq!(rows[0].values[1], Value::Text("c".to_string()))
+            }
+            other => panic!("Expected rows, got {:?}", other),
+        }
+    }
 }
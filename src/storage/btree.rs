@@ -3,6 +3,7 @@
 // A B-Tree is a self-balancing tree where each node can have multiple children
 // This makes it perfect for disk-based storage (databases)
 
+use super::page::RecordId;
 use super::Value;
 use std::collections::BTreeMap;
 
@@ -16,31 +17,66 @@ use std::collections::BTreeMap;
 /// 4. Disk-friendly: Minimizes disk reads by grouping data
 #[derive(Debug, Clone)]
 pub struct BTreeIndex {
-    /// Maps index key (Value) to row IDs
-    /// The row ID is a usize (unsigned integer) that identifies the row position
-    tree: BTreeMap<IndexKey, Vec<usize>>,
-    /// Name of the indexed column
+    /// Maps index key (Value) to record IDs
+    /// A record ID (page + slot) tells us exactly where the row is stored
+    tree: BTreeMap<IndexKey, Vec<RecordId>>,
+    /// Name of the indexed column (or columns)
     column_name: String,
 }
 
 /// Wrapper for Value to make it ordered (Ord trait)
 /// Rust requires types in BTreeMap to be orderable
+///
+/// A key holds one value per indexed column. Indexes on several columns
+/// (like UNIQUE (a, b)) order their keys by the first column, then the second...
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct IndexKey(pub Value);
+pub struct IndexKey(pub Vec<Value>);
 
 // Implement ordering for IndexKey
 // This is required for BTreeMap to sort the keys
 impl PartialOrd for IndexKey {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.0.compare(&other.0)
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for IndexKey {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        // For values that can't be compared, we treat them as equal
-        // This is a simplification for this educational database
-        self.partial_cmp(other).unwrap_or(std::cmp::Ordering::Equal)
+        // BTreeMap needs a total order: values of the same type compare as
+        // usual (floats included, see compare_floats), and values of
+        // different types are kept apart by ordering the types themselves
+        self.0
+            .iter()
+            .zip(&other.0)
+            .map(|(a, b)| key_order(a, b))
+            .find(|order| order.is_ne())
+            .unwrap_or_else(|| self.0.len().cmp(&other.0.len()))
+    }
+}
+
+/// The order of two values in an index, which ORDER BY sorts by too
+/// NULL comes before every other value
+pub fn key_order(a: &Value, b: &Value) -> std::cmp::Ordering {
+    a.compare(b)
+        .unwrap_or_else(|| type_rank(a).cmp(&type_rank(b)))
+}
+
+/// Position of a value's type in the order of keys
+fn type_rank(value: &Value) -> u8 {
+    match value {
+        Value::Null => 0,
+        Value::Boolean(_) => 1,
+        Value::Integer(_) => 2,
+        Value::Float(_) => 3,
+        Value::Decimal(_) => 4,
+        Value::Text(_) => 5,
+        Value::Date(_) => 6,
+        Value::Time(_) => 7,
+        Value::Timestamp(_) => 8,
+        Value::Interval(_) => 9,
+        Value::Bytes(_) => 10,
+        Value::Json(_) => 11,
+        Value::Uuid(_) => 12,
     }
 }
 
@@ -57,36 +93,51 @@ impl BTreeIndex {
     ///
     /// Parameters:
     /// - value: The column value to index
-    /// - row_id: The ID of the row containing this value
-    pub fn insert(&mut self, value: Value, row_id: usize) {
+    /// - row_id: The record ID of the row containing this value
+    pub fn insert(&mut self, value: Value, row_id: RecordId) {
+        self.insert_key(vec![value], row_id);
+    }
+
+    /// Insert a key of an index on several columns (one value per column)
+    pub fn insert_key(&mut self, values: Vec<Value>, row_id: RecordId) {
         // 'mut self' means we can modify the index
-        let key = IndexKey(value);
+        let key = IndexKey(values);
 
         // entry() is a powerful Rust API for HashMap/BTreeMap
         // It avoids double lookups (check if exists, then insert)
         self.tree
             .entry(key)
-            .or_insert_with(Vec::new) // Create empty Vec if key doesn't exist
+            .or_default() // Create empty Vec if key doesn't exist
             .push(row_id);
     }
 
     /// Look up a value in the index
-    /// Returns a reference to the vector of row IDs (if found)
+    /// Returns a reference to the vector of record IDs (if found)
     ///
     /// The '&' means we return a reference (borrowing), not ownership
     /// Option<T> is Rust's way of handling null - it's either Some(T) or None
-    pub fn lookup(&self, value: &Value) -> Option<&Vec<usize>> {
-        let key = IndexKey(value.clone());
+    pub fn lookup(&self, value: &Value) -> Option<&Vec<RecordId>> {
+        self.lookup_key(std::slice::from_ref(value))
+    }
+
+    /// Look up a key of an index on several columns
+    pub fn lookup_key(&self, values: &[Value]) -> Option<&Vec<RecordId>> {
+        let key = IndexKey(values.to_vec());
         self.tree.get(&key)
     }
 
     /// Range query: find all values between min and max
     /// This demonstrates the power of B-Trees for range queries
     ///
-    /// Returns: Vector of row IDs matching the range
-    pub fn range_query(&self, min: &Value, max: &Value) -> Vec<usize> {
-        let min_key = IndexKey(min.clone());
-        let max_key = IndexKey(max.clone());
+    /// Returns: Vector of record IDs matching the range
+    pub fn range_query(&self, min: &Value, max: &Value) -> Vec<RecordId> {
+        let min_key = IndexKey(vec![min.clone()]);
+        let max_key = IndexKey(vec![max.clone()]);
+
+        // An empty range (BTreeMap::range would panic)
+        if min_key > max_key {
+            return Vec::new();
+        }
 
         let mut result = Vec::new();
 
@@ -99,9 +150,22 @@ impl BTreeIndex {
         result
     }
 
+    /// Every key with the record IDs holding it, smallest key first
+    /// (call rev() for largest first) - this is how ORDER BY uses an index
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = (&[Value], &[RecordId])> {
+        self.tree
+            .iter()
+            .map(|(key, row_ids)| (key.0.as_slice(), row_ids.as_slice()))
+    }
+
     /// Remove a value from the index
-    pub fn remove(&mut self, value: &Value, row_id: usize) {
-        let key = IndexKey(value.clone());
+    pub fn remove(&mut self, value: &Value, row_id: RecordId) {
+        self.remove_key(std::slice::from_ref(value), row_id);
+    }
+
+    /// Remove a key of an index on several columns
+    pub fn remove_key(&mut self, values: &[Value], row_id: RecordId) {
+        let key = IndexKey(values.to_vec());
 
         // if let is Rust's way to handle Option types
         // It runs the block only if the value is Some(...)
@@ -137,31 +201,106 @@ impl BTreeIndex {
 mod tests {
     use super::*;
 
+    fn rid(slot: usize) -> RecordId {
+        RecordId::new(0, slot)
+    }
+
     #[test]
     fn test_btree_insert_and_lookup() {
         let mut index = BTreeIndex::new("id".to_string());
 
-        index.insert(Value::Integer(1), 0);
-        index.insert(Value::Integer(2), 1);
-        index.insert(Value::Integer(1), 2); // Duplicate value, different row
+        index.insert(Value::Integer(1), rid(0));
+        index.insert(Value::Integer(2), rid(1));
+        index.insert(Value::Integer(1), rid(2)); // Duplicate value, different row
 
         let result = index.lookup(&Value::Integer(1));
-        assert_eq!(result, Some(&vec![0, 2]));
+        assert_eq!(result, Some(&vec![rid(0), rid(2)]));
     }
 
     #[test]
     fn test_btree_range_query() {
         let mut index = BTreeIndex::new("age".to_string());
 
-        index.insert(Value::Integer(25), 0);
-        index.insert(Value::Integer(30), 1);
-        index.insert(Value::Integer(35), 2);
-        index.insert(Value::Integer(40), 3);
+        index.insert(Value::Integer(25), rid(0));
+        index.insert(Value::Integer(30), rid(1));
+        index.insert(Value::Integer(35), rid(2));
+        index.insert(Value::Integer(40), rid(3));
 
         let result = index.range_query(&Value::Integer(28), &Value::Integer(36));
-        assert!(result.contains(&1));
-        assert!(result.contains(&2));
-        assert!(!result.contains(&0));
-        assert!(!result.contains(&3));
+        assert!(result.contains(&rid(1)));
+        assert!(result.contains(&rid(2)));
+        assert!(!result.contains(&rid(0)));
+        assert!(!result.contains(&rid(3)));
+    }
+
+    #[test]
+    fn test_btree_orders_floats_totally() {
+        let mut index = BTreeIndex::new("x".to_string());
+
+        index.insert(Value::Float(f64::NAN), rid(0));
+        index.insert(Value::Float(-0.0), rid(1));
+        index.insert(Value::Float(0.0005), rid(2));
+        index.insert(Value::Float(f64::NEG_INFINITY), rid(3));
+        index.insert(Value::Float(1e300), rid(4));
+
+        // NaN finds NaN, and 0.0 finds -0.0
+        assert_eq!(index.lookup(&Value::Float(f64::NAN)), Some(&vec![rid(0)]));
+        assert_eq!(index.lookup(&Value::Float(0.0)), Some(&vec![rid(1)]));
+
+        // NaN sorts after every other value, including infinity
+        let all = index.range_query(&Value::Float(f64::NEG_INFINITY), &Value::Float(f64::NAN));
+        assert_eq!(all, vec![rid(3), rid(1), rid(2), rid(4), rid(0)]);
+        let finite = index.range_query(&Value::Float(-1.0), &Value::Float(f64::INFINITY));
+        assert_eq!(finite, vec![rid(1), rid(2), rid(4)]);
+    }
+
+    #[test]
+    fn test_btree_range_query_on_timestamps() {
+        use crate::storage::temporal::Timestamp;
+
+        let mut index = BTreeIndex::new("created_at".to_string());
+        let ts = |text: &str| Value::Timestamp(text.parse::<Timestamp>().unwrap());
+
+        index.insert(ts("2026-03-01 09:00:00"), rid(0));
+        index.insert(ts("2026-01-15 12:30:00"), rid(1));
+        index.insert(ts("2025-12-31 23:59:59"), rid(2));
+        index.insert(ts("2026-02-01 00:00:00"), rid(3));
+
+        // Everything in January 2026, in time order
+        let january = index.range_query(&ts("2026-01-01"), &ts("2026-01-31 23:59:59"));
+        assert_eq!(january, vec![rid(1)]);
+        let all = index.range_query(&ts("2000-01-01"), &ts("2100-01-01"));
+        assert_eq!(all, vec![rid(2), rid(1), rid(3), rid(0)]);
+    }
+
+    #[test]
+    fn test_btree_composite_keys() {
+        let mut index = BTreeIndex::new("a, b".to_string());
+        let key = |a: i64, b: &str| vec![Value::Integer(a), Value::Text(b.to_string())];
+
+        index.insert_key(key(1, "x"), rid(0));
+        index.insert_key(key(1, "y"), rid(1));
+        index.insert_key(key(2, "x"), rid(2));
+
+        // Only the whole key matches
+        assert_eq!(index.lookup_key(&key(1, "y")), Some(&vec![rid(1)]));
+        assert_eq!(index.lookup_key(&key(2, "y")), None);
+
+        // Walking the index gives the keys in order
+        index.insert_key(vec![Value::Null, Value::Text("z".to_string())], rid(3));
+        let order = |index: &BTreeIndex| {
+            index
+                .entries()
+                .flat_map(|(_, ids)| ids.to_vec())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(order(&index), vec![rid(3), rid(0), rid(1), rid(2)]);
+        let largest_first = index.entries().rev().map(|(key, _)| key.to_vec()).next();
+        assert_eq!(largest_first, Some(key(2, "x")));
+        index.remove_key(&[Value::Null, Value::Text("z".to_string())], rid(3));
+
+        index.remove_key(&key(1, "y"), rid(1));
+        assert_eq!(index.lookup_key(&key(1, "y")), None);
+        assert_eq!(index.lookup_key(&key(1, "x")), Some(&vec![rid(0)]));
     }
 }
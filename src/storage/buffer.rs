@@ -0,0 +1,555 @@
+// Buffer pool
+// Tables can be much bigger than the memory we are willing to spend on them,
+// so pages can't all stay in memory. The buffer pool keeps a fixed number of
+// page "frames" and caches the pages that are used most:
+//
+// - A page that is asked for and already cached is a "hit"; otherwise it is a
+//   "miss" and the page is read from disk into a frame.
+// - When every frame is taken, one page is "evicted" to make room. Pages that
+//   changed since they were read are "dirty" and are written back first.
+// - A page can be "pinned" while it is being worked on; pinned pages are
+//   never evicted.
+//
+// Two classic policies pick the page to evict:
+// - LRU (least recently used): the page that hasn't been used for the longest time
+// - CLOCK: the frames form a circle and a "hand" sweeps over them. Every use
+//   sets a page's reference bit; the hand clears set bits as it passes and
+//   evicts the first page whose bit is already clear. It approximates LRU
+//   without keeping the frames in order.
+//
+// One buffer pool is shared by every table of a database, so the memory budget
+// holds no matter how many tables there are. Each page file registers with the
+// pool and gets a FileId; pages are cached under (file, page number).
+//
+// Dirty pages only reach the disk when they are evicted or flushed (at a
+// checkpoint). That is safe because every change is in the write-ahead log
+// before it is applied to a page: recovery redoes whatever was lost.
+
+use super::disk::{DiskManager, PAGE_SIZE};
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Identifies a page file registered with a buffer pool
+pub type FileId = usize;
+
+/// A buffer pool shared by every table of a database
+pub type SharedBufferPool = Arc<Mutex<BufferPool>>;
+
+/// Default memory budget for cached pages (32 MiB = 4096 pages)
+pub const DEFAULT_MEMORY_BUDGET: usize = 32 * 1024 * 1024;
+
+/// The pool always has room for at least this many pages, whatever the budget
+/// (an operation may pin a page while it reads a few others)
+pub const MIN_FRAMES: usize = 8;
+
+/// How the buffer pool picks the page to evict when it is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EvictionPolicy {
+    /// Evict the least recently used page
+    #[default]
+    Lru,
+    /// Evict the first page the clock hand finds unreferenced
+    Clock,
+}
+
+impl FromStr for EvictionPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "lru" => Ok(EvictionPolicy::Lru),
+            "clock" => Ok(EvictionPolicy::Clock),
+            _ => Err(anyhow!(
+                "Unknown eviction policy '{}' (expected lru or clock)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for EvictionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvictionPolicy::Lru => write!(f, "LRU"),
+            EvictionPolicy::Clock => write!(f, "CLOCK"),
+        }
+    }
+}
+
+/// How big the buffer pool is and how it evicts pages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferPoolConfig {
+    /// Bytes of page data the pool may keep in memory
+    pub memory_budget: usize,
+    pub policy: EvictionPolicy,
+}
+
+impl Default for BufferPoolConfig {
+    fn default() -> Self {
+        Self {
+            memory_budget: DEFAULT_MEMORY_BUDGET,
+            policy: EvictionPolicy::default(),
+        }
+    }
+}
+
+/// Counters describing how well the buffer pool works
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BufferStats {
+    /// Page requests served from memory
+    pub hits: u64,
+    /// Page requests that had to read the page from disk
+    pub misses: u64,
+    /// Pages thrown out of memory to make room for others
+    pub evictions: u64,
+    /// Dirty pages written back to disk (on eviction or flush)
+    pub writes: u64,
+}
+
+impl BufferStats {
+    /// The share of page requests served from memory (0.0 to 1.0)
+    pub fn hit_ratio(&self) -> f64 {
+        let requests = self.hits + self.misses;
+        if requests == 0 {
+            return 0.0;
+        }
+        self.hits as f64 / requests as f64
+    }
+}
+
+/// A slot in the pool holding one cached page
+#[derive(Debug)]
+struct Frame {
+    file: FileId,
+    page_id: usize,
+    data: Vec<u8>,
+    /// Changed since it was read from (or last written to) disk
+    dirty: bool,
+    /// How many users are working on the page; pinned pages stay in memory
+    pins: usize,
+    /// When the page was last used (for LRU)
+    last_used: u64,
+    /// Used since the clock hand last passed (for CLOCK)
+    referenced: bool,
+}
+
+/// A page file registered with the pool
+#[derive(Debug)]
+struct PoolFile {
+    /// None for in-memory files: their pages exist only in the pool
+    disk: Option<DiskManager>,
+    /// Number of pages in the file
+    num_pages: usize,
+}
+
+/// Caches pages of one or more page files in a bounded number of frames
+#[derive(Debug)]
+pub struct BufferPool {
+    /// Maximum number of frames
+    capacity: usize,
+    policy: EvictionPolicy,
+    frames: Vec<Frame>,
+    /// Where each cached page lives: (file, page number) -> frame
+    page_table: HashMap<(FileId, usize), usize>,
+    files: Vec<PoolFile>,
+    /// Logical clock, advanced on every page request (for LRU)
+    tick: u64,
+    /// Position of the CLOCK hand
+    hand: usize,
+    stats: BufferStats,
+}
+
+impl BufferPool {
+    /// Create a buffer pool holding at most memory_budget bytes of pages
+    pub fn new(config: BufferPoolConfig) -> Self {
+        Self::with_capacity(
+            (config.memory_budget / PAGE_SIZE).max(MIN_FRAMES),
+            config.policy,
+        )
+    }
+
+    /// Create a pool that never evicts (for in-memory tables, whose pages
+    /// have nowhere else to go)
+    pub fn unbounded() -> Self {
+        Self::with_capacity(usize::MAX, EvictionPolicy::default())
+    }
+
+    fn with_capacity(capacity: usize, policy: EvictionPolicy) -> Self {
+        Self {
+            capacity,
+            policy,
+            frames: Vec::new(),
+            page_table: HashMap::new(),
+            files: Vec::new(),
+            tick: 0,
+            hand: 0,
+            stats: BufferStats::default(),
+        }
+    }
+
+    /// Register a page file (or an in-memory file, with None)
+    /// Returns the ID to use for its pages
+    pub fn register(&mut self, disk: Option<DiskManager>) -> FileId {
+        let num_pages = disk.as_ref().map_or(0, |disk| disk.num_pages());
+        self.files.push(PoolFile { disk, num_pages });
+        self.files.len() - 1
+    }
+
+    /// Get the number of pages in a file
+    pub fn num_pages(&self, file: FileId) -> usize {
+        self.files[file].num_pages
+    }
+
+    /// Check whether a file's pages are stored on disk
+    pub fn is_persistent(&self, file: FileId) -> bool {
+        self.files[file].disk.is_some()
+    }
+
+    /// Add a new page at the end of a file
+    /// The page is written to disk right away, so the file has no gaps
+    /// Returns the new page's number
+    pub fn allocate(&mut self, file: FileId, data: Vec<u8>) -> Result<usize> {
+        let frame = self.free_frame()?;
+
+        let page_id = self.files[file].num_pages;
+        if let Some(disk) = &mut self.files[file].disk {
+            disk.write_page(page_id, &data)?;
+        }
+        self.files[file].num_pages += 1;
+
+        self.install(frame, file, page_id, data);
+        Ok(page_id)
+    }
+
+    /// Replace a page and write it straight to disk, bypassing write-back
+    /// For pages that must be on disk before anything pointing to them is
+    pub fn write_through(&mut self, file: FileId, page_id: usize, data: Vec<u8>) -> Result<()> {
+        if page_id == self.files[file].num_pages {
+            self.allocate(file, data)?;
+            return Ok(());
+        }
+
+        if let Some(disk) = &mut self.files[file].disk {
+            disk.write_page(page_id, &data)?;
+            self.stats.writes += 1;
+        }
+        match self.page_table.get(&(file, page_id)) {
+            Some(&frame) => {
+                self.frames[frame].data = data;
+                self.frames[frame].dirty = false;
+            }
+            None => {
+                let frame = self.free_frame()?;
+                self.install(frame, file, page_id, data);
+            }
+        }
+        Ok(())
+    }
+
+    /// Get a page for reading
+    pub fn read(&mut self, file: FileId, page_id: usize) -> Result<&[u8]> {
+        let frame = self.fetch(file, page_id)?;
+        Ok(&self.frames[frame].data)
+    }
+
+    /// Get a page for changing it; the page becomes dirty
+    pub fn write(&mut self, file: FileId, page_id: usize) -> Result<&mut [u8]> {
+        let frame = self.fetch(file, page_id)?;
+        self.frames[frame].dirty = true;
+        Ok(&mut self.frames[frame].data)
+    }
+
+    /// Keep a page in memory until it is unpinned
+    pub fn pin(&mut self, file: FileId, page_id: usize) -> Result<()> {
+        let frame = self.fetch(file, page_id)?;
+        self.frames[frame].pins += 1;
+        Ok(())
+    }
+
+    /// Release a pin taken with pin()
+    pub fn unpin(&mut self, file: FileId, page_id: usize) {
+        if let Some(&frame) = self.page_table.get(&(file, page_id)) {
+            let frame = &mut self.frames[frame];
+            frame.pins = frame.pins.saturating_sub(1);
+        }
+    }
+
+    /// Write every dirty page of a file back to disk and sync the file
+    pub fn flush(&mut self, file: FileId) -> Result<()> {
+        for frame in 0..self.frames.len() {
+            if self.frames[frame].file == file {
+                self.write_back(frame)?;
+            }
+        }
+        match &mut self.files[file].disk {
+            Some(disk) => disk.sync(),
+            None => Ok(()),
+        }
+    }
+
+    /// Get the counters
+    pub fn stats(&self) -> BufferStats {
+        self.stats
+    }
+
+    /// Get the maximum number of pages the pool keeps in memory
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Get the number of pages currently in memory
+    pub fn cached_pages(&self) -> usize {
+        self.page_table.len()
+    }
+
+    /// Get the number of cached pages that changed since they were written
+    pub fn dirty_pages(&self) -> usize {
+        self.frames.iter().filter(|frame| frame.dirty).count()
+    }
+
+    /// Find (or load) the frame holding a page
+    fn fetch(&mut self, file: FileId, page_id: usize) -> Result<usize> {
+        if let Some(&frame) = self.page_table.get(&(file, page_id)) {
+            self.stats.hits += 1;
+            self.tick += 1;
+            let frame_ref = &mut self.frames[frame];
+            frame_ref.last_used = self.tick;
+            frame_ref.referenced = true;
+            return Ok(frame);
+        }
+
+        self.stats.misses += 1;
+        let data = match &mut self.files[file].disk {
+            Some(disk) => disk.read_page(page_id)?,
+            None => return Err(anyhow!("Page {} does not exist", page_id)),
+        };
+
+        let frame = self.free_frame()?;
+        self.install(frame, file, page_id, data);
+        Ok(frame)
+    }
+
+    /// Put a page into a frame
+    fn install(&mut self, frame: usize, file: FileId, page_id: usize, data: Vec<u8>) {
+        self.tick += 1;
+        let new_frame = Frame {
+            file,
+            page_id,
+            data,
+            dirty: false,
+            pins: 0,
+            last_used: self.tick,
+            referenced: true,
+        };
+
+        if frame == self.frames.len() {
+            self.frames.push(new_frame);
+        } else {
+            self.frames[frame] = new_frame;
+        }
+        self.page_table.insert((file, page_id), frame);
+    }
+
+    /// Get a frame to load a page into: an unused one, or one freed by
+    /// evicting its page
+    fn free_frame(&mut self) -> Result<usize> {
+        if self.frames.len() < self.capacity {
+            return Ok(self.frames.len());
+        }
+
+        let victim = match self.policy {
+            EvictionPolicy::Lru => self.lru_victim(),
+            EvictionPolicy::Clock => self.clock_victim(),
+        }
+        .ok_or_else(|| {
+            anyhow!(
+                "Buffer pool is full: all {} pages are pinned",
+                self.capacity
+            )
+        })?;
+
+        self.write_back(victim)?;
+        let frame = &self.frames[victim];
+        self.page_table.remove(&(frame.file, frame.page_id));
+        self.stats.evictions += 1;
+        Ok(victim)
+    }
+
+    /// Whether a frame's page may be evicted: it isn't pinned, and it can be
+    /// read back from disk later
+    fn evictable(&self, frame: usize) -> bool {
+        let frame = &self.frames[frame];
+        frame.pins == 0 && self.files[frame.file].disk.is_some()
+    }
+
+    /// The least recently used evictable frame
+    fn lru_victim(&self) -> Option<usize> {
+        (0..self.frames.len())
+            .filter(|&frame| self.evictable(frame))
+            .min_by_key(|&frame| self.frames[frame].last_used)
+    }
+
+    /// Sweep the clock hand until it finds an evictable frame that hasn't
+    /// been used since the last sweep
+    fn clock_victim(&mut self) -> Option<usize> {
+        // Two full turns: the first may only clear reference bits
+        for _ in 0..2 * self.frames.len() {
+            let frame = self.hand;
+            self.hand = (self.hand + 1) % self.frames.len();
+
+            if !self.evictable(frame) {
+                continue;
+            }
+            if self.frames[frame].referenced {
+                self.frames[frame].referenced = false;
+                continue;
+            }
+            return Some(frame);
+        }
+        None
+    }
+
+    /// Write a frame's page to disk if it is dirty
+    fn write_back(&mut self, frame: usize) -> Result<()> {
+        let frame = &mut self.frames[frame];
+        if !frame.dirty {
+            return Ok(());
+        }
+        if let Some(disk) = &mut self.files[frame.file].disk {
+            disk.write_page(frame.page_id, &frame.data)?;
+            self.stats.writes += 1;
+        }
+        frame.dirty = false;
+        Ok(())
+    }
+}
+
+impl Drop for BufferPool {
+    /// A clean shutdown writes the dirty pages back, so the next start doesn't
+    /// have to redo them from the log
+    fn drop(&mut self) {
+        for frame in 0..self.frames.len() {
+            let _ = self.write_back(frame);
+        }
+    }
+}
+
+/// Lock a shared buffer pool
+pub fn lock(pool: &SharedBufferPool) -> Result<MutexGuard<'_, BufferPool>> {
+    pool.lock()
+        .map_err(|_| anyhow!("Buffer pool lock poisoned"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str) -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("rustydb-buffer-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    /// A pool with room for MIN_FRAMES pages over a file of `pages` pages,
+    /// page N filled with the byte N
+    fn pool_with_pages(name: &str, policy: EvictionPolicy, pages: u8) -> (BufferPool, FileId) {
+        let path = temp_file(name);
+        let mut pool = BufferPool::new(BufferPoolConfig {
+            memory_budget: 0,
+            policy,
+        });
+        let file = pool.register(Some(DiskManager::open(&path).unwrap()));
+        for n in 0..pages {
+            pool.allocate(file, vec![n; PAGE_SIZE]).unwrap();
+        }
+        std::fs::remove_file(&path).unwrap();
+        (pool, file)
+    }
+
+    #[test]
+    fn test_buffer_pool_stays_within_budget() {
+        let (mut pool, file) = pool_with_pages("budget", EvictionPolicy::Lru, 20);
+        assert_eq!(pool.capacity(), MIN_FRAMES);
+        assert_eq!(pool.cached_pages(), MIN_FRAMES);
+
+        for page_id in 0..20 {
+            assert_eq!(pool.read(file, page_id).unwrap()[0], page_id as u8);
+        }
+        assert_eq!(pool.cached_pages(), MIN_FRAMES);
+
+        // Dirty pages are written back when they are evicted
+        pool.write(file, 0).unwrap()[0] = 42;
+        assert_eq!(pool.dirty_pages(), 1);
+        for page_id in 1..=MIN_FRAMES {
+            pool.read(file, page_id).unwrap();
+        }
+        assert_eq!(pool.dirty_pages(), 0);
+        assert_eq!(pool.read(file, 0).unwrap()[0], 42);
+        assert_eq!(pool.stats().writes, 1);
+        assert!(pool.stats().evictions > 0);
+    }
+
+    #[test]
+    fn test_buffer_pool_counts_hits_and_misses() {
+        let (mut pool, file) = pool_with_pages("stats", EvictionPolicy::Lru, 10);
+
+        // Pages 2..10 are cached after allocating; 0 and 1 were evicted
+        pool.read(file, 9).unwrap();
+        pool.read(file, 0).unwrap();
+        let stats = pool.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+        assert_eq!(stats.hit_ratio(), 0.5);
+    }
+
+    #[test]
+    fn test_lru_evicts_least_recently_used() {
+        let (mut pool, file) = pool_with_pages("lru", EvictionPolicy::Lru, MIN_FRAMES as u8);
+
+        // Page 0 was allocated first but has just been used, so page 1 goes
+        pool.read(file, 0).unwrap();
+        pool.allocate(file, vec![0; PAGE_SIZE]).unwrap();
+        let misses = pool.stats().misses;
+        pool.read(file, 0).unwrap();
+        assert_eq!(pool.stats().misses, misses);
+        pool.read(file, 1).unwrap();
+        assert_eq!(pool.stats().misses, misses + 1);
+    }
+
+    #[test]
+    fn test_clock_gives_referenced_pages_a_second_chance() {
+        let (mut pool, file) = pool_with_pages("clock", EvictionPolicy::Clock, MIN_FRAMES as u8);
+
+        // Every page is referenced: the hand clears all bits, comes around,
+        // and evicts page 0. Page 1 is then used again, so the next eviction
+        // skips it and takes page 2
+        pool.allocate(file, vec![0; PAGE_SIZE]).unwrap();
+        pool.read(file, 1).unwrap();
+        pool.allocate(file, vec![0; PAGE_SIZE]).unwrap();
+
+        let misses = pool.stats().misses;
+        pool.read(file, 1).unwrap();
+        assert_eq!(pool.stats().misses, misses);
+        pool.read(file, 2).unwrap();
+        assert_eq!(pool.stats().misses, misses + 1);
+    }
+
+    #[test]
+    fn test_pinned_pages_are_not_evicted() {
+        let (mut pool, file) = pool_with_pages("pins", EvictionPolicy::Lru, MIN_FRAMES as u8);
+
+        for page_id in 0..MIN_FRAMES {
+            pool.pin(file, page_id).unwrap();
+        }
+        assert!(pool.allocate(file, vec![0; PAGE_SIZE]).is_err());
+
+        pool.unpin(file, 3);
+        pool.allocate(file, vec![0; PAGE_SIZE]).unwrap();
+        let misses = pool.stats().misses;
+        pool.read(file, 0).unwrap();
+        assert_eq!(pool.stats().misses, misses);
+    }
+}
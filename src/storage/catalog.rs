@@ -0,0 +1,66 @@
+// Catalog
+// The catalog is the database's "table of contents": it records which tables
+// exist, their schemas and which columns are indexed, and the sequences that
+// number rows (see sequence.rs).
+// Row data lives in one page file per table; the catalog is what lets us find
+// and interpret those files again after a restart.
+
+use super::sequence::Sequence;
+use super::Schema;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the catalog file inside a database directory
+const CATALOG_FILE: &str = "catalog.json";
+
+/// Everything we need to know to reopen a table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableMeta {
+    pub name: String,
+    pub schema: Schema,
+    /// Columns (or JSON paths like data->>'type') that have a B-Tree index
+    /// (rebuilt from the pages on open)
+    pub indexes: Vec<String>,
+}
+
+/// The list of all tables (and sequences) in a database directory
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Catalog {
+    pub tables: Vec<TableMeta>,
+    #[serde(default)]
+    pub sequences: Vec<Sequence>,
+}
+
+impl Catalog {
+    /// Load the catalog from a database directory
+    /// A directory without a catalog is an empty database
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(CATALOG_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let data = fs::read(&path)?;
+        serde_json::from_slice(&data)
+            .map_err(|e| anyhow!("Corrupted catalog '{}': {}", path.display(), e))
+    }
+
+    /// Save the catalog into a database directory
+    /// We write to a temporary file and rename it, so a crash never leaves
+    /// a half-written catalog behind (rename is atomic on POSIX filesystems)
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        let path = dir.join(CATALOG_FILE);
+        let tmp_path = dir.join(format!("{}.tmp", CATALOG_FILE));
+
+        fs::write(&tmp_path, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+
+    /// Path of the page file holding a table's rows
+    pub fn table_path(dir: &Path, table_name: &str) -> PathBuf {
+        dir.join(format!("{}.tbl", table_name))
+    }
+}
@@ -0,0 +1,420 @@
+// Exact decimal numbers (DECIMAL / NUMERIC)
+// Floats are binary fractions: 0.1 can't be stored exactly, and adding up
+// prices ends up a fraction of a cent off. A decimal stores the digits
+// themselves, so what you insert is exactly what you get back.
+//
+// A Decimal is an integer plus a scale saying how many of its digits come
+// after the decimal point: 123.45 is 12345 with scale 2.
+//
+// A DECIMAL(precision, scale) column holds numbers with at most `precision`
+// digits in total, `scale` of them after the decimal point. Values inserted
+// into it are rounded to `scale` digits (halves round away from zero, like
+// PostgreSQL), and are rejected if they have too many digits before the point.
+//
+// Adding, subtracting and multiplying decimals is exact. Division usually
+// isn't (1 / 3), so its result is rounded to at least DIVISION_SCALE digits
+// after the point.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// The most digits a decimal can have (10^38 still fits in an i128)
+pub const MAX_PRECISION: u8 = 38;
+
+/// The digits after the point a division keeps, unless its operands have more
+const DIVISION_SCALE: u8 = 16;
+
+/// An exact decimal number: digits / 10^scale
+#[derive(Debug, Clone, Copy)]
+pub struct Decimal {
+    /// All the digits of the number, as one integer
+    digits: i128,
+    /// How many of the digits are after the decimal point
+    scale: u8,
+}
+
+/// 10^n
+fn pow10(n: u8) -> i128 {
+    10i128.pow(n as u32)
+}
+
+fn too_many_digits() -> anyhow::Error {
+    anyhow!("Decimal has more than {} digits", MAX_PRECISION)
+}
+
+impl Decimal {
+    /// Create a decimal from its digits and scale: new(12345, 2) is 123.45
+    pub fn new(digits: i128, scale: u8) -> Result<Self> {
+        if scale > MAX_PRECISION || digits.unsigned_abs() >= pow10(MAX_PRECISION) as u128 {
+            return Err(anyhow!("Decimal has more than {} digits", MAX_PRECISION));
+        }
+        Ok(Self { digits, scale })
+    }
+
+    /// Convert a float, using the shortest decimal text that reads back as
+    /// the same float (so 0.1 becomes 0.1, not 0.1000000000000000055...)
+    pub fn from_f64(value: f64) -> Result<Self> {
+        if !value.is_finite() {
+            return Err(anyhow!("Cannot convert {} to DECIMAL", value));
+        }
+        format!("{:?}", value).parse()
+    }
+
+    /// Convert to the nearest float
+    pub fn to_f64(&self) -> f64 {
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+
+    /// Number of digits after the decimal point
+    pub fn scale(&self) -> u8 {
+        self.scale
+    }
+
+    /// Round (or pad) to the given number of digits after the decimal point
+    /// Halves are rounded away from zero: 0.125 becomes 0.13, -0.125 becomes -0.13
+    pub fn round(&self, scale: u8) -> Result<Self> {
+        if scale >= self.scale {
+            let digits = self
+                .digits
+                .checked_mul(pow10(scale - self.scale))
+                .ok_or_else(|| anyhow!("Decimal has more than {} digits", MAX_PRECISION))?;
+            return Self::new(digits, scale);
+        }
+
+        let divisor = pow10(self.scale - scale);
+        let mut digits = self.digits / divisor;
+        let remainder = (self.digits % divisor).abs();
+        if remainder * 2 >= divisor {
+            digits += self.digits.signum();
+        }
+        Self::new(digits, scale)
+    }
+
+    /// Fit the number into a DECIMAL(precision, scale) column
+    pub fn fit(&self, precision: u8, scale: u8) -> Result<Self> {
+        let rounded = self.round(scale)?;
+        if rounded.digits.unsigned_abs() >= pow10(precision) as u128 {
+            return Err(anyhow!(
+                "Value {} does not fit in DECIMAL({}, {}): at most {} digits before the decimal point",
+                self,
+                precision,
+                scale,
+                precision - scale
+            ));
+        }
+        Ok(rounded)
+    }
+
+    /// The same number without trailing zeros after the decimal point
+    /// Equal decimals always normalize to the same digits and scale
+    pub fn normalize(&self) -> Self {
+        let mut normalized = *self;
+        while normalized.scale > 0 && normalized.digits % 10 == 0 {
+            normalized.digits /= 10;
+            normalized.scale -= 1;
+        }
+        normalized
+    }
+
+    /// The digits of the encoded form (see tuple.rs)
+    pub fn digits(&self) -> i128 {
+        self.digits
+    }
+
+    /// Split into the part before the decimal point, and the part after it
+    /// stretched to MAX_PRECISION digits, so two decimals with different
+    /// scales can be compared without overflowing
+    fn parts(&self) -> (i128, i128) {
+        let divisor = pow10(self.scale);
+        let fraction = self.digits % divisor;
+        (
+            self.digits / divisor,
+            fraction * pow10(MAX_PRECISION - self.scale),
+        )
+    }
+
+    /// The digits of both numbers at the larger of their scales
+    fn aligned(&self, other: &Self) -> Result<(i128, i128, u8)> {
+        let scale = self.scale.max(other.scale);
+        Ok((self.round(scale)?.digits, other.round(scale)?.digits, scale))
+    }
+
+    /// self + other
+    pub fn plus(&self, other: &Self) -> Result<Self> {
+        let (a, b, scale) = self.aligned(other)?;
+        Self::new(a.checked_add(b).ok_or_else(too_many_digits)?, scale)
+    }
+
+    /// self - other
+    pub fn minus(&self, other: &Self) -> Result<Self> {
+        let (a, b, scale) = self.aligned(other)?;
+        Self::new(a.checked_sub(b).ok_or_else(too_many_digits)?, scale)
+    }
+
+    /// self * other: 1.5 * 1.25 is 1.875, with the scales added up
+    pub fn times(&self, other: &Self) -> Result<Self> {
+        let product = Self {
+            digits: self
+                .digits
+                .checked_mul(other.digits)
+                .ok_or_else(too_many_digits)?,
+            scale: self.scale + other.scale,
+        };
+        product.round(product.scale.min(MAX_PRECISION))
+    }
+
+    /// self / other, rounded to DIVISION_SCALE digits after the point (fewer
+    /// if the number is too long for that), without trailing zeros
+    pub fn divided_by(&self, other: &Self) -> Result<Self> {
+        if other.digits == 0 {
+            return Err(anyhow!("Division by zero"));
+        }
+
+        // a / 10^s1 / (b / 10^s2) = (a * 10^(scale - s1 + s2) / b) / 10^scale
+        let mut scale = self.scale.max(other.scale).max(DIVISION_SCALE);
+        loop {
+            let shift = (scale + other.scale)
+                .checked_sub(self.scale)
+                .ok_or_else(too_many_digits)?;
+            let dividend = 10i128
+                .checked_pow(shift as u32)
+                .and_then(|power| self.digits.checked_mul(power));
+            if let Some(dividend) = dividend {
+                let mut quotient = dividend / other.digits;
+                let remainder = dividend % other.digits;
+                // Halves round away from zero, like round()
+                if remainder.unsigned_abs() * 2 >= other.digits.unsigned_abs() {
+                    quotient += dividend.signum() * other.digits.signum();
+                }
+                return Ok(Self::new(quotient, scale)?.normalize());
+            }
+            scale -= 1;
+        }
+    }
+
+    /// The remainder of self / other, with the sign of self (like PostgreSQL)
+    pub fn remainder(&self, other: &Self) -> Result<Self> {
+        let (a, b, scale) = self.aligned(other)?;
+        if b == 0 {
+            return Err(anyhow!("Division by zero"));
+        }
+        Self::new(a % b, scale)
+    }
+}
+
+/// Parse a decimal number: 42, -0.5, 1.25e3
+/// Digits beyond MAX_PRECISION after the decimal point are rounded off
+impl FromStr for Decimal {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid decimal number: '{}'", text);
+
+        let (mantissa, exponent) = match text.split_once(['e', 'E']) {
+            Some((mantissa, exponent)) => {
+                (mantissa, exponent.parse::<i32>().map_err(|_| invalid())?)
+            }
+            None => (text, 0),
+        };
+        let (negative, mantissa) = match mantissa.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, mantissa.strip_prefix('+').unwrap_or(mantissa)),
+        };
+        let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        if whole.is_empty() && fraction.is_empty()
+            || !whole
+                .chars()
+                .chain(fraction.chars())
+                .all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+
+        // 1.25e3 is 125 with scale 2 - 3 = -1; a negative scale means
+        // appending zeros instead
+        let mut digits = format!("{}{}", whole, fraction);
+        let mut scale = fraction.len() as i32 - exponent;
+        if scale < 0 {
+            digits.push_str(&"0".repeat(-scale as usize));
+            scale = 0;
+        }
+
+        // Leading zeros don't count towards the precision
+        let significant = digits.trim_start_matches('0');
+        let digits = if significant.is_empty() {
+            "0"
+        } else {
+            significant
+        };
+
+        // Too many digits after the decimal point: keep one extra digit, and
+        // let round() deal with it
+        let excess = (scale - MAX_PRECISION as i32).max(0) as usize;
+        let kept = &digits[..digits.len().saturating_sub(excess.saturating_sub(1))];
+        let kept_scale = scale - (digits.len() - kept.len()) as i32;
+        if kept.len() > MAX_PRECISION as usize + (excess > 0) as usize {
+            return Err(anyhow!(
+                "Decimal '{}' has more than {} digits",
+                text,
+                MAX_PRECISION
+            ));
+        }
+
+        let mut value: i128 = if kept.is_empty() {
+            0
+        } else {
+            kept.parse().map_err(|_| invalid())?
+        };
+        if negative {
+            value = -value;
+        }
+
+        let decimal = Self {
+            digits: value,
+            scale: kept_scale as u8,
+        };
+        if excess > 0 {
+            decimal.round(MAX_PRECISION)
+        } else {
+            Self::new(decimal.digits, decimal.scale)
+        }
+    }
+}
+
+/// Decimals print with exactly `scale` digits after the point, so a
+/// DECIMAL(10, 2) column shows 5 as 5.00
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.digits < 0 { "-" } else { "" };
+        let digits = self.digits.unsigned_abs().to_string();
+
+        if self.scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+        let digits = format!("{:0>width$}", digits, width = self.scale as usize + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - self.scale as usize);
+        write!(f, "{}{}.{}", sign, whole, fraction)
+    }
+}
+
+/// Decimals compare by value: 1.50 is equal to 1.5
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // The whole part and the fraction share the sign of the number, so
+        // comparing them one after the other orders negative numbers too
+        self.parts().cmp(&other.parts())
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+/// Decimals are written to JSON (the WAL) as strings: a JSON number would
+/// be read back through a float and lose digits
+impl Serialize for Decimal {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Decimal {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(text: &str) -> Decimal {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn test_decimal_parse_and_display() {
+        assert_eq!(dec("123.45").to_string(), "123.45");
+        assert_eq!(dec("-0.05").to_string(), "-0.05");
+        assert_eq!(dec("007").to_string(), "7");
+        assert_eq!(dec("1.25e3").to_string(), "1250");
+        assert_eq!(dec("1.5e-3").to_string(), "0.0015");
+        assert_eq!(Decimal::from_f64(0.1).unwrap().to_string(), "0.1");
+
+        assert!("1.2.3".parse::<Decimal>().is_err());
+        assert!("".parse::<Decimal>().is_err());
+        assert!("1".repeat(39).parse::<Decimal>().is_err());
+        assert!(Decimal::from_f64(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_decimal_arithmetic() {
+        assert_eq!(dec("0.1").plus(&dec("0.2")).unwrap().to_string(), "0.3");
+        assert_eq!(dec("5").minus(&dec("0.25")).unwrap().to_string(), "4.75");
+        assert_eq!(
+            dec("1.5").times(&dec("-1.25")).unwrap().to_string(),
+            "-1.875"
+        );
+        assert_eq!(
+            dec("1").divided_by(&dec("3")).unwrap().to_string(),
+            "0.3333333333333333"
+        );
+        assert_eq!(
+            dec("2").divided_by(&dec("3")).unwrap().to_string(),
+            "0.6666666666666667"
+        );
+        assert_eq!(
+            dec("10.0").divided_by(&dec("4")).unwrap().to_string(),
+            "2.5"
+        );
+        assert_eq!(
+            dec("-7.5").remainder(&dec("2")).unwrap().to_string(),
+            "-1.5"
+        );
+        assert!(dec("1").divided_by(&dec("0.00")).is_err());
+
+        // Too long for 16 digits after the point: keep as many as fit
+        let big = dec(&"9".repeat(30));
+        assert_eq!(big.divided_by(&dec("1")).unwrap(), big);
+        assert!(big.times(&big).is_err());
+    }
+
+    #[test]
+    fn test_decimal_comparison_ignores_scale() {
+        assert_eq!(dec("1.50"), dec("1.5"));
+        assert!(dec("-1.5") < dec("-1.2"));
+        assert!(dec("-0.5") < dec("0.3"));
+        assert!(
+            dec("99999999999999999999999999999999999999")
+                > dec("0.00000000000000000000000000000000000001")
+        );
+        assert_eq!(dec("2.500").normalize().to_string(), "2.5");
+    }
+
+    #[test]
+    fn test_decimal_rounding_and_fit() {
+        assert_eq!(dec("0.125").round(2).unwrap().to_string(), "0.13");
+        assert_eq!(dec("-0.125").round(2).unwrap().to_string(), "-0.13");
+        assert_eq!(dec("0.124").round(2).unwrap().to_string(), "0.12");
+        assert_eq!(dec("5").round(2).unwrap().to_string(), "5.00");
+
+        // DECIMAL(5, 2) holds up to 999.99
+        assert_eq!(dec("999.994").fit(5, 2).unwrap().to_string(), "999.99");
+        assert!(dec("999.995").fit(5, 2).is_err());
+        assert!(dec("1000").fit(5, 2).is_err());
+    }
+}
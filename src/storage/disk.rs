@@ -0,0 +1,149 @@
+// Disk manager
+// The disk manager is the lowest layer of the storage engine: it knows nothing
+// about rows or tables, only about fixed-size blocks of bytes inside a file.
+//
+// Page N lives at byte offset N * PAGE_SIZE, so reading or writing a page is a
+// single seek plus a single read/write - exactly how real databases talk to disk.
+
+use anyhow::{anyhow, Result};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Size of one on-disk page in bytes (8 KiB, the same default as PostgreSQL)
+pub const PAGE_SIZE: usize = 8192;
+
+/// Reads and writes fixed-size pages in a single database file
+#[derive(Debug)]
+pub struct DiskManager {
+    /// The open file handle
+    file: File,
+    /// Where the file lives (kept for error messages)
+    path: PathBuf,
+    /// Number of pages currently stored in the file
+    num_pages: usize,
+}
+
+impl DiskManager {
+    /// Open (or create) a page file
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(|e| anyhow!("Cannot open '{}': {}", path.display(), e))?;
+
+        // A partially written last page (e.g. after a crash) is ignored -
+        // only complete pages count
+        let len = file.metadata()?.len() as usize;
+
+        Ok(Self {
+            file,
+            path: path.to_path_buf(),
+            num_pages: len / PAGE_SIZE,
+        })
+    }
+
+    /// Read a whole page into a freshly allocated buffer
+    pub fn read_page(&mut self, page_id: usize) -> Result<Vec<u8>> {
+        if page_id >= self.num_pages {
+            return Err(anyhow!(
+                "Page {} does not exist in '{}'",
+                page_id,
+                self.path.display()
+            ));
+        }
+
+        let mut buffer = vec![0u8; PAGE_SIZE];
+        self.file
+            .seek(SeekFrom::Start((page_id * PAGE_SIZE) as u64))?;
+        self.file.read_exact(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// Write a page, padding it with zeros up to PAGE_SIZE
+    /// Writing one page past the end grows the file
+    pub fn write_page(&mut self, page_id: usize, data: &[u8]) -> Result<()> {
+        if data.len() > PAGE_SIZE {
+            return Err(anyhow!(
+                "Page data is {} bytes, but pages hold at most {} bytes",
+                data.len(),
+                PAGE_SIZE
+            ));
+        }
+        if page_id > self.num_pages {
+            return Err(anyhow!(
+                "Cannot write page {}: file only has {} pages",
+                page_id,
+                self.num_pages
+            ));
+        }
+
+        let mut buffer = vec![0u8; PAGE_SIZE];
+        buffer[..data.len()].copy_from_slice(data);
+
+        self.file
+            .seek(SeekFrom::Start((page_id * PAGE_SIZE) as u64))?;
+        self.file.write_all(&buffer)?;
+
+        if page_id == self.num_pages {
+            self.num_pages += 1;
+        }
+        Ok(())
+    }
+
+    /// Force all written pages to stable storage
+    pub fn sync(&mut self) -> Result<()> {
+        self.file.sync_data()?;
+        Ok(())
+    }
+
+    /// Get the number of pages in the file
+    pub fn num_pages(&self) -> usize {
+        self.num_pages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("rustydb-disk-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_disk_write_and_read_page() {
+        let path = temp_file("rw");
+        let mut disk = DiskManager::open(&path).unwrap();
+
+        disk.write_page(0, b"hello").unwrap();
+        disk.write_page(1, b"world").unwrap();
+        assert_eq!(disk.num_pages(), 2);
+
+        // Reopen to make sure the data really hit the file
+        let mut disk = DiskManager::open(&path).unwrap();
+        assert_eq!(disk.num_pages(), 2);
+        assert_eq!(&disk.read_page(1).unwrap()[..5], b"world");
+        assert_eq!(disk.read_page(0).unwrap().len(), PAGE_SIZE);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_disk_rejects_oversized_and_sparse_writes() {
+        let path = temp_file("bad");
+        let mut disk = DiskManager::open(&path).unwrap();
+
+        assert!(disk.write_page(0, &vec![1u8; PAGE_SIZE + 1]).is_err());
+        assert!(disk.write_page(5, b"gap").is_err());
+        assert!(disk.read_page(0).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
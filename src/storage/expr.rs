@@ -0,0 +1,986 @@
+// Expressions
+// Most values in a statement are constants, and the parser works them out
+// right away. Some have to be worked out later, once for every row:
+//   WHERE age > 30 AND city = 'Oslo'  looks at each row of the table
+//   SELECT price * quantity AS total  is worked out for each row returned
+//   ORDER BY price * quantity DESC    is the key each row is sorted by
+//   CHECK (price > 0)             looks at the row being written
+//   DEFAULT NOW()                 is the time of each INSERT, not of CREATE TABLE
+//   DEFAULT nextval('orders_id_seq')  is a new number for each INSERT
+// Those are kept as an expression tree and evaluated when they are needed.
+// The tree is saved in the catalog with the table's schema.
+//
+// Comparisons follow SQL's three-valued logic: comparing with NULL gives
+// NULL ("unknown"), and AND / OR / NOT treat NULL as "could be either":
+//   NULL AND false = false      NULL AND true = NULL
+//   NULL OR true   = true       NULL OR false = NULL
+//   NOT NULL       = NULL       NULL = NULL   = NULL
+// Arithmetic, ||, functions, LIKE, IN and BETWEEN give NULL for NULL input too.
+//
+// Arithmetic keeps integers as integers (7 / 2 = 3, like PostgreSQL); with a
+// DECIMAL the result is an exact decimal, and with a FLOAT a float.
+// A WHERE keeps only the rows whose condition is true. To ask about NULL
+// itself, use the tests that always give true or false:
+//   x IS [NOT] NULL
+//   x IS [NOT] DISTINCT FROM y    like <> / =, but NULL is equal to NULL
+//   cond IS [NOT] TRUE / FALSE / UNKNOWN
+
+use super::btree::key_order;
+use super::decimal::Decimal;
+use super::json::ColumnRef;
+use super::sequence::SequenceSession;
+use super::{temporal, DataType, Row, Schema, Value};
+use crate::query::functions;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// An expression, evaluated against a row
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Expr {
+    /// A constant: 42, 'text', DATE '2026-01-01'
+    Literal(Value),
+    /// A column, or a path into a JSON column (see json.rs)
+    Column(ColumnRef),
+    /// A function called every time the expression is evaluated: NOW(),
+    /// gen_random_uuid(), LENGTH(name)
+    Function { name: String, args: Vec<Expr> },
+    /// left op right
+    Binary {
+        left: Box<Expr>,
+        op: BinaryOp,
+        right: Box<Expr>,
+    },
+    /// NOT expr
+    Not(Box<Expr>),
+    /// expr IS NULL, or expr IS NOT NULL when negated
+    IsNull { expr: Box<Expr>, negated: bool },
+    /// expr [NOT] LIKE pattern, or ILIKE when case_insensitive
+    /// In the pattern % matches any text and _ any one character; the
+    /// escape character (backslash unless ESCAPE says otherwise) makes
+    /// the character after it match itself
+    Like {
+        expr: Box<Expr>,
+        pattern: Box<Expr>,
+        negated: bool,
+        case_insensitive: bool,
+        escape: Option<char>,
+    },
+    /// expr [NOT] IN (a, b, ...)
+    InList {
+        expr: Box<Expr>,
+        list: Vec<Expr>,
+        negated: bool,
+    },
+    /// expr [NOT] BETWEEN low AND high (both ends included)
+    Between {
+        expr: Box<Expr>,
+        low: Box<Expr>,
+        high: Box<Expr>,
+        negated: bool,
+    },
+    /// left IS DISTINCT FROM right, or IS NOT DISTINCT FROM when negated
+    IsDistinctFrom {
+        left: Box<Expr>,
+        right: Box<Expr>,
+        negated: bool,
+    },
+    /// expr IS [NOT] TRUE, FALSE or UNKNOWN (truth is None)
+    IsTruth {
+        expr: Box<Expr>,
+        truth: Option<bool>,
+        negated: bool,
+    },
+}
+
+/// The NULL an IS NULL test looks up in an index
+static NULL: Value = Value::Null;
+
+/// What a condition says about the values of a column, for index lookups
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyFilter<'a> {
+    /// column = v, or column IN (v1, v2, ...)
+    Values(Vec<&'a Value>),
+    /// column BETWEEN low AND high
+    Range(&'a Value, &'a Value),
+}
+
+/// The operators of Expr::Binary
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BinaryOp {
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    And,
+    Or,
+    /// Arithmetic on numbers, and on dates and times:
+    /// DATE '2026-01-01' + INTERVAL '1 day'
+    Plus,
+    Minus,
+    Multiply,
+    Divide,
+    Modulo,
+    /// Text concatenation: first_name || ' ' || last_name
+    Concat,
+}
+
+/// One key of an ORDER BY: price * quantity DESC NULLS LAST
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortKey {
+    /// What rows are sorted by
+    pub expr: Expr,
+    /// Largest value first
+    pub descending: bool,
+    /// Whether NULLs come before the other values. By default NULL sorts
+    /// as if it were larger than any value (last going up, first going
+    /// down), like in PostgreSQL
+    pub nulls_first: bool,
+    /// The result column the key is (its alias or the expression as written),
+    /// to merge rows sorted on several shards
+    pub name: String,
+}
+
+impl Expr {
+    /// Evaluate the expression for a row of a table with the given schema
+    pub fn evaluate(&self, schema: &Schema, row: &Row) -> Result<Value> {
+        self.eval(schema, row, None)
+    }
+
+    /// Evaluate the expression, letting it call the sequence functions:
+    /// nextval('s'), currval('s') and setval('s', n) (see sequence.rs)
+    pub fn evaluate_with(
+        &self,
+        schema: &Schema,
+        row: &Row,
+        session: &mut SequenceSession,
+    ) -> Result<Value> {
+        self.eval(schema, row, Some(session))
+    }
+
+    /// The sequence this expression takes numbers from, if it is a
+    /// nextval('s') call, like the default of a SERIAL column
+    pub fn sequence(&self) -> Option<&str> {
+        match self {
+            Expr::Function { name, args } if name.eq_ignore_ascii_case("nextval") => {
+                match args.as_slice() {
+                    [Expr::Literal(Value::Text(sequence))] => Some(sequence),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn eval(
+        &self,
+        schema: &Schema,
+        row: &Row,
+        mut session: Option<&mut SequenceSession>,
+    ) -> Result<Value> {
+        match self {
+            Expr::Literal(value) => Ok(value.clone()),
+            Expr::Column(column) => {
+                let index = column.index_in(schema)?;
+                let value = row
+                    .values
+                    .get(index)
+                    .ok_or_else(|| anyhow!("Column not found: {}", column))?;
+                Ok(column.extract(value))
+            }
+            Expr::Function { name, args } => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.eval(schema, row, session.as_deref_mut()))
+                    .collect::<Result<Vec<_>>>()?;
+                if let Some(result) = session.and_then(|session| session.call(name, &args)) {
+                    return result;
+                }
+                functions::call(name, &args)
+            }
+            Expr::Binary { left, op, right } => {
+                let left = left.eval(schema, row, session.as_deref_mut())?;
+                let right = right.eval(schema, row, session)?;
+                binary(*op, &left, &right)
+            }
+            Expr::Not(expr) => not(expr.eval(schema, row, session)?),
+            Expr::IsNull { expr, negated } => {
+                let is_null = matches!(expr.eval(schema, row, session)?, Value::Null);
+                Ok(Value::Boolean(is_null != *negated))
+            }
+            Expr::Like {
+                expr,
+                pattern,
+                negated,
+                case_insensitive,
+                escape,
+            } => {
+                let value = expr.eval(schema, row, session.as_deref_mut())?;
+                let pattern = pattern.eval(schema, row, session)?;
+                match (&value, &pattern) {
+                    (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
+                    (Value::Text(text), Value::Text(pattern)) => {
+                        let matched = if *case_insensitive {
+                            like(&text.to_lowercase(), &pattern.to_lowercase(), *escape)?
+                        } else {
+                            like(text, pattern, *escape)?
+                        };
+                        Ok(Value::Boolean(matched != *negated))
+                    }
+                    _ => Err(anyhow!("LIKE expects text, got {} and {}", value, pattern)),
+                }
+            }
+            // x IN (a, b) is x = a OR x = b: if no item matches but one is
+            // NULL, we can't tell whether x is in the list
+            Expr::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                let value = expr.eval(schema, row, session.as_deref_mut())?;
+                let mut found = Value::Boolean(false);
+                for item in list {
+                    let item = item.eval(schema, row, session.as_deref_mut())?;
+                    found = binary(BinaryOp::Or, &found, &binary(BinaryOp::Eq, &value, &item)?)?;
+                }
+                if *negated {
+                    not(found)
+                } else {
+                    Ok(found)
+                }
+            }
+            Expr::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => {
+                let value = expr.eval(schema, row, session.as_deref_mut())?;
+                let low = low.eval(schema, row, session.as_deref_mut())?;
+                let high = high.eval(schema, row, session)?;
+                let inside = binary(
+                    BinaryOp::And,
+                    &binary(BinaryOp::GtEq, &value, &low)?,
+                    &binary(BinaryOp::LtEq, &value, &high)?,
+                )?;
+                if *negated {
+                    not(inside)
+                } else {
+                    Ok(inside)
+                }
+            }
+            Expr::IsDistinctFrom {
+                left,
+                right,
+                negated,
+            } => {
+                let left = left.eval(schema, row, session.as_deref_mut())?;
+                let right = right.eval(schema, row, session)?;
+                let distinct = match (&left, &right) {
+                    (Value::Null, Value::Null) => false,
+                    (Value::Null, _) | (_, Value::Null) => true,
+                    _ => binary(BinaryOp::NotEq, &left, &right)? == Value::Boolean(true),
+                };
+                Ok(Value::Boolean(distinct != *negated))
+            }
+            Expr::IsTruth {
+                expr,
+                truth,
+                negated,
+            } => {
+                let is = match expr.eval(schema, row, session)? {
+                    Value::Null => truth.is_none(),
+                    Value::Boolean(b) => *truth == Some(b),
+                    other => {
+                        return Err(anyhow!(
+                            "IS TRUE / FALSE / UNKNOWN expects a boolean, got {}",
+                            other
+                        ))
+                    }
+                };
+                Ok(Value::Boolean(is != *negated))
+            }
+        }
+    }
+
+    /// Work out the parts that don't depend on the row once, up front:
+    /// WHERE id = LENGTH('abc') becomes WHERE id = 3, which can use an index
+    /// Volatile calls like gen_random_uuid() stay, to run for each row
+    pub fn fold_constants(self) -> Result<Expr> {
+        if self.columns().is_empty() && !self.is_volatile() {
+            let value = self.evaluate(&Schema::new(Vec::new()), &Row { values: Vec::new() })?;
+            return Ok(Expr::Literal(value));
+        }
+        Ok(match self {
+            Expr::Function { name, args } => Expr::Function {
+                name,
+                args: args
+                    .into_iter()
+                    .map(Expr::fold_constants)
+                    .collect::<Result<_>>()?,
+            },
+            Expr::Binary { left, op, right } => Expr::Binary {
+                left: Box::new(left.fold_constants()?),
+                op,
+                right: Box::new(right.fold_constants()?),
+            },
+            Expr::Not(expr) => Expr::Not(Box::new(expr.fold_constants()?)),
+            Expr::IsNull { expr, negated } => Expr::IsNull {
+                expr: Box::new(expr.fold_constants()?),
+                negated,
+            },
+            Expr::Like {
+                expr,
+                pattern,
+                negated,
+                case_insensitive,
+                escape,
+            } => Expr::Like {
+                expr: Box::new(expr.fold_constants()?),
+                pattern: Box::new(pattern.fold_constants()?),
+                negated,
+                case_insensitive,
+                escape,
+            },
+            Expr::InList {
+                expr,
+                list,
+                negated,
+            } => Expr::InList {
+                expr: Box::new(expr.fold_constants()?),
+                list: list
+                    .into_iter()
+                    .map(Expr::fold_constants)
+                    .collect::<Result<_>>()?,
+                negated,
+            },
+            Expr::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => Expr::Between {
+                expr: Box::new(expr.fold_constants()?),
+                low: Box::new(low.fold_constants()?),
+                high: Box::new(high.fold_constants()?),
+                negated,
+            },
+            Expr::IsDistinctFrom {
+                left,
+                right,
+                negated,
+            } => Expr::IsDistinctFrom {
+                left: Box::new(left.fold_constants()?),
+                right: Box::new(right.fold_constants()?),
+                negated,
+            },
+            Expr::IsTruth {
+                expr,
+                truth,
+                negated,
+            } => Expr::IsTruth {
+                expr: Box::new(expr.fold_constants()?),
+                truth,
+                negated,
+            },
+            other => other,
+        })
+    }
+
+    /// The tests on a column against constants that a row has to pass to
+    /// meet this condition: WHERE a IN (1, 2) AND (b > 2 OR c = 3) gives
+    /// a IN (1, 2). An index on one of those columns finds the candidate
+    /// rows without scanning the table.
+    pub fn key_filters(&self) -> Vec<(&ColumnRef, KeyFilter<'_>)> {
+        match self {
+            Expr::Binary {
+                left,
+                op: BinaryOp::And,
+                right,
+            } => {
+                let mut filters = left.key_filters();
+                filters.extend(right.key_filters());
+                filters
+            }
+            // x IS NOT DISTINCT FROM 5 is x = 5, but also finds NULL
+            Expr::Binary {
+                left,
+                op: BinaryOp::Eq,
+                right,
+            }
+            | Expr::IsDistinctFrom {
+                left,
+                right,
+                negated: true,
+            } => match (left.as_ref(), right.as_ref()) {
+                (Expr::Column(column), Expr::Literal(value))
+                | (Expr::Literal(value), Expr::Column(column)) => {
+                    vec![(column, KeyFilter::Values(vec![value]))]
+                }
+                _ => Vec::new(),
+            },
+            Expr::IsNull {
+                expr,
+                negated: false,
+            } => match expr.as_ref() {
+                Expr::Column(column) => vec![(column, KeyFilter::Values(vec![&NULL]))],
+                _ => Vec::new(),
+            },
+            Expr::InList {
+                expr,
+                list,
+                negated: false,
+            } => match (
+                expr.as_ref(),
+                list.iter().map(Expr::literal).collect::<Option<Vec<_>>>(),
+            ) {
+                (Expr::Column(column), Some(values)) => vec![(column, KeyFilter::Values(values))],
+                _ => Vec::new(),
+            },
+            Expr::Between {
+                expr,
+                low,
+                high,
+                negated: false,
+            } => match (expr.as_ref(), low.literal(), high.literal()) {
+                (Expr::Column(column), Some(low), Some(high)) => {
+                    vec![(column, KeyFilter::Range(low, high))]
+                }
+                _ => Vec::new(),
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    fn literal(&self) -> Option<&Value> {
+        match self {
+            Expr::Literal(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The `column = constant` tests a row has to pass to meet this condition:
+    /// WHERE a = 1 AND (b > 2 OR c = 3) gives a = 1
+    pub fn equalities(&self) -> Vec<(&ColumnRef, &Value)> {
+        self.key_filters()
+            .into_iter()
+            .filter_map(|(column, filter)| match filter {
+                KeyFilter::Values(values) if values.len() == 1 => Some((column, values[0])),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Whether the expression calls a volatile function (see functions.rs)
+    fn is_volatile(&self) -> bool {
+        match self {
+            Expr::Literal(_) | Expr::Column(_) => false,
+            Expr::Function { name, args } => {
+                functions::is_volatile(name) || args.iter().any(Expr::is_volatile)
+            }
+            Expr::Binary { left, right, .. } | Expr::IsDistinctFrom { left, right, .. } => {
+                left.is_volatile() || right.is_volatile()
+            }
+            Expr::Not(expr) | Expr::IsNull { expr, .. } | Expr::IsTruth { expr, .. } => {
+                expr.is_volatile()
+            }
+            Expr::Like { expr, pattern, .. } => expr.is_volatile() || pattern.is_volatile(),
+            Expr::InList { expr, list, .. } => {
+                expr.is_volatile() || list.iter().any(Expr::is_volatile)
+            }
+            Expr::Between {
+                expr, low, high, ..
+            } => expr.is_volatile() || low.is_volatile() || high.is_volatile(),
+        }
+    }
+
+    /// The columns the expression reads
+    pub fn columns(&self) -> Vec<&ColumnRef> {
+        match self {
+            Expr::Literal(_) => Vec::new(),
+            Expr::Column(column) => vec![column],
+            Expr::Function { args, .. } => args.iter().flat_map(Expr::columns).collect(),
+            Expr::Binary { left, right, .. } | Expr::IsDistinctFrom { left, right, .. } => {
+                let mut columns = left.columns();
+                columns.extend(right.columns());
+                columns
+            }
+            Expr::Not(expr) | Expr::IsNull { expr, .. } | Expr::IsTruth { expr, .. } => {
+                expr.columns()
+            }
+            Expr::Like { expr, pattern, .. } => {
+                let mut columns = expr.columns();
+                columns.extend(pattern.columns());
+                columns
+            }
+            Expr::InList { expr, list, .. } => {
+                let mut columns = expr.columns();
+                columns.extend(list.iter().flat_map(Expr::columns));
+                columns
+            }
+            Expr::Between {
+                expr, low, high, ..
+            } => {
+                let mut columns = expr.columns();
+                columns.extend(low.columns());
+                columns.extend(high.columns());
+                columns
+            }
+        }
+    }
+}
+
+impl SortKey {
+    /// Compare two rows' values of this key
+    pub fn compare(&self, a: &Value, b: &Value) -> Ordering {
+        match (a, b) {
+            (Value::Null, Value::Null) => Ordering::Equal,
+            (Value::Null, _) if self.nulls_first => Ordering::Less,
+            (Value::Null, _) => Ordering::Greater,
+            (_, Value::Null) => self.compare(b, a).reverse(),
+            _ if self.descending => key_order(a, b).reverse(),
+            _ => key_order(a, b),
+        }
+    }
+}
+
+/// Sort rows by the keys of an ORDER BY: by the first key, then rows that
+/// tie by the second, and so on. Each key is worked out once per row.
+pub fn sort_rows(schema: &Schema, keys: &[SortKey], rows: Vec<Row>) -> Result<Vec<Row>> {
+    let mut keyed = rows
+        .into_iter()
+        .map(|row| {
+            let values = keys
+                .iter()
+                .map(|key| key.expr.evaluate(schema, &row))
+                .collect::<Result<Vec<_>>>()?;
+            Ok((values, row))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    keyed.sort_by(|(a, _), (b, _)| compare_keys(keys, a, b));
+    Ok(keyed.into_iter().map(|(_, row)| row).collect())
+}
+
+/// Compare two rows by the values of their sort keys, in turn
+fn compare_keys(keys: &[SortKey], a: &[Value], b: &[Value]) -> Ordering {
+    keys.iter()
+        .zip(a.iter().zip(b))
+        .map(|(key, (a, b))| key.compare(a, b))
+        .find(|order| order.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// NOT, where NOT NULL is NULL
+fn not(value: Value) -> Result<Value> {
+    match value {
+        Value::Null => Ok(Value::Null),
+        Value::Boolean(b) => Ok(Value::Boolean(!b)),
+        other => Err(anyhow!("NOT expects a boolean, got {}", other)),
+    }
+}
+
+/// Check whether text matches a LIKE pattern
+fn like(text: &str, pattern: &str, escape: Option<char>) -> Result<bool> {
+    enum Token {
+        /// _
+        One,
+        /// %
+        Any,
+        Char(char),
+    }
+
+    let mut tokens = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            c if Some(c) == escape => {
+                Token::Char(chars.next().ok_or_else(|| {
+                    anyhow!("LIKE pattern must not end with the escape character")
+                })?)
+            }
+            '_' => Token::One,
+            '%' => Token::Any,
+            c => Token::Char(c),
+        });
+    }
+
+    // matches[i]: the first i tokens can match the text read so far
+    let mut matches = vec![false; tokens.len() + 1];
+    matches[0] = true;
+    for (i, token) in tokens.iter().enumerate() {
+        matches[i + 1] = matches[i] && matches!(token, Token::Any);
+    }
+    for c in text.chars() {
+        let mut next = vec![false; tokens.len() + 1];
+        for (i, token) in tokens.iter().enumerate() {
+            next[i + 1] = match token {
+                Token::Any => next[i] || matches[i + 1],
+                Token::One => matches[i],
+                Token::Char(expected) => matches[i] && *expected == c,
+            };
+        }
+        matches = next;
+    }
+    Ok(matches[tokens.len()])
+}
+
+/// Apply a binary operator to two values
+fn binary(op: BinaryOp, left: &Value, right: &Value) -> Result<Value> {
+    let truth = |value: &Value| match value {
+        Value::Null => Ok(None),
+        Value::Boolean(b) => Ok(Some(*b)),
+        other => Err(anyhow!(
+            "{} expects booleans, got {}",
+            if op == BinaryOp::And { "AND" } else { "OR" },
+            other
+        )),
+    };
+
+    match op {
+        BinaryOp::And => Ok(match (truth(left)?, truth(right)?) {
+            (Some(false), _) | (_, Some(false)) => Value::Boolean(false),
+            (Some(true), Some(true)) => Value::Boolean(true),
+            _ => Value::Null,
+        }),
+        BinaryOp::Or => Ok(match (truth(left)?, truth(right)?) {
+            (Some(true), _) | (_, Some(true)) => Value::Boolean(true),
+            (Some(false), Some(false)) => Value::Boolean(false),
+            _ => Value::Null,
+        }),
+        _ if matches!(left, Value::Null) || matches!(right, Value::Null) => Ok(Value::Null),
+        BinaryOp::Plus
+        | BinaryOp::Minus
+        | BinaryOp::Multiply
+        | BinaryOp::Divide
+        | BinaryOp::Modulo => arithmetic(op, left, right),
+        // Anything can be concatenated as its text ('#' || 1 is '#1'), and
+        // binary data as its bytes
+        BinaryOp::Concat => Ok(match (left, right) {
+            (Value::Bytes(a), Value::Bytes(b)) => Value::Bytes([a.as_slice(), b].concat()),
+            _ => Value::Text(format!("{}{}", left, right)),
+        }),
+        BinaryOp::Eq
+        | BinaryOp::NotEq
+        | BinaryOp::Lt
+        | BinaryOp::LtEq
+        | BinaryOp::Gt
+        | BinaryOp::GtEq => {
+            let order = compare(left, right).ok_or_else(|| {
+                anyhow!(
+                    "Cannot compare {} with {}",
+                    left.type_name(),
+                    right.type_name()
+                )
+            })?;
+            Ok(Value::Boolean(match op {
+                BinaryOp::Eq => order == Ordering::Equal,
+                BinaryOp::NotEq => order != Ordering::Equal,
+                BinaryOp::Lt => order == Ordering::Less,
+                BinaryOp::LtEq => order != Ordering::Greater,
+                BinaryOp::Gt => order == Ordering::Greater,
+                _ => order != Ordering::Less,
+            }))
+        }
+    }
+}
+
+/// +, -, *, / or % on two values that are not NULL
+/// Floats follow IEEE-754: 1.0 / 0 is Infinity and 0.0 / 0 is NaN
+fn arithmetic(op: BinaryOp, left: &Value, right: &Value) -> Result<Value> {
+    let is_zero = match right {
+        Value::Integer(i) => *i == 0,
+        Value::Decimal(d) => d.digits() == 0,
+        _ => false,
+    };
+    let is_float = matches!(left, Value::Float(_)) || matches!(right, Value::Float(_));
+    if is_zero && !is_float && matches!(op, BinaryOp::Divide | BinaryOp::Modulo) {
+        return Err(anyhow!("Division by zero"));
+    }
+
+    match (left, right) {
+        (Value::Integer(a), Value::Integer(b)) => match op {
+            BinaryOp::Plus => a.checked_add(*b),
+            BinaryOp::Minus => a.checked_sub(*b),
+            BinaryOp::Multiply => a.checked_mul(*b),
+            BinaryOp::Divide => a.checked_div(*b),
+            _ => a.checked_rem(*b),
+        }
+        .map(Value::Integer)
+        .ok_or_else(|| anyhow!("Integer out of range")),
+        // A float anywhere makes the result a float
+        (Value::Float(_), Value::Integer(_) | Value::Float(_) | Value::Decimal(_))
+        | (Value::Integer(_) | Value::Decimal(_), Value::Float(_)) => {
+            let float = |value: &Value| match value {
+                Value::Integer(i) => *i as f64,
+                Value::Decimal(d) => d.to_f64(),
+                Value::Float(f) => *f,
+                _ => unreachable!("only numbers get here"),
+            };
+            let (a, b) = (float(left), float(right));
+            Ok(Value::Float(match op {
+                BinaryOp::Plus => a + b,
+                BinaryOp::Minus => a - b,
+                BinaryOp::Multiply => a * b,
+                BinaryOp::Divide => a / b,
+                _ => a % b,
+            }))
+        }
+        // Otherwise a decimal makes it exact
+        (Value::Integer(_) | Value::Decimal(_), Value::Integer(_) | Value::Decimal(_)) => {
+            let decimal = |value: &Value| match value {
+                Value::Integer(i) => Decimal::new(*i as i128, 0),
+                Value::Decimal(d) => Ok(*d),
+                _ => unreachable!("only numbers get here"),
+            };
+            let (a, b) = (decimal(left)?, decimal(right)?);
+            Ok(Value::Decimal(match op {
+                BinaryOp::Plus => a.plus(&b)?,
+                BinaryOp::Minus => a.minus(&b)?,
+                BinaryOp::Multiply => a.times(&b)?,
+                BinaryOp::Divide => a.divided_by(&b)?,
+                _ => a.remainder(&b)?,
+            }))
+        }
+        _ => match op {
+            BinaryOp::Plus => temporal::add(left, right),
+            BinaryOp::Minus => temporal::subtract(left, right),
+            BinaryOp::Multiply => Err(anyhow!("Cannot multiply {} by {}", left, right)),
+            _ => Err(anyhow!("Cannot divide {} by {}", left, right)),
+        },
+    }
+}
+
+/// Compare two values, converting one to the other's type when they differ:
+/// 5 and 5.0 are equal, and '2026-01-01' compares as a date with a DATE
+pub fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    if let Some(order) = left.compare(right) {
+        return Some(order);
+    }
+
+    let converted = |value: &Value, like: &Value| value.convert_to(&type_of(like)?).ok();
+    converted(right, left)
+        .and_then(|right| left.compare(&right))
+        .or_else(|| converted(left, right).and_then(|left| left.compare(right)))
+}
+
+/// The column type a value would be stored as
+fn type_of(value: &Value) -> Option<DataType> {
+    Some(match value {
+        Value::Null => return None,
+        Value::Integer(_) => DataType::Integer,
+        Value::Float(_) => DataType::Float,
+        Value::Decimal(d) => DataType::Decimal {
+            precision: super::decimal::MAX_PRECISION,
+            scale: Some(d.scale()),
+        },
+        Value::Text(_) => DataType::Text,
+        Value::Boolean(_) => DataType::Boolean,
+        Value::Date(_) => DataType::Date,
+        Value::Time(_) => DataType::Time,
+        Value::Timestamp(_) => DataType::Timestamp,
+        Value::Interval(_) => DataType::Interval,
+        Value::Bytes(_) => DataType::Blob,
+        Value::Json(_) => DataType::Json,
+        Value::Uuid(_) => DataType::Uuid,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Column;
+
+    fn column(name: &str) -> Box<Expr> {
+        Box::new(Expr::Column(ColumnRef::column(name)))
+    }
+
+    fn literal(value: Value) -> Box<Expr> {
+        Box::new(Expr::Literal(value))
+    }
+
+    #[test]
+    fn test_evaluate_with_nulls() {
+        let schema = Schema::new(vec![Column {
+            name: "price".to_string(),
+            data_type: DataType::Float,
+            primary_key: false,
+            nullable: true,
+            default: None,
+        }]);
+        let positive = Expr::Binary {
+            left: column("price"),
+            op: BinaryOp::Gt,
+            right: literal(Value::Integer(0)),
+        };
+        let row = |value: Value| Row {
+            values: vec![value],
+        };
+
+        assert_eq!(
+            positive.evaluate(&schema, &row(Value::Float(2.5))).unwrap(),
+            Value::Boolean(true)
+        );
+        assert_eq!(
+            positive
+                .evaluate(&schema, &row(Value::Float(-1.0)))
+                .unwrap(),
+            Value::Boolean(false)
+        );
+        assert_eq!(
+            positive.evaluate(&schema, &row(Value::Null)).unwrap(),
+            Value::Null
+        );
+
+        // NULL AND false is false, NULL OR false is unknown
+        let with = |op: BinaryOp, value: bool| Expr::Binary {
+            left: Box::new(positive.clone()),
+            op,
+            right: literal(Value::Boolean(value)),
+        };
+        assert_eq!(
+            with(BinaryOp::And, false)
+                .evaluate(&schema, &row(Value::Null))
+                .unwrap(),
+            Value::Boolean(false)
+        );
+        assert_eq!(
+            with(BinaryOp::Or, false)
+                .evaluate(&schema, &row(Value::Null))
+                .unwrap(),
+            Value::Null
+        );
+        assert_eq!(
+            with(BinaryOp::Or, true)
+                .evaluate(&schema, &row(Value::Null))
+                .unwrap(),
+            Value::Boolean(true)
+        );
+
+        // IS [NOT] DISTINCT FROM and IS UNKNOWN are never unknown themselves
+        let distinct = |value: Value, negated: bool| Expr::IsDistinctFrom {
+            left: column("price"),
+            right: literal(value),
+            negated,
+        };
+        let null_row = row(Value::Null);
+        assert_eq!(
+            distinct(Value::Null, false)
+                .evaluate(&schema, &null_row)
+                .unwrap(),
+            Value::Boolean(false)
+        );
+        assert_eq!(
+            distinct(Value::Integer(1), false)
+                .evaluate(&schema, &null_row)
+                .unwrap(),
+            Value::Boolean(true)
+        );
+        assert_eq!(
+            distinct(Value::Integer(1), true)
+                .evaluate(&schema, &row(Value::Float(1.0)))
+                .unwrap(),
+            Value::Boolean(true)
+        );
+        let unknown = Expr::IsTruth {
+            expr: Box::new(positive.clone()),
+            truth: None,
+            negated: false,
+        };
+        assert_eq!(
+            unknown.evaluate(&schema, &null_row).unwrap(),
+            Value::Boolean(true)
+        );
+        assert_eq!(
+            unknown.evaluate(&schema, &row(Value::Float(1.0))).unwrap(),
+            Value::Boolean(false)
+        );
+
+        assert!(Expr::Binary {
+            left: column("price"),
+            op: BinaryOp::Eq,
+            right: literal(Value::Text("x".into())),
+        }
+        .evaluate(&schema, &row(Value::Float(1.0)))
+        .is_err());
+    }
+
+    #[test]
+    fn test_like_patterns() {
+        assert!(like("apple", "a%", Some('\\')).unwrap());
+        assert!(like("apple", "%pl_", Some('\\')).unwrap());
+        assert!(like("", "%", Some('\\')).unwrap());
+        assert!(!like("apple", "a_", Some('\\')).unwrap());
+        assert!(!like("apple", "%x%", Some('\\')).unwrap());
+        assert!(like("50%", "50\\%", Some('\\')).unwrap());
+        assert!(!like("500", "50\\%", Some('\\')).unwrap());
+        assert!(like("a_b", "a!_b", Some('!')).unwrap());
+        assert!(like("a\\b", "a\\b", None).unwrap());
+        assert!(like("x", "x\\", Some('\\')).is_err());
+    }
+
+    #[test]
+    fn test_in_lists_and_key_filters() {
+        let schema = Schema::new(Vec::new());
+        let one = || literal(Value::Integer(1));
+        let condition = Expr::Binary {
+            left: Box::new(Expr::InList {
+                expr: column("a"),
+                list: vec![*one(), Expr::Literal(Value::Integer(2))],
+                negated: false,
+            }),
+            op: BinaryOp::And,
+            right: Box::new(Expr::Between {
+                expr: column("b"),
+                low: one(),
+                high: one(),
+                negated: false,
+            }),
+        };
+        let filters = condition.key_filters();
+        assert_eq!(filters.len(), 2);
+        assert_eq!(
+            filters[0].1,
+            KeyFilter::Values(vec![&Value::Integer(1), &Value::Integer(2)])
+        );
+        assert_eq!(
+            filters[1].1,
+            KeyFilter::Range(&Value::Integer(1), &Value::Integer(1))
+        );
+
+        // An OR, or a NOT IN, doesn't limit a column to some values
+        let either = Expr::Binary {
+            left: Box::new(condition.clone()),
+            op: BinaryOp::Or,
+            right: one(),
+        };
+        assert!(either.key_filters().is_empty());
+
+        // NULL IN (1, NULL) is unknown, 2 NOT IN (1, NULL) too
+        let in_list = |value: Value, negated: bool| Expr::InList {
+            expr: literal(value),
+            list: vec![*one(), Expr::Literal(Value::Null)],
+            negated,
+        };
+        let no_row = Row { values: Vec::new() };
+        assert_eq!(
+            in_list(Value::Integer(1), false)
+                .evaluate(&schema, &no_row)
+                .unwrap(),
+            Value::Boolean(true)
+        );
+        assert_eq!(
+            in_list(Value::Integer(2), true)
+                .evaluate(&schema, &no_row)
+                .unwrap(),
+            Value::Null
+        );
+        assert_eq!(
+            in_list(Value::Null, false)
+                .evaluate(&schema, &no_row)
+                .unwrap(),
+            Value::Null
+        );
+    }
+}
@@ -0,0 +1,420 @@
+// JSON documents
+// A JSON column holds a whole document - objects, arrays, strings, numbers -
+// so semi-structured data can be stored without deciding on its columns first.
+// Documents are kept parsed (as serde_json values) and written as JSON text.
+//
+// Paths reach into a document, the way PostgreSQL does it:
+//   data->'user'          the member "user" of the object, still JSON
+//   data->'tags'->0       the first element of the array "tags"
+//   data->>'type'         the member "type" as text: "click" becomes click
+//   json_extract(data, '$.user.name')      the same as data->'user'->'name'
+//   json_array_length(data->'tags')        the number of elements
+// A path that leads nowhere (a missing key, or an index past the end) is NULL.
+//
+// A column path can be indexed like a column: CREATE INDEX ON events
+// ((data->>'type')) keeps the extracted values of every row in a B-Tree.
+
+use super::{compare_floats, DataType, Schema, Value};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// A parsed JSON document
+pub type Json = serde_json::Value;
+
+/// One step of a path into a document
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathStep {
+    /// A member of an object
+    Key(String),
+    /// An element of an array; negative indexes count from the end
+    Index(i64),
+}
+
+/// What a column path produces
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathOutput {
+    /// The value itself: the column, or the JSON found at the path (->)
+    Value,
+    /// The JSON found at the path as text (->>)
+    Text,
+    /// The number of elements of the array found at the path
+    ArrayLength,
+}
+
+/// A column, or a value extracted from a JSON column
+/// This is what WHERE clauses, SELECT lists and indexes look at
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnRef {
+    pub column: String,
+    pub path: Vec<PathStep>,
+    pub output: PathOutput,
+}
+
+impl ColumnRef {
+    /// A plain column
+    pub fn column(name: &str) -> Self {
+        Self {
+            column: name.to_string(),
+            path: Vec::new(),
+            output: PathOutput::Value,
+        }
+    }
+
+    /// Check whether this is a plain column (no JSON path)
+    pub fn is_column(&self) -> bool {
+        self.path.is_empty() && self.output == PathOutput::Value
+    }
+
+    /// Find the column in a schema
+    /// Paths can only be followed into JSON columns
+    pub fn index_in(&self, schema: &Schema) -> Result<usize> {
+        let index = schema
+            .get_column_index(&self.column)
+            .ok_or_else(|| anyhow!("Column not found: {}", self.column))?;
+
+        if !self.is_column() && schema.columns[index].data_type != DataType::Json {
+            return Err(anyhow!("Column '{}' is not a JSON column", self.column));
+        }
+        Ok(index)
+    }
+
+    /// The type of the values this produces
+    pub fn data_type(&self, schema: &Schema) -> Result<DataType> {
+        let index = self.index_in(schema)?;
+        Ok(match self.output {
+            PathOutput::Value if self.path.is_empty() => schema.columns[index].data_type.clone(),
+            PathOutput::Value => DataType::Json,
+            PathOutput::Text => DataType::Text,
+            PathOutput::ArrayLength => DataType::Integer,
+        })
+    }
+
+    /// Extract the value from the column's value
+    pub fn extract(&self, value: &Value) -> Value {
+        if self.is_column() {
+            return value.clone();
+        }
+
+        let Value::Json(doc) = value else {
+            return Value::Null;
+        };
+        let Some(found) = follow(doc, &self.path) else {
+            return Value::Null;
+        };
+
+        match self.output {
+            PathOutput::Value => Value::Json(found.clone()),
+            PathOutput::Text => to_text(found),
+            PathOutput::ArrayLength => Value::Integer(array_length(found) as i64),
+        }
+    }
+}
+
+/// Written the way it is in SQL: data->'tags'->>0
+/// Indexes on paths are named (and saved in the catalog) with this text
+impl fmt::Display for ColumnRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.output == PathOutput::ArrayLength {
+            write!(f, "json_array_length(")?;
+        }
+
+        write!(f, "{}", self.column)?;
+        for (i, step) in self.path.iter().enumerate() {
+            let last = i == self.path.len() - 1;
+            write!(
+                f,
+                "{}",
+                if last && self.output == PathOutput::Text {
+                    "->>"
+                } else {
+                    "->"
+                }
+            )?;
+            match step {
+                PathStep::Key(key) => write!(f, "'{}'", key.replace('\'', "''"))?,
+                PathStep::Index(index) => write!(f, "{}", index)?,
+            }
+        }
+
+        if self.output == PathOutput::ArrayLength {
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+/// Read back what Display wrote
+impl FromStr for ColumnRef {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid column path: {}", text);
+
+        let (inner, array_length) = match text
+            .strip_prefix("json_array_length(")
+            .and_then(|rest| rest.strip_suffix(')'))
+        {
+            Some(inner) => (inner, true),
+            None => (text, false),
+        };
+
+        let (column, mut rest) = match inner.find("->") {
+            Some(pos) => inner.split_at(pos),
+            None => (inner, ""),
+        };
+        let mut column_ref = Self::column(column);
+        if column.is_empty() {
+            return Err(invalid());
+        }
+
+        while !rest.is_empty() {
+            if column_ref.output == PathOutput::Text {
+                // ->> has to be the last step
+                return Err(invalid());
+            }
+            rest = if let Some(after) = rest.strip_prefix("->>") {
+                column_ref.output = PathOutput::Text;
+                after
+            } else {
+                rest.strip_prefix("->").ok_or_else(invalid)?
+            };
+
+            let (step, after) = match rest.strip_prefix('\'') {
+                Some(quoted) => parse_quoted(quoted).ok_or_else(invalid)?,
+                None => {
+                    let end = rest.find("->").unwrap_or(rest.len());
+                    let index = rest[..end].parse().map_err(|_| invalid())?;
+                    (PathStep::Index(index), &rest[end..])
+                }
+            };
+            column_ref.path.push(step);
+            rest = after;
+        }
+
+        if array_length {
+            if column_ref.output == PathOutput::Text {
+                return Err(invalid());
+            }
+            column_ref.output = PathOutput::ArrayLength;
+        }
+        Ok(column_ref)
+    }
+}
+
+/// Column paths are saved (in CHECK constraints, in the catalog) as their SQL text
+impl Serialize for ColumnRef {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for ColumnRef {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Split 'key'... into the key (with '' unescaped) and what follows it
+fn parse_quoted(text: &str) -> Option<(PathStep, &str)> {
+    let mut key = String::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c != '\'' {
+            key.push(c);
+        } else if chars.peek().is_some_and(|&(_, next)| next == '\'') {
+            key.push('\'');
+            chars.next();
+        } else {
+            return Some((PathStep::Key(key), &text[i + 1..]));
+        }
+    }
+    None
+}
+
+/// Parse a JSON path like the ones of json_extract(): $.user.name, $.tags[0]
+/// Keys with unusual characters can be quoted: $."first name"
+pub fn parse_json_path(path: &str) -> Result<Vec<PathStep>> {
+    let invalid = || {
+        anyhow!(
+            "Invalid JSON path: '{}' (expected something like '$.a.b[0]')",
+            path
+        )
+    };
+
+    let mut rest = path.trim().strip_prefix('$').ok_or_else(invalid)?;
+    let mut steps = Vec::new();
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('[') {
+            let (index, after) = after.split_once(']').ok_or_else(invalid)?;
+            steps.push(PathStep::Index(
+                index.trim().parse().map_err(|_| invalid())?,
+            ));
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix(".\"") {
+            let (key, after) = after.split_once('"').ok_or_else(invalid)?;
+            steps.push(PathStep::Key(key.to_string()));
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            if end == 0 {
+                return Err(invalid());
+            }
+            steps.push(PathStep::Key(after[..end].to_string()));
+            rest = &after[end..];
+        } else {
+            return Err(invalid());
+        }
+    }
+    Ok(steps)
+}
+
+/// Follow a path into a document
+/// Returns None if a step leads nowhere
+pub fn follow<'a>(doc: &'a Json, path: &[PathStep]) -> Option<&'a Json> {
+    let mut current = doc;
+    for step in path {
+        current = match (step, current) {
+            (PathStep::Key(key), Json::Object(members)) => members.get(key)?,
+            (PathStep::Index(index), Json::Array(elements)) => {
+                let index = if *index < 0 {
+                    elements.len().checked_sub(index.unsigned_abs() as usize)?
+                } else {
+                    *index as usize
+                };
+                elements.get(index)?
+            }
+            _ => return None,
+        };
+    }
+    Some(current)
+}
+
+/// A JSON value as text, the way ->> returns it: strings lose their quotes,
+/// JSON null is SQL NULL, and anything else is written as JSON
+pub fn to_text(json: &Json) -> Value {
+    match json {
+        Json::Null => Value::Null,
+        Json::String(s) => Value::Text(s.clone()),
+        other => Value::Text(other.to_string()),
+    }
+}
+
+/// The number of elements of an array (0 for anything that is not an array)
+pub fn array_length(json: &Json) -> usize {
+    match json {
+        Json::Array(elements) => elements.len(),
+        _ => 0,
+    }
+}
+
+/// A total order for JSON values, so they can be compared and indexed
+/// Like PostgreSQL's jsonb: null < strings < numbers < booleans < arrays < objects.
+/// Numbers compare by value (1 equals 1.0), arrays element by element and
+/// objects member by member, in key order.
+pub fn compare(a: &Json, b: &Json) -> Ordering {
+    fn rank(json: &Json) -> u8 {
+        match json {
+            Json::Null => 0,
+            Json::String(_) => 1,
+            Json::Number(_) => 2,
+            Json::Bool(_) => 3,
+            Json::Array(_) => 4,
+            Json::Object(_) => 5,
+        }
+    }
+
+    match (a, b) {
+        (Json::String(a), Json::String(b)) => a.cmp(b),
+        (Json::Number(a), Json::Number(b)) => match (a.as_i64(), b.as_i64()) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            _ => compare_floats(
+                a.as_f64().unwrap_or(f64::NAN),
+                b.as_f64().unwrap_or(f64::NAN),
+            ),
+        },
+        (Json::Bool(a), Json::Bool(b)) => a.cmp(b),
+        (Json::Array(a), Json::Array(b)) => a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| compare(a, b))
+            .find(|order| order.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        // serde_json keeps object members sorted by key
+        (Json::Object(a), Json::Object(b)) => a
+            .iter()
+            .zip(b)
+            .map(|((key_a, a), (key_b, b))| key_a.cmp(key_b).then_with(|| compare(a, b)))
+            .find(|order| order.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_column_paths() {
+        let doc = Value::Json(json!({"type": "click", "tags": ["a", "b"], "n": 5, "it's": null}));
+        let path = |text: &str| text.parse::<ColumnRef>().unwrap();
+
+        assert_eq!(
+            path("data->>'type'").extract(&doc),
+            Value::Text("click".into())
+        );
+        assert_eq!(
+            path("data->'type'").extract(&doc),
+            Value::Json(json!("click"))
+        );
+        assert_eq!(
+            path("data->'tags'->>-1").extract(&doc),
+            Value::Text("b".into())
+        );
+        assert_eq!(path("data->>'n'").extract(&doc), Value::Text("5".into()));
+        assert_eq!(path("data->>'it''s'").extract(&doc), Value::Null);
+        assert_eq!(path("data->'missing'->0").extract(&doc), Value::Null);
+        assert_eq!(
+            path("json_array_length(data->'tags')").extract(&doc),
+            Value::Integer(2)
+        );
+        assert_eq!(path("data").extract(&doc), doc);
+
+        // Display and FromStr agree, so index names survive the catalog
+        for text in ["data", "data->'a'->>0", "json_array_length(data->'it''s')"] {
+            assert_eq!(path(text).to_string(), text);
+        }
+        assert!("data->>'a'->'b'".parse::<ColumnRef>().is_err());
+        assert!("data->oops".parse::<ColumnRef>().is_err());
+    }
+
+    #[test]
+    fn test_json_paths() {
+        let steps = parse_json_path("$.user.\"first name\"[1]").unwrap();
+        assert_eq!(
+            steps,
+            vec![
+                PathStep::Key("user".into()),
+                PathStep::Key("first name".into()),
+                PathStep::Index(1),
+            ]
+        );
+        assert_eq!(parse_json_path("$").unwrap(), vec![]);
+        assert!(parse_json_path("user.name").is_err());
+        assert!(parse_json_path("$..name").is_err());
+    }
+
+    #[test]
+    fn test_json_order() {
+        assert_eq!(compare(&json!(1), &json!(1.0)), Ordering::Equal);
+        assert_eq!(compare(&json!("z"), &json!(1)), Ordering::Less);
+        assert_eq!(compare(&json!([1, 2]), &json!([1, 2, 0])), Ordering::Less);
+        assert_eq!(compare(&json!({"a": 1}), &json!({"a": 2})), Ordering::Less);
+        assert_eq!(compare(&json!(null), &json!(false)), Ordering::Less);
+    }
+}
@@ -2,10 +2,30 @@
 // This module contains the core storage engine for our database
 
 pub mod btree;
+pub mod buffer;
+pub mod catalog;
+pub mod decimal;
+pub mod disk;
+pub mod expr;
+pub mod json;
+pub mod mvcc;
+pub mod overflow;
 pub mod page;
+pub mod sequence;
 pub mod table;
+pub mod temporal;
+pub mod tuple;
+pub mod uuid;
+pub mod wal;
 
+use anyhow::{anyhow, Result};
+use decimal::Decimal;
+use expr::Expr;
+use json::Json;
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use temporal::{Date, Interval, Time, Timestamp};
+use uuid::Uuid;
 
 /// Represents a single row in a table
 /// In Rust, we use Vec<Value> to represent a row where each Value is a column
@@ -17,48 +37,327 @@ pub struct Row {
 /// Represents different data types that can be stored in the database
 /// This is called an "enum" in Rust - it can be one of several variants
 /// The Serialize and Deserialize traits allow us to convert to/from JSON
-/// Note: We derive Eq even though Float doesn't strictly support it
-/// This is a simplification for educational purposes
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Value {
     Null,
     Integer(i64),
-    // For Float, we'll use i64 representation to allow Eq
-    // In a real database, you'd handle floats more carefully
-    Float(i64), // Stored as integer representation (multiply by 1000 for precision)
+    /// A 64-bit IEEE-754 float
+    /// f64 is only partially ordered (NaN != NaN), so Value defines its own
+    /// equality and ordering for floats - see compare_floats()
+    Float(#[serde(with = "float_format")] f64),
+    /// An exact decimal number (DECIMAL / NUMERIC columns)
+    Decimal(Decimal),
     Text(String),
     Boolean(bool),
+    Date(Date),
+    Time(Time),
+    Timestamp(Timestamp),
+    Interval(Interval),
+    /// Binary data (BLOB / BYTEA columns)
+    Bytes(#[serde(with = "hex_format")] Vec<u8>),
+    /// A JSON document (JSON / JSONB columns, see json.rs)
+    Json(Json),
+    /// A 128-bit UUID
+    Uuid(Uuid),
 }
 
-impl Value {
-    /// Convert Value to a string representation
-    /// The 'impl' keyword implements methods for a type
-    pub fn to_string(&self) -> String {
+/// Convert Value to a string representation
+/// Implementing the Display trait gives us to_string() and {} formatting for free
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             // The 'match' keyword is Rust's pattern matching - like a powerful switch statement
-            Value::Null => "NULL".to_string(),
-            Value::Integer(i) => i.to_string(),
-            Value::Float(f) => {
-                // Convert back to float representation (divided by 1000)
-                let float_val = (*f as f64) / 1000.0;
-                float_val.to_string()
+            Value::Null => write!(f, "NULL"),
+            Value::Integer(i) => write!(f, "{}", i),
+            // Written the way PostgreSQL writes them (and reads them back)
+            Value::Float(x) if x.is_infinite() => {
+                write!(f, "{}", if *x > 0.0 { "Infinity" } else { "-Infinity" })
             }
-            Value::Text(s) => s.clone(),
-            Value::Boolean(b) => b.to_string(),
+            Value::Float(x) => {
+                // Debug formatting gives the shortest text that reads back as
+                // the same f64, and switches to exponents for huge or tiny
+                // values (Display would print 1e300 with 300 zeros)
+                let text = format!("{:?}", x);
+                write!(f, "{}", text.strip_suffix(".0").unwrap_or(&text))
+            }
+            Value::Decimal(d) => write!(f, "{}", d),
+            Value::Text(s) => write!(f, "{}", s),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Date(d) => write!(f, "{}", d),
+            Value::Time(t) => write!(f, "{}", t),
+            Value::Timestamp(ts) => write!(f, "{}", ts),
+            Value::Interval(i) => write!(f, "{}", i),
+            // Like PostgreSQL, binary data shows as hex: \xdeadbeef
+            Value::Bytes(b) => write!(f, "\\x{}", hex_format::encode(b)),
+            Value::Json(doc) => write!(f, "{}", doc),
+            Value::Uuid(u) => write!(f, "{}", u),
         }
     }
+}
 
+impl Value {
     /// Compare two values (used for WHERE clauses)
     /// The '&self' means this method borrows 'self' (doesn't take ownership)
     pub fn compare(&self, other: &Value) -> Option<std::cmp::Ordering> {
         match (self, other) {
             (Value::Integer(a), Value::Integer(b)) => Some(a.cmp(b)),
-            (Value::Float(a), Value::Float(b)) => Some(a.cmp(b)),
+            (Value::Float(a), Value::Float(b)) => Some(compare_floats(*a, *b)),
+            (Value::Decimal(a), Value::Decimal(b)) => Some(a.cmp(b)),
             (Value::Text(a), Value::Text(b)) => Some(a.cmp(b)),
             (Value::Boolean(a), Value::Boolean(b)) => Some(a.cmp(b)),
+            (Value::Date(a), Value::Date(b)) => Some(a.cmp(b)),
+            (Value::Time(a), Value::Time(b)) => Some(a.cmp(b)),
+            (Value::Timestamp(a), Value::Timestamp(b)) => Some(a.cmp(b)),
+            (Value::Interval(a), Value::Interval(b)) => Some(a.cmp(b)),
+            // Byte by byte, like memcmp
+            (Value::Bytes(a), Value::Bytes(b)) => Some(a.cmp(b)),
+            (Value::Json(a), Value::Json(b)) => Some(json::compare(a, b)),
+            (Value::Uuid(a), Value::Uuid(b)) => Some(a.cmp(b)),
             _ => None, // Can't compare different types
         }
     }
+
+    /// The name of the value's type, for error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "NULL",
+            Value::Integer(_) => "INTEGER",
+            Value::Float(_) => "FLOAT",
+            Value::Decimal(_) => "DECIMAL",
+            Value::Text(_) => "TEXT",
+            Value::Boolean(_) => "BOOLEAN",
+            Value::Date(_) => "DATE",
+            Value::Time(_) => "TIME",
+            Value::Timestamp(_) => "TIMESTAMP",
+            Value::Interval(_) => "INTERVAL",
+            Value::Bytes(_) => "BLOB",
+            Value::Json(_) => "JSON",
+            Value::Uuid(_) => "UUID",
+        }
+    }
+
+    /// Check whether a value can be stored in a column of the given type as it is
+    /// NULL fits every type (NOT NULL is checked separately)
+    pub fn has_type(&self, data_type: &DataType) -> bool {
+        matches!(
+            (self, data_type),
+            (Value::Null, _)
+                | (Value::Integer(_), DataType::Integer)
+                | (Value::Float(_), DataType::Float)
+                | (Value::Decimal(_), DataType::Decimal { .. })
+                | (Value::Text(_), DataType::Text)
+                | (Value::Boolean(_), DataType::Boolean)
+                | (Value::Date(_), DataType::Date)
+                | (Value::Time(_), DataType::Time)
+                | (Value::Timestamp(_), DataType::Timestamp)
+                | (Value::Interval(_), DataType::Interval)
+                | (Value::Bytes(_), DataType::Blob)
+                | (Value::Json(_), DataType::Json)
+                | (Value::Uuid(_), DataType::Uuid)
+        )
+    }
+
+    /// Convert a value so it can be stored in or compared with a column of
+    /// the given type: 5 in a FLOAT column is 5.0, and '2026-01-01' in a DATE
+    /// column is a date
+    /// Decimals are converted exactly, without rounding to the column's scale
+    /// (see Column::coerce for that). Other values are returned unchanged.
+    pub fn convert_to(&self, data_type: &DataType) -> Result<Value> {
+        match (self, data_type) {
+            // Only whole numbers go into INTEGER columns: 2.0 is 2, 2.5 is an error
+            (Value::Float(f), DataType::Integer) => {
+                if f.fract() == 0.0 && *f >= i64::MIN as f64 && *f < i64::MAX as f64 {
+                    Ok(Value::Integer(*f as i64))
+                } else {
+                    Err(anyhow!(
+                        "{} is not a whole number in the INTEGER range",
+                        self
+                    ))
+                }
+            }
+            (Value::Decimal(d), DataType::Integer) => {
+                let whole = d.round(0)?;
+                match i64::try_from(whole.digits()) {
+                    Ok(i) if whole == *d => Ok(Value::Integer(i)),
+                    _ => Err(anyhow!("{} is not a whole number in the INTEGER range", d)),
+                }
+            }
+            (Value::Integer(i), DataType::Float) => Ok(Value::Float(*i as f64)),
+            (Value::Decimal(d), DataType::Float) => Ok(Value::Float(d.to_f64())),
+            // Text that is a number is read as one: '1.5', and the values no
+            // numeric literal can write: 'NaN', 'Infinity', '-Infinity' (any
+            // case). Other text stays text, and is rejected as such.
+            (Value::Text(s), DataType::Float) => match s.trim().parse::<f64>() {
+                Ok(f) => Ok(Value::Float(f)),
+                Err(_) => Ok(self.clone()),
+            },
+            (Value::Integer(i), DataType::Decimal { .. }) => {
+                Ok(Value::Decimal(Decimal::new(*i as i128, 0)?))
+            }
+            (Value::Float(f), DataType::Decimal { .. }) => {
+                Ok(Value::Decimal(Decimal::from_f64(*f)?))
+            }
+            (Value::Text(s), DataType::Date) => Ok(Value::Date(s.parse()?)),
+            (Value::Text(s), DataType::Time) => Ok(Value::Time(s.parse()?)),
+            (Value::Text(s), DataType::Timestamp) => Ok(Value::Timestamp(s.parse()?)),
+            (Value::Text(s), DataType::Interval) => Ok(Value::Interval(s.parse()?)),
+            // '\xdeadbeef' is hex, any other text is taken as its UTF-8 bytes
+            (Value::Text(s), DataType::Blob) => match s.strip_prefix("\\x") {
+                Some(hex) => Ok(Value::Bytes(hex_format::decode(hex)?)),
+                None => Ok(Value::Bytes(s.as_bytes().to_vec())),
+            },
+            // JSON text is parsed; numbers and booleans become JSON scalars
+            (Value::Text(s), DataType::Json) => serde_json::from_str(s)
+                .map(Value::Json)
+                .map_err(|e| anyhow!("Invalid JSON '{}': {}", s, e)),
+            (Value::Integer(i), DataType::Json) => Ok(Value::Json(Json::from(*i))),
+            (Value::Float(f), DataType::Json) => serde_json::Number::from_f64(*f)
+                .map(|n| Value::Json(Json::Number(n)))
+                .ok_or_else(|| anyhow!("JSON cannot hold {}", f)),
+            (Value::Decimal(d), DataType::Json) => serde_json::from_str(&d.to_string())
+                .map(Value::Json)
+                .map_err(|e| anyhow!("JSON cannot hold {}: {}", d, e)),
+            (Value::Boolean(b), DataType::Json) => Ok(Value::Json(Json::Bool(*b))),
+            (Value::Text(s), DataType::Uuid) => Ok(Value::Uuid(s.parse()?)),
+            (Value::Bytes(b), DataType::Uuid) => match <[u8; 16]>::try_from(b.as_slice()) {
+                Ok(bytes) => Ok(Value::Uuid(Uuid::from_bytes(bytes))),
+                Err(_) => Err(anyhow!("A UUID is 16 bytes, got {}", b.len())),
+            },
+            (Value::Date(d), DataType::Timestamp) => Ok(Value::Timestamp(d.to_timestamp())),
+            // Only midnight is a whole date: 2026-01-01 12:00 is not 2026-01-01
+            (Value::Timestamp(ts), DataType::Date) if ts.time().0 == 0 => {
+                Ok(Value::Date(ts.date()))
+            }
+            (Value::Timestamp(ts), DataType::Date) => {
+                Err(anyhow!("Timestamp {} is not a whole date", ts))
+            }
+            _ => Ok(self.clone()),
+        }
+    }
+}
+
+/// Values are equal when compare() says so - Null is equal to Null
+/// (this is the storage's equality, used for keys; SQL's =, where NULL = NULL
+/// is unknown, is in expr.rs)
+/// Implementing this by hand (instead of deriving it) makes floats follow
+/// compare_floats(), which is what lets us promise Eq
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Null, Value::Null) => true,
+            _ => self.compare(other) == Some(std::cmp::Ordering::Equal),
+        }
+    }
+}
+
+impl Eq for Value {}
+
+/// A total order for floats, the way PostgreSQL defines it:
+/// - NaN is equal to NaN, and greater than every other value
+/// - -0.0 is equal to 0.0
+pub fn compare_floats(a: f64, b: f64) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        // Without NaN, partial_cmp always succeeds (and treats -0.0 == 0.0)
+        (false, false) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+    }
+}
+
+/// How binary data is written to JSON (the WAL): as a hex string, which is
+/// half the size of a JSON array of numbers
+pub mod hex_format {
+    use anyhow::{anyhow, Result};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    /// Two lowercase hex digits per byte
+    pub fn encode(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// The bytes written as hex digits (upper or lower case)
+    pub fn decode(hex: &str) -> Result<Vec<u8>> {
+        if !hex.len().is_multiple_of(2) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(anyhow!("Invalid hex string: '{}'", hex));
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| Ok(u8::from_str_radix(&hex[i..i + 2], 16)?))
+            .collect()
+    }
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        decode(&hex).map_err(serde::de::Error::custom)
+    }
+}
+
+/// How a Float is written to JSON (the WAL)
+/// JSON has no NaN or infinity, so those are written as strings.
+/// Finite floats are JSON numbers, which serde_json always writes with a
+/// fraction or exponent (5.0, not 5). An integer in place of a float is
+/// therefore an old WAL record from when floats were stored as fixed-point
+/// integers (the value times 1000), and is converted on the way in.
+mod float_format {
+    use serde::de::{self, Visitor};
+    use serde::{Deserializer, Serializer};
+    use std::fmt;
+
+    pub fn serialize<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        if value.is_nan() {
+            serializer.serialize_str("NaN")
+        } else if value.is_infinite() {
+            serializer.serialize_str(if *value < 0.0 {
+                "-Infinity"
+            } else {
+                "Infinity"
+            })
+        } else {
+            serializer.serialize_f64(*value)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        deserializer.deserialize_any(FloatVisitor)
+    }
+
+    struct FloatVisitor;
+
+    impl Visitor<'_> for FloatVisitor {
+        type Value = f64;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "a float, or \"NaN\", \"Infinity\" or \"-Infinity\"")
+        }
+
+        fn visit_f64<E: de::Error>(self, value: f64) -> Result<f64, E> {
+            Ok(value)
+        }
+
+        fn visit_i64<E: de::Error>(self, fixed_point: i64) -> Result<f64, E> {
+            Ok(fixed_point as f64 / 1000.0)
+        }
+
+        fn visit_u64<E: de::Error>(self, fixed_point: u64) -> Result<f64, E> {
+            Ok(fixed_point as f64 / 1000.0)
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<f64, E> {
+            match value {
+                "NaN" => Ok(f64::NAN),
+                "Infinity" => Ok(f64::INFINITY),
+                "-Infinity" => Ok(f64::NEG_INFINITY),
+                _ => Err(E::invalid_value(de::Unexpected::Str(value), &self)),
+            }
+        }
+    }
 }
 
 /// Represents the schema (structure) of a table
@@ -66,6 +365,50 @@ impl Value {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Schema {
     pub columns: Vec<Column>,
+    /// UNIQUE constraints: each one is a list of columns whose values, taken
+    /// together, can't appear in two rows (the primary key is not listed here)
+    #[serde(default)]
+    pub unique: Vec<Vec<String>>,
+    /// FOREIGN KEY constraints: columns whose values must exist in another table
+    #[serde(default)]
+    pub foreign_keys: Vec<ForeignKey>,
+    /// CHECK constraints: conditions every row has to meet
+    #[serde(default)]
+    pub checks: Vec<Check>,
+}
+
+/// A CHECK constraint: CONSTRAINT positive_price CHECK (price > 0)
+/// A row breaks it when the condition is false; NULL (unknown) lets it pass
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Check {
+    /// The constraint's name, reported when a row breaks it
+    pub name: String,
+    pub condition: Expr,
+}
+
+/// A FOREIGN KEY constraint: orders (customer_id) REFERENCES customers (id)
+/// Every row's key (unless it contains NULL) must be a key of a row in the
+/// referenced table, which has to be its primary key or UNIQUE
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForeignKey {
+    pub columns: Vec<String>,
+    pub ref_table: String,
+    pub ref_columns: Vec<String>,
+    /// What happens to our rows when the row they reference is deleted
+    pub on_delete: ReferentialAction,
+    /// What happens to our rows when the key they reference changes
+    pub on_update: ReferentialAction,
+}
+
+/// What to do with the rows referencing a key that goes away
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ReferentialAction {
+    /// Refuse the change (also what NO ACTION means here)
+    Restrict,
+    /// Delete the referencing rows, or give them the new key
+    Cascade,
+    /// Set the referencing columns to NULL
+    SetNull,
 }
 
 /// Represents a single column definition
@@ -75,6 +418,9 @@ pub struct Column {
     pub data_type: DataType,
     pub primary_key: bool,
     pub nullable: bool,
+    /// The value used when an INSERT doesn't give one (NULL if there is no default)
+    #[serde(default)]
+    pub default: Option<Expr>,
 }
 
 /// The data types our database supports
@@ -82,15 +428,108 @@ pub struct Column {
 pub enum DataType {
     Integer,
     Float,
+    /// DECIMAL(precision, scale): at most `precision` digits, `scale` of
+    /// them after the decimal point
+    /// Without a scale (plain DECIMAL or NUMERIC) values keep the digits
+    /// after the point they were written with, as in PostgreSQL
+    Decimal {
+        precision: u8,
+        scale: Option<u8>,
+    },
     Text,
     Boolean,
+    Date,
+    Time,
+    Timestamp,
+    Interval,
+    /// Binary data
+    Blob,
+    /// JSON documents
+    Json,
+    /// 128-bit UUIDs
+    Uuid,
+}
+
+/// Types are written the way CREATE TABLE spells them
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataType::Integer => write!(f, "INTEGER"),
+            DataType::Float => write!(f, "FLOAT"),
+            DataType::Decimal {
+                precision,
+                scale: Some(scale),
+            } => write!(f, "DECIMAL({}, {})", precision, scale),
+            DataType::Decimal { scale: None, .. } => write!(f, "DECIMAL"),
+            DataType::Text => write!(f, "TEXT"),
+            DataType::Boolean => write!(f, "BOOLEAN"),
+            DataType::Date => write!(f, "DATE"),
+            DataType::Time => write!(f, "TIME"),
+            DataType::Timestamp => write!(f, "TIMESTAMP"),
+            DataType::Interval => write!(f, "INTERVAL"),
+            DataType::Blob => write!(f, "BLOB"),
+            DataType::Json => write!(f, "JSON"),
+            DataType::Uuid => write!(f, "UUID"),
+        }
+    }
+}
+
+impl Column {
+    /// Check whether the column rejects NULL (primary keys always do)
+    pub fn is_not_null(&self) -> bool {
+        self.primary_key || !self.nullable
+    }
+
+    /// Turn a value into what gets stored in this column
+    /// Values are converted to the column's type where SQL allows it (an
+    /// integer in a FLOAT column, text in a DATE column), and decimals are
+    /// rounded to the column's scale. Anything else is an error: text in an
+    /// INTEGER column, or NULL in a NOT NULL column.
+    pub fn coerce(&self, value: Value) -> Result<Value> {
+        if matches!(value, Value::Null) && self.is_not_null() {
+            return Err(anyhow!(
+                "NULL value in column '{}' violates NOT NULL constraint",
+                self.name
+            ));
+        }
+
+        let value = value
+            .convert_to(&self.data_type)
+            .map_err(|e| anyhow!("Column '{}': {}", self.name, e))?;
+        if !value.has_type(&self.data_type) {
+            let shown = match &value {
+                Value::Text(s) => format!("'{}'", s),
+                other => other.to_string(),
+            };
+            return Err(anyhow!(
+                "Column '{}' is {}, but got {} {}",
+                self.name,
+                self.data_type,
+                value.type_name(),
+                shown
+            ));
+        }
+
+        match (value, &self.data_type) {
+            (Value::Decimal(d), DataType::Decimal { precision, scale }) => d
+                .fit(*precision, scale.unwrap_or(d.scale()))
+                .map(Value::Decimal)
+                .map_err(|e| anyhow!("Column '{}': {}", self.name, e)),
+            (value, _) => Ok(value),
+        }
+    }
 }
 
 impl Schema {
     /// Create a new schema with the given columns
     pub fn new(columns: Vec<Column>) -> Self {
         // 'Self' refers to the type we're implementing (Schema)
-        Self { columns }
+        Self {
+            columns,
+            unique: Vec::new(),
+            foreign_keys: Vec::new(),
+            checks: Vec::new(),
+        }
     }
 
     /// Find the index of a column by name
@@ -103,4 +542,171 @@ impl Schema {
     pub fn get_primary_key_index(&self) -> Option<usize> {
         self.columns.iter().position(|col| col.primary_key)
     }
+
+    /// Check whether some columns, taken together, are the primary key or a
+    /// UNIQUE constraint (in any order)
+    pub fn is_unique_key(&self, columns: &[String]) -> bool {
+        let mut wanted: Vec<&str> = columns.iter().map(String::as_str).collect();
+        wanted.sort_unstable();
+
+        let primary_key = self
+            .get_primary_key_index()
+            .map(|index| vec![self.columns[index].name.clone()]);
+        primary_key.iter().chain(&self.unique).any(|key| {
+            let mut key: Vec<&str> = key.iter().map(String::as_str).collect();
+            key.sort_unstable();
+            key == wanted
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_float_equality_and_ordering() {
+        use std::cmp::Ordering;
+
+        assert_eq!(Value::Float(f64::NAN), Value::Float(f64::NAN));
+        assert_eq!(Value::Float(-0.0), Value::Float(0.0));
+        assert_ne!(Value::Float(1.0), Value::Integer(1));
+        assert_eq!(compare_floats(f64::NAN, f64::INFINITY), Ordering::Greater);
+        assert_eq!(compare_floats(-1.0, f64::NAN), Ordering::Less);
+        assert_eq!(compare_floats(0.0005, 0.0), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_float_display() {
+        assert_eq!(Value::Float(2.75).to_string(), "2.75");
+        assert_eq!(Value::Float(5.0).to_string(), "5");
+        assert_eq!(Value::Float(0.0005).to_string(), "0.0005");
+        assert_eq!(Value::Float(-0.0).to_string(), "-0");
+        assert_eq!(Value::Float(1e300).to_string(), "1e300");
+        assert_eq!(Value::Float(f64::NAN).to_string(), "NaN");
+        assert_eq!(Value::Float(f64::NEG_INFINITY).to_string(), "-Infinity");
+    }
+
+    #[test]
+    fn test_decimal_columns_round_on_the_way_in() {
+        let price = Column {
+            name: "price".to_string(),
+            data_type: DataType::Decimal {
+                precision: 6,
+                scale: Some(2),
+            },
+            primary_key: false,
+            nullable: true,
+            default: None,
+        };
+        let dec = |text: &str| Value::Decimal(text.parse().unwrap());
+
+        assert_eq!(price.coerce(dec("19.999")).unwrap().to_string(), "20.00");
+        assert_eq!(price.coerce(Value::Integer(7)).unwrap().to_string(), "7.00");
+        assert_eq!(price.coerce(Value::Float(0.1)).unwrap(), dec("0.1"));
+        assert_eq!(price.coerce(Value::Null).unwrap(), Value::Null);
+        assert!(price.coerce(dec("12345.6")).is_err());
+        assert!(price.coerce(Value::Float(f64::NAN)).is_err());
+
+        // Without a scale, values keep theirs
+        let numeric = Column {
+            data_type: DataType::Decimal {
+                precision: decimal::MAX_PRECISION,
+                scale: None,
+            },
+            ..price
+        };
+        assert_eq!(numeric.coerce(dec("1.2345")).unwrap().to_string(), "1.2345");
+        assert_eq!(numeric.coerce(dec("-0.50")).unwrap().to_string(), "-0.50");
+        assert_eq!(numeric.coerce(Value::Integer(7)).unwrap().to_string(), "7");
+
+        // Decimals travel through the WAL as exact text
+        let json = serde_json::to_string(&dec("0.10")).unwrap();
+        assert_eq!(json, r#"{"Decimal":"0.10"}"#);
+        let back: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.to_string(), "0.10");
+    }
+
+    #[test]
+    fn test_column_types_are_enforced() {
+        let column = |data_type: DataType, nullable: bool| Column {
+            name: "c".to_string(),
+            data_type,
+            primary_key: false,
+            nullable,
+            default: None,
+        };
+        let id = column(DataType::Integer, false);
+        let score = column(DataType::Float, true);
+
+        assert_eq!(score.coerce(Value::Integer(3)).unwrap(), Value::Float(3.0));
+        assert_eq!(id.coerce(Value::Float(2.0)).unwrap(), Value::Integer(2));
+        assert_eq!(
+            id.coerce(Value::Decimal("7.00".parse().unwrap())).unwrap(),
+            Value::Integer(7)
+        );
+        assert_eq!(score.coerce(Value::Null).unwrap(), Value::Null);
+
+        let error = id.coerce(Value::Text("x".to_string())).unwrap_err();
+        assert_eq!(error.to_string(), "Column 'c' is INTEGER, but got TEXT 'x'");
+        let error = id.coerce(Value::Null).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "NULL value in column 'c' violates NOT NULL constraint"
+        );
+        assert!(id.coerce(Value::Decimal("2.5".parse().unwrap())).is_err());
+        assert!(id.coerce(Value::Float(1e300)).is_err());
+        assert!(score.coerce(Value::Boolean(true)).is_err());
+        assert!(column(DataType::Text, true)
+            .coerce(Value::Integer(1))
+            .is_err());
+    }
+
+    #[test]
+    fn test_bytes_display_and_json() {
+        let bytes = Value::Bytes(vec![0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(bytes.to_string(), "\\xdeadbeef");
+
+        let json = serde_json::to_string(&bytes).unwrap();
+        assert_eq!(json, r#"{"Bytes":"deadbeef"}"#);
+        assert_eq!(serde_json::from_str::<Value>(&json).unwrap(), bytes);
+
+        let blob = DataType::Blob;
+        assert_eq!(
+            Value::Text("\\xDEADBEEF".into()).convert_to(&blob).unwrap(),
+            bytes
+        );
+        assert_eq!(
+            Value::Text("hi".into()).convert_to(&blob).unwrap(),
+            Value::Bytes(b"hi".to_vec())
+        );
+        assert!(Value::Text("\\xabc".into()).convert_to(&blob).is_err());
+        let shorter = Value::Bytes(vec![1, 2]).compare(&Value::Bytes(vec![1, 2, 0]));
+        assert_eq!(shorter, Some(std::cmp::Ordering::Less));
+    }
+
+    #[test]
+    fn test_float_json_roundtrip() {
+        for f in [
+            0.1,
+            -0.0,
+            5.0,
+            1e300,
+            f64::NAN,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+        ] {
+            let json = serde_json::to_string(&Value::Float(f)).unwrap();
+            let back: Value = serde_json::from_str(&json).unwrap();
+            match back {
+                Value::Float(g) => assert_eq!(g.to_bits(), f.to_bits(), "{}", json),
+                other => panic!("{} came back as {:?}", json, other),
+            }
+        }
+
+        // Old WAL records hold floats as fixed-point integers
+        let legacy: Value = serde_json::from_str(r#"{"Float":2750}"#).unwrap();
+        assert_eq!(legacy, Value::Float(2.75));
+        assert!(serde_json::from_str::<Value>(r#"{"Float":"pi"}"#).is_err());
+    }
 }
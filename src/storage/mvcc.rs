@@ -0,0 +1,197 @@
+// MVCC - Multi-Version Concurrency Control
+// Instead of overwriting a row, every change creates a new *version* of it.
+// Each version remembers which transaction created it (xmin) and which
+// transaction deleted it (xmax). A transaction reads through a "snapshot":
+// it only sees versions created by transactions that had committed when the
+// snapshot was taken, and ignores deletions that happened after that point.
+//
+// The result: readers never see half-finished work, a long-running reader sees
+// one consistent state of the database, and writers never wait for readers.
+//
+// The price is that old versions pile up. VACUUM removes the versions that no
+// running transaction can see anymore.
+
+use super::Row;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Transaction IDs are simple increasing numbers
+pub type TxnId = u64;
+
+/// One version of a row, as stored in a page
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RowVersion {
+    /// The transaction that created this version
+    pub xmin: TxnId,
+    /// The transaction that deleted (or replaced) this version, if any
+    pub xmax: Option<TxnId>,
+    /// The column values
+    pub row: Row,
+}
+
+impl RowVersion {
+    /// The transaction IDs of this version
+    pub fn header(&self) -> VersionHeader {
+        VersionHeader {
+            xmin: self.xmin,
+            xmax: self.xmax,
+        }
+    }
+}
+
+/// The transaction IDs of a row version, without its values
+/// This is all a scan needs to decide whether a version is visible, and it
+/// can be read from the start of a tuple without decoding the rest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VersionHeader {
+    pub xmin: TxnId,
+    pub xmax: Option<TxnId>,
+}
+
+/// What a transaction is allowed to see
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// The transaction reading through this snapshot
+    pub txn_id: TxnId,
+    /// Transactions with an ID >= limit started after the snapshot was taken
+    pub limit: TxnId,
+    /// Transactions that were still running when the snapshot was taken
+    pub in_progress: BTreeSet<TxnId>,
+}
+
+impl Snapshot {
+    /// Check whether the effects of a transaction are visible in this snapshot
+    /// We always see our own changes; other transactions must have finished
+    /// before the snapshot was taken
+    pub fn sees(&self, txn_id: TxnId) -> bool {
+        txn_id == self.txn_id || (txn_id < self.limit && !self.in_progress.contains(&txn_id))
+    }
+
+    /// Check whether a row version is visible in this snapshot:
+    /// its creation is visible, and its deletion (if any) is not
+    pub fn is_visible(&self, version: &RowVersion) -> bool {
+        self.can_see(version.header())
+    }
+
+    /// Check whether a row version is visible, going by its header alone
+    pub fn can_see(&self, header: VersionHeader) -> bool {
+        self.sees(header.xmin) && !header.xmax.is_some_and(|xmax| self.sees(xmax))
+    }
+
+    /// The oldest transaction this snapshot might still consider running
+    fn horizon(&self) -> TxnId {
+        self.in_progress
+            .iter()
+            .next()
+            .copied()
+            .unwrap_or(self.limit)
+            .min(self.txn_id)
+    }
+}
+
+/// Hands out transaction IDs and keeps track of running transactions
+/// Rolled-back changes are undone physically, so a transaction that is not
+/// running anymore is always a committed one
+#[derive(Debug, Default)]
+pub struct TransactionManager {
+    /// The next ID to hand out
+    next_txn_id: TxnId,
+    /// Running transactions and the horizon of their snapshots
+    active: BTreeMap<TxnId, TxnId>,
+}
+
+impl TransactionManager {
+    /// Create a transaction manager whose IDs start after last_txn_id
+    pub fn new(last_txn_id: TxnId) -> Self {
+        Self {
+            next_txn_id: last_txn_id + 1,
+            active: BTreeMap::new(),
+        }
+    }
+
+    /// Start a transaction and take its snapshot
+    pub fn begin(&mut self) -> Snapshot {
+        let txn_id = self.next_txn_id;
+        self.next_txn_id += 1;
+
+        let snapshot = Snapshot {
+            txn_id,
+            limit: txn_id,
+            in_progress: self.active.keys().copied().collect(),
+        };
+        self.active.insert(txn_id, snapshot.horizon());
+        snapshot
+    }
+
+    /// Forget a transaction once it has committed or rolled back
+    pub fn finish(&mut self, txn_id: TxnId) {
+        self.active.remove(&txn_id);
+    }
+
+    /// Check whether any transaction is running
+    pub fn has_active(&self) -> bool {
+        !self.active.is_empty()
+    }
+
+    /// Versions deleted by a transaction older than this are invisible to every
+    /// running (and future) transaction, so VACUUM may remove them
+    pub fn vacuum_horizon(&self) -> TxnId {
+        self.active
+            .values()
+            .copied()
+            .min()
+            .unwrap_or(self.next_txn_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Value;
+
+    fn version(xmin: TxnId, xmax: Option<TxnId>) -> RowVersion {
+        RowVersion {
+            xmin,
+            xmax,
+            row: Row {
+                values: vec![Value::Integer(1)],
+            },
+        }
+    }
+
+    #[test]
+    fn test_snapshot_visibility() {
+        let mut txns = TransactionManager::new(0);
+        let writer = txns.begin(); // txn 1
+        let reader = txns.begin(); // txn 2, sees txn 1 as running
+
+        let uncommitted = version(writer.txn_id, None);
+        assert!(writer.is_visible(&uncommitted));
+        assert!(!reader.is_visible(&uncommitted));
+
+        // Even after the writer commits, the reader's snapshot doesn't change
+        txns.finish(writer.txn_id);
+        assert!(!reader.is_visible(&uncommitted));
+        assert!(txns.begin().is_visible(&uncommitted));
+
+        // A deletion by a transaction the reader can't see yet is ignored
+        let deleted_later = version(0, Some(writer.txn_id));
+        assert!(reader.is_visible(&deleted_later));
+        assert!(!reader.is_visible(&version(0, Some(reader.txn_id))));
+    }
+
+    #[test]
+    fn test_vacuum_horizon_follows_oldest_snapshot() {
+        let mut txns = TransactionManager::new(10);
+        assert_eq!(txns.vacuum_horizon(), 11);
+
+        let old = txns.begin(); // 11
+        let newer = txns.begin(); // 12, still thinks 11 is running
+        txns.finish(old.txn_id);
+        assert_eq!(txns.vacuum_horizon(), 11);
+
+        txns.finish(newer.txn_id);
+        assert_eq!(txns.vacuum_horizon(), 13);
+        assert!(!txns.has_active());
+    }
+}
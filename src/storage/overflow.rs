@@ -0,0 +1,232 @@
+// Overflow pages ("TOAST")
+// A row has to fit in one page, but a TEXT, BLOB or JSON value can be much bigger than that.
+// Like PostgreSQL's TOAST ("The Oversized-Attribute Storage Technique"), we
+// move large values out of the row into a chain of overflow pages, and leave
+// a small pointer behind in the row.
+//
+// Overflow pages live in their own file next to the table's page file
+// (<table>.toast). Each one holds a piece of a value and the number of the
+// page holding the next piece:
+//
+//   +-----------------+---------------+---------------------------+
+//   | next page (u32) | length (u16)  | data ...                  |
+//   +-----------------+---------------+---------------------------+
+//
+// Overflow pages are never changed while a row points to them: updating a
+// value writes a new chain and frees the old one. A new chain is written
+// straight to disk, before the row pointing to it. Freed pages are only reused
+// after the next checkpoint - until then, the row that pointed to them may
+// still be on disk.
+
+use super::buffer::{BufferPool, FileId};
+use super::disk::PAGE_SIZE;
+use anyhow::{anyhow, Result};
+use std::collections::BTreeSet;
+
+/// Size of the overflow page header: next page (u32) + data length (u16)
+const HEADER_SIZE: usize = 6;
+
+/// How many bytes of a value one overflow page holds
+const DATA_PER_PAGE: usize = PAGE_SIZE - HEADER_SIZE;
+
+/// Marks the last page of a chain
+const END_OF_CHAIN: u32 = u32::MAX;
+
+/// Stores large values in chains of overflow pages
+/// The pages live in a file registered with the buffer pool
+#[derive(Debug)]
+pub struct OverflowStore {
+    /// The overflow file
+    file: FileId,
+    /// Pages that don't belong to any chain and can be reused
+    free: BTreeSet<u32>,
+    /// Pages freed since the last checkpoint (see checkpoint())
+    released: Vec<u32>,
+}
+
+impl OverflowStore {
+    /// Keep overflow pages in a file registered with the pool
+    /// Every page starts out as free; call keep_only() once the table knows
+    /// which chains its rows point to
+    pub fn new(pool: &BufferPool, file: FileId) -> Self {
+        Self {
+            file,
+            free: (0..pool.num_pages(file) as u32).collect(),
+            released: Vec::new(),
+        }
+    }
+
+    /// Write a value into a new chain of pages
+    /// Returns the first page of the chain
+    pub fn write(&mut self, pool: &mut BufferPool, bytes: &[u8]) -> Result<u32> {
+        let chunks: Vec<&[u8]> = bytes.chunks(DATA_PER_PAGE).collect();
+
+        // Free pages first, then new pages at the end of the file
+        let mut next_new = pool.num_pages(self.file) as u32;
+        let page_ids: Vec<u32> = chunks
+            .iter()
+            .map(|_| {
+                self.free.pop_first().unwrap_or_else(|| {
+                    next_new += 1;
+                    next_new - 1
+                })
+            })
+            .collect();
+
+        // Written in page order, so new pages extend the file without gaps
+        let mut pages: Vec<(u32, Vec<u8>)> = chunks
+            .iter()
+            .enumerate()
+            .map(|(i, chunk)| {
+                let next = page_ids.get(i + 1).copied().unwrap_or(END_OF_CHAIN);
+
+                let mut page = vec![0u8; PAGE_SIZE];
+                page[0..4].copy_from_slice(&next.to_le_bytes());
+                page[4..6].copy_from_slice(&(chunk.len() as u16).to_le_bytes());
+                page[HEADER_SIZE..HEADER_SIZE + chunk.len()].copy_from_slice(chunk);
+                (page_ids[i], page)
+            })
+            .collect();
+        pages.sort_by_key(|(page_id, _)| *page_id);
+
+        for (page_id, page) in pages {
+            pool.write_through(self.file, page_id as usize, page)?;
+        }
+
+        // The row pointing at this chain is written next - make sure the
+        // chain reaches the disk first, or a crash could leave a dangling pointer
+        pool.flush(self.file)?;
+
+        page_ids
+            .first()
+            .copied()
+            .ok_or_else(|| anyhow!("Cannot store an empty value in overflow pages"))
+    }
+
+    /// Read back a value of len bytes starting at first_page
+    pub fn read(&self, pool: &mut BufferPool, first_page: u32, len: usize) -> Result<Vec<u8>> {
+        let mut bytes = Vec::with_capacity(len);
+
+        for page_id in self.chain(pool, first_page)? {
+            let page = pool.read(self.file, page_id as usize)?;
+            let chunk_len = u16::from_le_bytes([page[4], page[5]]) as usize;
+            bytes.extend_from_slice(&page[HEADER_SIZE..HEADER_SIZE + chunk_len]);
+        }
+
+        if bytes.len() != len {
+            return Err(anyhow!(
+                "Overflow chain at page {} holds {} bytes, expected {}",
+                first_page,
+                bytes.len(),
+                len
+            ));
+        }
+        Ok(bytes)
+    }
+
+    /// Give the pages of a chain back for reuse
+    pub fn free(&mut self, pool: &mut BufferPool, first_page: u32) -> Result<()> {
+        let chain = self.chain(pool, first_page)?;
+        if pool.is_persistent(self.file) {
+            self.released.extend(chain);
+        } else {
+            self.free.extend(chain);
+        }
+        Ok(())
+    }
+
+    /// Mark every page outside the given chains as free
+    /// Pages can be left over by a crash between writing a chain and the row
+    /// that points at it; this is how they are reclaimed
+    pub fn keep_only(&mut self, pool: &mut BufferPool, first_pages: &[u32]) -> Result<()> {
+        self.free = (0..pool.num_pages(self.file) as u32).collect();
+        self.released.clear();
+        for &first_page in first_pages {
+            for page_id in self.chain(pool, first_page)? {
+                self.free.remove(&page_id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Called once the rows are safely on disk: the pages freed since the
+    /// last checkpoint can no longer be reached from them and become reusable
+    pub fn checkpoint(&mut self, pool: &mut BufferPool) -> Result<()> {
+        pool.flush(self.file)?;
+        self.free.extend(self.released.drain(..));
+        Ok(())
+    }
+
+    /// Get the number of pages in use
+    pub fn used_pages(&self, pool: &BufferPool) -> usize {
+        pool.num_pages(self.file) - self.free.len() - self.released.len()
+    }
+
+    /// The page IDs of a chain, in order
+    fn chain(&self, pool: &mut BufferPool, first_page: u32) -> Result<Vec<u32>> {
+        let num_pages = pool.num_pages(self.file);
+        let mut page_ids = Vec::new();
+        let mut page_id = first_page;
+
+        while page_id != END_OF_CHAIN {
+            if page_id as usize >= num_pages {
+                return Err(anyhow!("Overflow page {} does not exist", page_id));
+            }
+            // A chain can't be longer than the file - anything else is a loop
+            if page_ids.len() >= num_pages {
+                return Err(anyhow!(
+                    "Overflow chain at page {} is corrupted",
+                    first_page
+                ));
+            }
+
+            let page = pool.read(self.file, page_id as usize)?;
+            page_ids.push(page_id);
+            page_id = u32::from_le_bytes([page[0], page[1], page[2], page[3]]);
+        }
+
+        Ok(page_ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::buffer::BufferPoolConfig;
+    use crate::storage::disk::DiskManager;
+
+    #[test]
+    fn test_overflow_chains_roundtrip_and_reuse() {
+        let path = std::env::temp_dir().join(format!("rustydb-overflow-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let open = |pool: &mut BufferPool| {
+            let file = pool.register(Some(DiskManager::open(&path).unwrap()));
+            (file, OverflowStore::new(pool, file))
+        };
+
+        let big: Vec<u8> = (0..3 * PAGE_SIZE).map(|i| (i % 251) as u8).collect();
+        let mut pool = BufferPool::new(BufferPoolConfig::default());
+        let (_, mut store) = open(&mut pool);
+        let first = store.write(&mut pool, &big).unwrap();
+        let small = store.write(&mut pool, b"hello").unwrap();
+        assert_eq!(store.used_pages(&pool), 5);
+        assert_eq!(store.read(&mut pool, first, big.len()).unwrap(), big);
+        assert!(store.read(&mut pool, first, 10).is_err());
+
+        // Freed pages are only reused after a checkpoint
+        store.free(&mut pool, first).unwrap();
+        assert_eq!(store.used_pages(&pool), 1);
+        assert_ne!(store.write(&mut pool, b"again").unwrap(), first);
+        store.checkpoint(&mut pool).unwrap();
+        assert_eq!(store.write(&mut pool, b"again").unwrap(), first);
+
+        // After reopening, only the chains still referenced are kept
+        let mut pool = BufferPool::new(BufferPoolConfig::default());
+        let (_, mut store) = open(&mut pool);
+        store.keep_only(&mut pool, &[small]).unwrap();
+        assert_eq!(store.used_pages(&pool), 1);
+        assert_eq!(store.read(&mut pool, small, 5).unwrap(), b"hello");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -4,158 +4,1061 @@
 // 1. Disks read/write in blocks (usually 4KB or 8KB)
 // 2. It's more efficient to read/write multiple rows at once
 // 3. Pages can be cached in memory for faster access
+//
+// Each page is a "slotted page" of exactly PAGE_SIZE bytes:
+//
+//   +--------+--------+--------+-----> free space <-----+---------+---------+
+//   | header | slot 0 | slot 1 |                        | tuple 1 | tuple 0 |
+//   +--------+--------+--------+------------------------+---------+---------+
+//
+// The slot directory grows from the front, the tuples (encoded rows, see
+// tuple.rs) grow from the back, and the free space sits in the middle.
+// Each slot holds the offset and length of its tuple, so tuples can be moved
+// around inside the page (compaction) without changing their slot number.
+//
+// A row must fit in one page. Rows with large TEXT, BLOB or JSON values still do,
+// because those values are moved to overflow pages (see overflow.rs) and the
+// tuple only keeps a pointer to them.
 
-use super::Row;
+use super::buffer::{self, BufferPool, FileId, SharedBufferPool};
+use super::disk::{DiskManager, PAGE_SIZE};
+use super::mvcc::{RowVersion, TxnId, VersionHeader};
+use super::overflow::OverflowStore;
+use super::tuple::{self, OverflowRef, OVERFLOW_REF_SIZE};
+use super::wal::Lsn;
+use super::Value;
+use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Identifies where a row version is stored: which page, and which slot in it
+/// ("TID" in PostgreSQL, "RID" in many textbooks)
+///
+/// Rows never move to another slot: removing a row leaves an empty slot
+/// behind instead of shifting its neighbours, and compacting a page only moves
+/// tuple bytes, so a record ID stays valid until the row version it points to
+/// is removed. Indexes store record IDs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct RecordId {
+    pub page_id: usize,
+    pub slot: usize,
+}
+
+impl RecordId {
+    pub fn new(page_id: usize, slot: usize) -> Self {
+        Self { page_id, slot }
+    }
+}
+
+impl fmt::Display for RecordId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {})", self.page_id, self.slot)
+    }
+}
+
+/// Size of the page header: slot count (u16) + start of the tuple area (u16)
+/// + LSN of the last logged change applied to the page (u64)
+const HEADER_SIZE: usize = 12;
+
+/// Size of one slot directory entry: tuple offset (u16) + tuple length (u16)
+/// An entry with length 0 is an empty slot, ready to be reused
+const SLOT_SIZE: usize = 4;
+
+/// The largest tuple that fits in an empty page
+pub const MAX_TUPLE_SIZE: usize = PAGE_SIZE - HEADER_SIZE - SLOT_SIZE;
+
+/// Rows larger than this have their biggest TEXT, BLOB and JSON values moved to
+/// overflow pages, so that a page still holds at least four rows (PostgreSQL uses the
+/// same threshold)
+pub const TOAST_THRESHOLD: usize = PAGE_SIZE / 4;
 
 /// A page is a fixed-size block that stores multiple rows
-/// This is a simplified version - real databases have complex page formats
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Page {
-    /// The page ID (unique identifier)
+/// The rows are kept in their encoded form, exactly as they are laid out on disk
+///
+/// A page is a view over PAGE_SIZE bytes: either its own (Page::new), or bytes
+/// held elsewhere, like a frame of the buffer pool (Page::wrap)
+#[derive(Debug, Clone)]
+pub struct Page<B = Vec<u8>> {
+    /// The page ID (its position in the page file)
     pub id: usize,
-    /// The rows stored in this page
-    /// Vec<T> is Rust's growable array (like ArrayList in Java)
-    pub rows: Vec<Row>,
-    /// Maximum number of rows per page (simplified - real DBs use byte size)
-    pub max_rows: usize,
+    /// The raw page bytes (always PAGE_SIZE long)
+    data: B,
 }
 
 impl Page {
-    /// Create a new page with a given ID
-    pub fn new(id: usize, max_rows: usize) -> Self {
-        Self {
+    /// Create a new empty page with a given ID
+    pub fn new(id: usize) -> Self {
+        let mut page = Self {
             id,
-            rows: Vec::new(),
-            max_rows,
+            data: vec![0; PAGE_SIZE],
+        };
+        page.set_free_end(PAGE_SIZE);
+        page
+    }
+
+    /// Load a page from the bytes read from disk, checking that its header
+    /// and slot directory make sense
+    pub fn from_bytes(id: usize, bytes: &[u8]) -> Result<Self> {
+        let page = Self {
+            id,
+            data: bytes.to_vec(),
+        };
+        page.check()?;
+        Ok(page)
+    }
+}
+
+impl<B: AsRef<[u8]>> Page<B> {
+    /// Look at page bytes held elsewhere as a slotted page
+    pub fn wrap(id: usize, data: B) -> Self {
+        Self { id, data }
+    }
+
+    /// Check that the header and slot directory make sense
+    pub fn check(&self) -> Result<()> {
+        if self.data.as_ref().len() != PAGE_SIZE {
+            return Err(anyhow!(
+                "Page {} is {} bytes, expected {}",
+                self.id,
+                self.data.as_ref().len(),
+                PAGE_SIZE
+            ));
         }
+
+        let directory_end = HEADER_SIZE + self.slot_count() * SLOT_SIZE;
+        if self.free_end() < directory_end || self.free_end() > PAGE_SIZE {
+            return Err(anyhow!("Page {} has a corrupted header", self.id));
+        }
+        for slot in 0..self.slot_count() {
+            let (offset, len) = self.slot(slot);
+            if len > 0 && (offset < self.free_end() || offset + len > PAGE_SIZE) {
+                return Err(anyhow!("Page {} has a corrupted slot {}", self.id, slot));
+            }
+        }
+        Ok(())
+    }
+
+    /// The raw bytes to write to disk
+    pub fn as_bytes(&self) -> &[u8] {
+        self.data.as_ref()
     }
 
-    /// Insert a row into the page
-    /// Returns true if successful, false if page is full
-    pub fn insert(&mut self, row: Row) -> bool {
-        if self.is_full() {
-            return false;
+    /// Get the tuple stored in a slot
+    pub fn get(&self, slot: usize) -> Option<&[u8]> {
+        if slot >= self.slot_count() {
+            return None;
         }
 
-        self.rows.push(row);
-        true
+        let (offset, len) = self.slot(slot);
+        (len > 0).then(|| &self.data.as_ref()[offset..offset + len])
     }
 
-    /// Check if the page is full
-    pub fn is_full(&self) -> bool {
-        self.rows.len() >= self.max_rows
+    /// Copy out every tuple, with its slot
+    pub fn tuples(&self) -> Vec<(usize, Vec<u8>)> {
+        (0..self.slot_count())
+            .filter_map(|slot| Some((slot, self.get(slot)?.to_vec())))
+            .collect()
     }
 
-    /// Get a row by index within this page
-    pub fn get(&self, index: usize) -> Option<&Row> {
-        self.rows.get(index)
+    /// Check whether a tuple of the given length could be stored in a slot
+    /// (replacing whatever is there), compacting the page if needed
+    pub fn can_put(&self, slot: usize, len: usize) -> bool {
+        let new_slots = (slot + 1).saturating_sub(self.slot_count());
+        let old_len = self.get(slot).map_or(0, |tuple| tuple.len());
+
+        len + new_slots * SLOT_SIZE <= self.free_space() + old_len
     }
 
-    /// Get a mutable reference to a row
-    /// 'mut' allows modifying the row (used for UPDATE operations)
-    pub fn get_mut(&mut self, index: usize) -> Option<&mut Row> {
-        self.rows.get_mut(index)
+    /// The slot the next inserted tuple would go into:
+    /// the first empty slot, or a new one at the end of the directory
+    pub fn free_slot(&self) -> usize {
+        (0..self.slot_count())
+            .find(|&slot| self.slot(slot).1 == 0)
+            .unwrap_or(self.slot_count())
     }
 
-    /// Delete a row by index
-    /// Returns the deleted row if successful
-    pub fn delete(&mut self, index: usize) -> Option<Row> {
-        if index < self.rows.len() {
-            Some(self.rows.remove(index))
-        } else {
-            None
-        }
+    /// Bytes available for new tuples and slots once the page is compacted
+    pub fn free_space(&self) -> usize {
+        let used: usize = (0..self.slot_count()).map(|slot| self.slot(slot).1).sum();
+        PAGE_SIZE - HEADER_SIZE - self.slot_count() * SLOT_SIZE - used
     }
 
-    /// Get the number of rows in this page
+    /// Get the number of live rows in this page
     pub fn len(&self) -> usize {
-        self.rows.len()
+        (0..self.slot_count())
+            .filter(|&slot| self.slot(slot).1 > 0)
+            .count()
     }
 
-    /// Check if the page is empty
+    /// Check if the page has no live rows
     pub fn is_empty(&self) -> bool {
-        self.rows.is_empty()
+        self.len() == 0
+    }
+
+    /// The LSN of the last logged change applied to this page
+    /// Recovery skips log records the page has already seen
+    pub fn lsn(&self) -> Lsn {
+        u64::from_le_bytes(
+            self.data.as_ref()[4..12]
+                .try_into()
+                .expect("header is 12 bytes"),
+        )
+    }
+
+    /// Get the number of slots in the directory (used and empty)
+    pub fn slot_count(&self) -> usize {
+        self.read_u16(0)
+    }
+
+    /// Where the tuple area starts (tuples grow down from the end of the page)
+    fn free_end(&self) -> usize {
+        self.read_u16(2)
+    }
+
+    /// Bytes between the end of the slot directory and the first tuple
+    fn contiguous_free_space(&self) -> usize {
+        self.free_end() - HEADER_SIZE - self.slot_count() * SLOT_SIZE
+    }
+
+    /// Read a slot directory entry: (offset, length)
+    fn slot(&self, slot: usize) -> (usize, usize) {
+        let pos = HEADER_SIZE + slot * SLOT_SIZE;
+        (self.read_u16(pos), self.read_u16(pos + 2))
+    }
+
+    fn read_u16(&self, pos: usize) -> usize {
+        let data = self.data.as_ref();
+        u16::from_le_bytes([data[pos], data[pos + 1]]) as usize
+    }
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> Page<B> {
+    /// Get the tuple stored in a slot, to change it in place
+    pub fn get_mut(&mut self, slot: usize) -> Option<&mut [u8]> {
+        if slot >= self.slot_count() {
+            return None;
+        }
+
+        let (offset, len) = self.slot(slot);
+        (len > 0).then(|| &mut self.data.as_mut()[offset..offset + len])
+    }
+
+    /// Store a tuple in a slot, replacing the tuple that was there
+    /// Slots past the end of the directory are created (empty) as needed
+    /// Returns the tuple that was there before, if any
+    pub fn put(&mut self, slot: usize, tuple: &[u8]) -> Result<Option<Vec<u8>>> {
+        if tuple.is_empty() {
+            return Err(anyhow!("Cannot store an empty tuple"));
+        }
+        if !self.can_put(slot, tuple.len()) {
+            return Err(anyhow!(
+                "Page {} has no room for a {} byte row",
+                self.id,
+                tuple.len()
+            ));
+        }
+
+        let previous = self.get(slot).map(|old| old.to_vec());
+        if slot < self.slot_count() {
+            self.set_slot(slot, 0, 0);
+        }
+
+        // Make sure the new slots and the tuple fit in the gap in the middle
+        let new_slots = (slot + 1).saturating_sub(self.slot_count());
+        if self.contiguous_free_space() < new_slots * SLOT_SIZE + tuple.len() {
+            self.compact();
+        }
+
+        while self.slot_count() <= slot {
+            let count = self.slot_count();
+            self.set_slot_count(count + 1);
+            self.set_slot(count, 0, 0);
+        }
+
+        let offset = self.free_end() - tuple.len();
+        self.data.as_mut()[offset..offset + tuple.len()].copy_from_slice(tuple);
+        self.set_free_end(offset);
+        self.set_slot(slot, offset, tuple.len());
+
+        Ok(previous)
+    }
+
+    /// Insert a tuple into the first free slot
+    /// Returns the slot, or None if the page is full
+    pub fn insert(&mut self, tuple: &[u8]) -> Option<usize> {
+        let slot = self.free_slot();
+        self.put(slot, tuple).ok()?;
+        Some(slot)
+    }
+
+    /// Delete the tuple in a slot, leaving the slot empty
+    /// Returns the deleted tuple if there was one
+    pub fn delete(&mut self, slot: usize) -> Option<Vec<u8>> {
+        let tuple = self.get(slot)?.to_vec();
+        self.set_slot(slot, 0, 0);
+
+        // Empty slots at the end of the directory can simply go away
+        while self.slot_count() > 0 && self.slot(self.slot_count() - 1).1 == 0 {
+            self.set_slot_count(self.slot_count() - 1);
+        }
+
+        Some(tuple)
+    }
+
+    /// Move all tuples to the back of the page, so the space freed by deleted
+    /// or replaced tuples becomes one contiguous gap again
+    /// Slot numbers don't change, only the offsets stored in the slots
+    pub fn compact(&mut self) {
+        let mut free_end = PAGE_SIZE;
+        for (slot, tuple) in self.tuples() {
+            free_end -= tuple.len();
+            self.data.as_mut()[free_end..free_end + tuple.len()].copy_from_slice(&tuple);
+            self.set_slot(slot, free_end, tuple.len());
+        }
+        self.set_free_end(free_end);
+    }
+
+    pub fn set_lsn(&mut self, lsn: Lsn) {
+        self.data.as_mut()[4..12].copy_from_slice(&lsn.to_le_bytes());
+    }
+
+    fn set_slot_count(&mut self, count: usize) {
+        self.write_u16(0, count);
+    }
+
+    fn set_free_end(&mut self, offset: usize) {
+        self.write_u16(2, offset);
+    }
+
+    fn set_slot(&mut self, slot: usize, offset: usize, len: usize) {
+        let pos = HEADER_SIZE + slot * SLOT_SIZE;
+        self.write_u16(pos, offset);
+        self.write_u16(pos + 2, len);
+    }
+
+    fn write_u16(&mut self, pos: usize, value: usize) {
+        self.data.as_mut()[pos..pos + 2].copy_from_slice(&(value as u16).to_le_bytes());
     }
 }
 
 /// Page Manager - handles multiple pages
-/// In a real database, this would also handle:
-/// - Writing pages to disk
-/// - Caching frequently accessed pages
-/// - Managing free space
+///
+/// A page manager is either purely in-memory (PageManager::new) or backed by a
+/// page file (PageManager::open). Either way its pages live in a buffer pool
+/// (see buffer.rs). Persistent tables share the database's pool, which keeps
+/// the pages in use in memory and writes changed pages back to the file when
+/// they are evicted or at a checkpoint.
 #[derive(Debug)]
 pub struct PageManager {
-    /// All pages in memory (in a real DB, these would be on disk)
-    pages: Vec<Page>,
-    /// Maximum rows per page
-    max_rows_per_page: usize,
+    /// The buffer pool caching the pages
+    pool: SharedBufferPool,
+    /// The page file, as registered with the pool
+    file: FileId,
+    /// Free-space map: how many bytes each page can still take
+    /// Lets an insert find a page with room without looking inside every page
+    free_space: Vec<usize>,
+    /// The highest LSN stamped on any page
+    max_lsn: Lsn,
+    /// Large values that didn't fit in their rows
+    overflow: OverflowStore,
 }
 
 impl PageManager {
-    /// Create a new page manager
-    pub fn new(max_rows_per_page: usize) -> Self {
+    /// Create a new in-memory page manager, with a pool of its own
+    pub fn new() -> Self {
+        let mut pool = BufferPool::unbounded();
+        let file = pool.register(None);
+        let overflow_file = pool.register(None);
+        let overflow = OverflowStore::new(&pool, overflow_file);
+
         Self {
-            pages: Vec::new(),
-            max_rows_per_page,
+            pool: Arc::new(Mutex::new(pool)),
+            file,
+            free_space: Vec::new(),
+            max_lsn: 0,
+            overflow,
         }
     }
 
+    /// Open a page manager backed by a page file, caching its pages in the
+    /// given buffer pool
+    /// Overflow pages are kept next to it, in a file with the extension .toast
+    pub fn open(path: &Path, pool: SharedBufferPool) -> Result<Self> {
+        let disk = DiskManager::open(path)?;
+        let overflow_disk = DiskManager::open(&path.with_extension("toast"))?;
+
+        let (file, free_space, max_lsn, overflow) = {
+            let mut pool = buffer::lock(&pool)?;
+            let file = pool.register(Some(disk));
+            let overflow_file = pool.register(Some(overflow_disk));
+            let mut overflow = OverflowStore::new(&pool, overflow_file);
+
+            // Read every page once: to fill the free-space map, and to walk
+            // every tuple, so a corrupted page or tuple layout shows up here
+            // and not in the middle of a query. The values themselves (and
+            // their overflow pages) are only decoded when a row is read.
+            let mut free_space = Vec::new();
+            let mut max_lsn = 0;
+            let mut chains = Vec::new();
+            for page_id in 0..pool.num_pages(file) {
+                let tuples = {
+                    let page = Page::wrap(page_id, pool.read(file, page_id)?);
+                    page.check()?;
+                    free_space.push(page.free_space());
+                    max_lsn = max_lsn.max(page.lsn());
+                    page.tuples()
+                };
+
+                for (slot, mut bytes) in tuples {
+                    let refs = tuple::overflow_refs(&bytes).map_err(|e| {
+                        anyhow!("Row {} is corrupted: {}", RecordId::new(page_id, slot), e)
+                    })?;
+                    chains.extend(refs.iter().map(|p| p.first_page));
+
+                    // Rows written before floats were f64 get upgraded on the spot
+                    if tuple::migrate_floats(&mut bytes)? {
+                        let mut page = Page::wrap(page_id, pool.write(file, page_id)?);
+                        if let Some(tuple) = page.get_mut(slot) {
+                            tuple.copy_from_slice(&bytes);
+                        }
+                    }
+                }
+            }
+            overflow.keep_only(&mut pool, &chains)?;
+
+            (file, free_space, max_lsn, overflow)
+        };
+
+        Ok(Self {
+            pool,
+            file,
+            free_space,
+            max_lsn,
+            overflow,
+        })
+    }
+
     /// Insert a row, creating new pages as needed
-    /// Returns (page_id, row_index_in_page)
-    pub fn insert(&mut self, row: Row) -> (usize, usize) {
-        // Try to find a page with space
-        for page in &mut self.pages {
-            if !page.is_full() {
-                let row_index = page.rows.len();
-                page.insert(row);
-                return (page.id, row_index);
+    /// Returns the record ID the row was stored under
+    pub fn insert(&mut self, row: RowVersion, lsn: Lsn) -> Result<RecordId> {
+        let rid = self.next_record_id(&row)?;
+        self.put(rid, row, lsn)?;
+        Ok(rid)
+    }
+
+    /// Work out the record ID that inserting this row would get, without inserting it
+    /// This lets the caller log the change (WAL) before applying it
+    ///
+    /// Pages are filled by bytes, not by row count: a wide row takes more
+    /// of a page than a narrow one. Empty slots are reused first, so deleting
+    /// and re-inserting rows doesn't grow the table.
+    pub fn next_record_id(&self, row: &RowVersion) -> Result<RecordId> {
+        let (_, len) = Self::overflow_columns(row)?;
+        let mut pool = self.lock()?;
+
+        // Try to find a page with space, using the free-space map
+        for (page_id, &free) in self.free_space.iter().enumerate() {
+            if free < len {
+                continue;
+            }
+            let page = Page::wrap(page_id, pool.read(self.file, page_id)?);
+            let slot = page.free_slot();
+            if page.can_put(slot, len) {
+                return Ok(RecordId::new(page_id, slot));
             }
         }
 
-        // No space found - create a new page
-        let page_id = self.pages.len();
-        let mut new_page = Page::new(page_id, self.max_rows_per_page);
-        new_page.insert(row);
-        self.pages.push(new_page);
+        // No space found - the row goes to the start of a new page
+        Ok(RecordId::new(self.free_space.len(), 0))
+    }
+
+    /// Store a row under a specific record ID, creating the page if needed
+    /// Storing over an existing row replaces it
+    /// lsn is the log record describing the change (0 when there is no log)
+    /// Returns the row that was there before, if any
+    pub fn put(&mut self, rid: RecordId, row: RowVersion, lsn: Lsn) -> Result<Option<RowVersion>> {
+        let page_id = rid.page_id;
+        let (columns, len) = Self::overflow_columns(&row)?;
+        let mut pool = buffer::lock(&self.pool)?;
 
-        (page_id, 0)
+        // New pages are added at the end of the file, so it has no gaps
+        while self.free_space.len() <= page_id {
+            let page = Page::new(self.free_space.len());
+            pool.allocate(self.file, page.as_bytes().to_vec())?;
+            self.free_space.push(page.free_space());
+        }
+
+        if !Page::wrap(page_id, pool.read(self.file, page_id)?).can_put(rid.slot, len) {
+            return Err(anyhow!("Row {} does not fit in its page", rid));
+        }
+
+        // Large values go to their overflow pages before the tuple pointing
+        // to them is written. The page stays pinned meanwhile, so writing
+        // the overflow pages can't evict it
+        pool.pin(self.file, page_id)?;
+        let pointers = Self::write_overflow(&mut self.overflow, &mut pool, &row, &columns);
+        pool.unpin(self.file, page_id);
+        let bytes = tuple::encode_with(&row, &pointers?);
+
+        let previous = {
+            let mut page = Page::wrap(page_id, pool.write(self.file, page_id)?);
+            let previous = page.put(rid.slot, &bytes)?;
+            page.set_lsn(lsn);
+            self.free_space[page_id] = page.free_space();
+            previous
+        };
+        self.max_lsn = self.max_lsn.max(lsn);
+
+        previous
+            .map(|bytes| Self::release(&mut self.overflow, &mut pool, &bytes))
+            .transpose()
     }
 
-    /// Get a row by global row ID
-    /// Row ID format: page_id * max_rows_per_page + row_index
-    pub fn get(&self, row_id: usize) -> Option<&Row> {
-        let page_id = row_id / self.max_rows_per_page;
-        let row_index = row_id % self.max_rows_per_page;
+    /// Set the xmax of the row stored under a record ID
+    /// The tuple is changed in place, so its overflow values stay where they are
+    pub fn set_xmax(&mut self, rid: RecordId, xmax: Option<TxnId>, lsn: Lsn) -> Result<()> {
+        if rid.page_id >= self.free_space.len() {
+            return Ok(());
+        }
 
-        self.pages.get(page_id)?.get(row_index)
+        let mut pool = buffer::lock(&self.pool)?;
+        let mut page = Page::wrap(rid.page_id, pool.write(self.file, rid.page_id)?);
+        let Some(bytes) = page.get_mut(rid.slot) else {
+            return Ok(());
+        };
+        tuple::set_xmax(bytes, xmax);
+        page.set_lsn(lsn);
+        self.max_lsn = self.max_lsn.max(lsn);
+        Ok(())
     }
 
-    /// Get a mutable reference to a row
-    pub fn get_mut(&mut self, row_id: usize) -> Option<&mut Row> {
-        let page_id = row_id / self.max_rows_per_page;
-        let row_index = row_id % self.max_rows_per_page;
+    /// Replace the row stored under a record ID
+    pub fn update(&mut self, rid: RecordId, row: RowVersion, lsn: Lsn) -> Result<()> {
+        if self.get(rid)?.is_none() {
+            return Err(anyhow!("Row {} does not exist", rid));
+        }
 
-        self.pages.get_mut(page_id)?.get_mut(row_index)
+        self.put(rid, row, lsn)?;
+        Ok(())
     }
 
-    /// Get all rows (for table scans)
-    /// Returns an iterator over all rows with their row IDs
-    pub fn scan(&self) -> Vec<(usize, &Row)> {
-        let mut results = Vec::new();
+    /// Remove the row stored under a record ID, leaving an empty slot behind
+    /// Returns the removed row, if there was one
+    pub fn remove(&mut self, rid: RecordId, lsn: Lsn) -> Result<Option<RowVersion>> {
+        if rid.page_id >= self.free_space.len() {
+            return Ok(None);
+        }
 
-        for page in &self.pages {
-            for (row_index, row) in page.rows.iter().enumerate() {
-                let row_id = page.id * self.max_rows_per_page + row_index;
-                results.push((row_id, row));
-            }
+        let mut pool = buffer::lock(&self.pool)?;
+        let bytes = {
+            let mut page = Page::wrap(rid.page_id, pool.write(self.file, rid.page_id)?);
+            let Some(bytes) = page.delete(rid.slot) else {
+                return Ok(None);
+            };
+            page.set_lsn(lsn);
+            self.free_space[rid.page_id] = page.free_space();
+            bytes
+        };
+        self.max_lsn = self.max_lsn.max(lsn);
+
+        Ok(Some(Self::release(&mut self.overflow, &mut pool, &bytes)?))
+    }
+
+    /// Get a row by record ID
+    /// Values stored in overflow pages are read back into the row
+    pub fn get(&self, rid: RecordId) -> Result<Option<RowVersion>> {
+        if rid.page_id >= self.free_space.len() {
+            return Ok(None);
         }
 
-        results
+        let mut pool = self.lock()?;
+        let page = Page::wrap(rid.page_id, pool.read(self.file, rid.page_id)?);
+        let Some(bytes) = page.get(rid.slot).map(<[u8]>::to_vec) else {
+            return Ok(None);
+        };
+        Self::decode(&self.overflow, &mut pool, &bytes).map(Some)
+    }
+
+    /// Get the xmin and xmax of the row stored under a record ID, without
+    /// decoding its values
+    pub fn header(&self, rid: RecordId) -> Result<Option<VersionHeader>> {
+        if rid.page_id >= self.free_space.len() {
+            return Ok(None);
+        }
+
+        let mut pool = self.lock()?;
+        let page = Page::wrap(rid.page_id, pool.read(self.file, rid.page_id)?);
+        page.get(rid.slot).map(tuple::decode_header).transpose()
+    }
+
+    /// Walk every row version (for table scans), yielding its record ID and
+    /// header. Pages are read one at a time through the buffer pool, and no
+    /// values are decoded: call get() for the rows you actually need.
+    pub fn scan(&self) -> Scan<'_> {
+        Scan {
+            pages: self,
+            next_page: 0,
+            current: Vec::new().into_iter(),
+        }
+    }
+
+    /// The record IDs and headers of the row versions in one page
+    /// Empty slots left by removed rows are skipped
+    fn page_headers(&self, page_id: usize) -> Result<Vec<(RecordId, VersionHeader)>> {
+        let mut pool = self.lock()?;
+        let page = Page::wrap(page_id, pool.read(self.file, page_id)?);
+        (0..page.slot_count())
+            .filter_map(|slot| Some((slot, page.get(slot)?)))
+            .map(|(slot, bytes)| Ok((RecordId::new(page_id, slot), tuple::decode_header(bytes)?)))
+            .collect()
     }
 
     /// Get the total number of rows across all pages
-    pub fn total_rows(&self) -> usize {
-        self.pages.iter().map(|p| p.len()).sum()
+    pub fn total_rows(&self) -> Result<usize> {
+        let mut pool = self.lock()?;
+        let mut total = 0;
+        for page_id in 0..self.free_space.len() {
+            total += Page::wrap(page_id, pool.read(self.file, page_id)?).len();
+        }
+        Ok(total)
+    }
+
+    /// Get the number of pages
+    pub fn page_count(&self) -> usize {
+        self.free_space.len()
+    }
+
+    /// Get the number of overflow pages holding large values
+    pub fn overflow_page_count(&self) -> Result<usize> {
+        let pool = self.lock()?;
+        Ok(self.overflow.used_pages(&pool))
+    }
+
+    /// The LSN of the last logged change applied to a page (0 if the page doesn't exist)
+    pub fn page_lsn(&self, page_id: usize) -> Result<Lsn> {
+        if page_id >= self.free_space.len() {
+            return Ok(0);
+        }
+        let mut pool = self.lock()?;
+        Ok(Page::wrap(page_id, pool.read(self.file, page_id)?).lsn())
+    }
+
+    /// The highest LSN of any page
+    pub fn max_lsn(&self) -> Lsn {
+        self.max_lsn
+    }
+
+    /// Write every changed page back and force the pages to stable storage
+    pub fn sync(&mut self) -> Result<()> {
+        let mut pool = buffer::lock(&self.pool)?;
+        pool.flush(self.file)?;
+        self.overflow.checkpoint(&mut pool)
+    }
+
+    /// Lock the buffer pool for the duration of one operation
+    fn lock(&self) -> Result<MutexGuard<'_, BufferPool>> {
+        buffer::lock(&self.pool)
+    }
+
+    /// Decide which TEXT, BLOB and JSON values of a row go to overflow pages: the largest
+    /// ones, until the rest of the row fits in TOAST_THRESHOLD bytes
+    /// Returns those columns and the size of the tuple that is left
+    fn overflow_columns(row: &RowVersion) -> Result<(Vec<usize>, usize)> {
+        let mut len = tuple::encode(row).len();
+
+        let mut texts: Vec<(usize, usize)> = row
+            .row
+            .values
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| matches!(value, Value::Text(_) | Value::Bytes(_) | Value::Json(_)))
+            .map(|(column, value)| (column, tuple::encoded_size(value)))
+            .collect();
+        texts.sort_by_key(|&(column, size)| (Reverse(size), column));
+
+        let mut columns = Vec::new();
+        for (column, size) in texts {
+            if len <= TOAST_THRESHOLD || size <= OVERFLOW_REF_SIZE {
+                break;
+            }
+            len -= size - OVERFLOW_REF_SIZE;
+            columns.push(column);
+        }
+
+        if len > MAX_TUPLE_SIZE {
+            return Err(anyhow!(
+                "Row is too large to fit in a {} byte page",
+                PAGE_SIZE
+            ));
+        }
+        Ok((columns, len))
+    }
+
+    /// Write the given columns of a row to overflow pages
+    fn write_overflow(
+        overflow: &mut OverflowStore,
+        pool: &mut BufferPool,
+        row: &RowVersion,
+        columns: &[usize],
+    ) -> Result<HashMap<usize, OverflowRef>> {
+        let mut pointers = HashMap::new();
+        for &column in columns {
+            let bytes = match &row.row.values[column] {
+                Value::Text(text) => Cow::Borrowed(text.as_bytes()),
+                Value::Bytes(bytes) => Cow::Borrowed(bytes.as_slice()),
+                Value::Json(doc) => Cow::Owned(doc.to_string().into_bytes()),
+                _ => continue,
+            };
+            let pointer = OverflowRef {
+                len: bytes.len() as u32,
+                first_page: overflow.write(pool, &bytes)?,
+            };
+            pointers.insert(column, pointer);
+        }
+        Ok(pointers)
+    }
+
+    /// Decode a tuple, reading its overflow values back
+    fn decode(overflow: &OverflowStore, pool: &mut BufferPool, bytes: &[u8]) -> Result<RowVersion> {
+        tuple::decode_with(bytes, &mut |pointer| {
+            overflow.read(pool, pointer.first_page, pointer.len as usize)
+        })
+    }
+
+    /// Decode a tuple that is going away, and free its overflow pages
+    fn release(
+        overflow: &mut OverflowStore,
+        pool: &mut BufferPool,
+        bytes: &[u8],
+    ) -> Result<RowVersion> {
+        let version = Self::decode(overflow, pool, bytes)?;
+        for pointer in tuple::overflow_refs(bytes)? {
+            overflow.free(pool, pointer.first_page)?;
+        }
+        Ok(version)
+    }
+}
+
+/// A scan over the row versions of a PageManager (see PageManager::scan)
+/// Only the headers of one page are held at a time
+pub struct Scan<'a> {
+    pages: &'a PageManager,
+    next_page: usize,
+    current: std::vec::IntoIter<(RecordId, VersionHeader)>,
+}
+
+impl Iterator for Scan<'_> {
+    type Item = Result<(RecordId, VersionHeader)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.current.next() {
+                return Some(Ok(entry));
+            }
+            if self.next_page >= self.pages.page_count() {
+                return None;
+            }
+
+            let page_id = self.next_page;
+            self.next_page += 1;
+            match self.pages.page_headers(page_id) {
+                Ok(headers) => self.current = headers.into_iter(),
+                Err(e) => {
+                    // Stop after an error
+                    self.next_page = self.pages.page_count();
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+impl Default for PageManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::buffer::BufferPoolConfig;
+    use crate::storage::{Row, Value};
+
+    fn temp_file(name: &str) -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("rustydb-page-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("toast"));
+        path
+    }
+
+    fn open(path: &Path) -> PageManager {
+        let pool = BufferPool::new(BufferPoolConfig::default());
+        PageManager::open(path, Arc::new(Mutex::new(pool))).unwrap()
+    }
+
+    fn remove_files(path: &Path) {
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(path.with_extension("toast")).unwrap();
+    }
+
+    fn text_row(text: &str) -> RowVersion {
+        RowVersion {
+            xmin: 1,
+            xmax: None,
+            row: Row {
+                values: vec![Value::Text(text.to_string())],
+            },
+        }
+    }
+
+    #[test]
+    fn test_page_manager_reloads_from_disk() {
+        let path = temp_file("reload");
+        let wide = "x".repeat(3000);
+
+        {
+            let mut pm = open(&path);
+            pm.insert(text_row("a"), 0).unwrap();
+            pm.insert(text_row(&wide), 0).unwrap();
+            pm.insert(text_row(&wide), 0).unwrap();
+            pm.update(RecordId::new(0, 0), text_row("A"), 0).unwrap();
+        }
+
+        let pm = open(&path);
+        let rows: Vec<RowVersion> = pm
+            .scan()
+            .map(|entry| pm.get(entry.unwrap().0).unwrap().unwrap())
+            .collect();
+        assert_eq!(rows, vec![text_row("A"), text_row(&wide), text_row(&wide)]);
+        assert_eq!(pm.get(RecordId::new(0, 2)).unwrap(), Some(text_row(&wide)));
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), PAGE_SIZE as u64);
+
+        remove_files(&path);
+    }
+
+    #[test]
+    fn test_page_manager_splits_pages_by_byte_size() {
+        let path = temp_file("bytes");
+        let mut pm = open(&path);
+
+        // Each row is ~2 KiB, so only four fit in an 8 KiB page
+        let wide = "x".repeat(2000);
+        let rids: Vec<RecordId> = (0..5)
+            .map(|_| pm.insert(text_row(&wide), 0).unwrap())
+            .collect();
+        assert_eq!(rids[3], RecordId::new(0, 3));
+        assert_eq!(rids[4], RecordId::new(1, 0));
+
+        // ... while small rows keep filling the space that is left
+        assert_eq!(
+            pm.insert(text_row("small"), 0).unwrap(),
+            RecordId::new(0, 4)
+        );
+
+        // A row that is too large even without its TEXT values is rejected
+        let integers = RowVersion {
+            row: Row {
+                values: vec![Value::Integer(0); 1000],
+            },
+            ..text_row("")
+        };
+        assert!(pm.insert(integers, 0).is_err());
+
+        remove_files(&path);
+    }
+
+    #[test]
+    fn test_page_manager_stores_large_values_in_overflow_pages() {
+        let path = temp_file("overflow");
+        let document = "lorem ipsum ".repeat(2000);
+        let row = |text: &str| RowVersion {
+            xmin: 1,
+            xmax: None,
+            row: Row {
+                values: vec![Value::Integer(1), Value::Text(text.to_string())],
+            },
+        };
+
+        let rid = {
+            let mut pm = open(&path);
+            let rid = pm.insert(row(&document), 0).unwrap();
+            assert_eq!(pm.get(rid).unwrap(), Some(row(&document)));
+            assert_eq!(pm.overflow_page_count().unwrap(), 3);
+
+            // Deleting the version keeps its value where it is
+            pm.set_xmax(rid, Some(5), 0).unwrap();
+            assert_eq!(pm.get(rid).unwrap().unwrap().xmax, Some(5));
+            assert_eq!(pm.overflow_page_count().unwrap(), 3);
+
+            // Small rows stay in their page; the old value's pages are freed
+            pm.update(rid, row("short"), 0).unwrap();
+            assert_eq!(pm.overflow_page_count().unwrap(), 0);
+            pm.update(rid, row(&document), 0).unwrap();
+            rid
+        };
+
+        let mut pm = open(&path);
+        assert_eq!(pm.get(rid).unwrap(), Some(row(&document)));
+        assert_eq!(pm.page_count(), 1);
+        assert_eq!(pm.overflow_page_count().unwrap(), 3);
+
+        assert_eq!(pm.remove(rid, 0).unwrap(), Some(row(&document)));
+        assert_eq!(pm.overflow_page_count().unwrap(), 0);
+
+        // Freed overflow pages are reused instead of growing the file
+        // (once the removal is safely on disk)
+        pm.sync().unwrap();
+        let toast_size = || {
+            std::fs::metadata(path.with_extension("toast"))
+                .unwrap()
+                .len()
+        };
+        let size = toast_size();
+        pm.insert(row(&document), 0).unwrap();
+        pm.insert(row(&document), 0).unwrap();
+        assert_eq!(toast_size(), size);
+
+        remove_files(&path);
+    }
+
+    #[test]
+    fn test_scan_stays_within_the_buffer_pool() {
+        let path = temp_file("scan");
+        let pool = Arc::new(Mutex::new(BufferPool::new(BufferPoolConfig {
+            memory_budget: 0, // the smallest pool there is
+            ..BufferPoolConfig::default()
+        })));
+        let capacity = buffer::lock(&pool).unwrap().capacity();
+
+        // Many more pages than the pool holds (about four rows per page),
+        // each row with an overflow value
+        let row = RowVersion {
+            row: Row {
+                values: vec![
+                    Value::Text("x".repeat(1800)),
+                    Value::Text("lorem ipsum ".repeat(1000)),
+                ],
+            },
+            ..text_row("")
+        };
+        let mut rids = Vec::new();
+        {
+            let mut pm = PageManager::open(&path, Arc::clone(&pool)).unwrap();
+            for _ in 0..capacity * 16 {
+                rids.push(pm.insert(row.clone(), 0).unwrap());
+            }
+            assert!(pm.page_count() > capacity * 2);
+            pm.sync().unwrap();
+        }
+
+        // Opening and scanning read one page after the other, and never
+        // the overflow pages
+        let pm = PageManager::open(&path, Arc::clone(&pool)).unwrap();
+        let reads = || {
+            let stats = buffer::lock(&pool).unwrap().stats();
+            stats.hits + stats.misses
+        };
+        let before = reads();
+        let mut scanned = Vec::new();
+        for entry in pm.scan() {
+            let (rid, header) = entry.unwrap();
+            assert_eq!(header, row.header());
+            assert!(buffer::lock(&pool).unwrap().cached_pages() <= capacity);
+            scanned.push(rid);
+        }
+        assert_eq!(scanned, rids);
+        assert_eq!(reads() - before, pm.page_count() as u64);
+
+        // Values are decoded when a row is read
+        assert_eq!(pm.get(rids[5]).unwrap(), Some(row));
+        assert!(buffer::lock(&pool).unwrap().cached_pages() <= capacity);
+
+        remove_files(&path);
+    }
+
+    #[test]
+    fn test_page_manager_reuses_freed_slots() {
+        let mut pm = PageManager::new();
+        let wide = "x".repeat(2000);
+        let rids: Vec<RecordId> = (0..5)
+            .map(|_| pm.insert(text_row(&wide), 0).unwrap())
+            .collect();
+
+        assert_eq!(pm.remove(rids[0], 0).unwrap(), Some(text_row(&wide)));
+        assert_eq!(pm.remove(rids[0], 0).unwrap(), None);
+        assert_eq!(pm.total_rows().unwrap(), 4);
+        assert_eq!(pm.get(rids[1]).unwrap(), Some(text_row(&wide)));
+
+        // The freed slot is used before the mostly empty last page
+        assert_eq!(pm.insert(text_row(&wide), 0).unwrap(), rids[0]);
+        assert_eq!(pm.insert(text_row(&wide), 0).unwrap(), RecordId::new(1, 1));
+    }
+
+    #[test]
+    fn test_page_compaction_keeps_slots() {
+        let mut page = Page::new(0);
+        let a = page.insert(&[1; 3000]).unwrap();
+        let b = page.insert(&[2; 3000]).unwrap();
+        let c = page.insert(&[3; 1000]).unwrap();
+        assert!(page.insert(&[4; 2000]).is_none());
+
+        // Freeing a tuple in the middle leaves a hole; a new tuple bigger than
+        // the gap at the front forces the page to compact itself
+        page.delete(a).unwrap();
+        assert_eq!(page.put(a, &[5; 4000]).unwrap(), None);
+        assert_eq!(page.get(a), Some(&[5; 4000][..]));
+        assert_eq!(page.get(b), Some(&[2; 3000][..]));
+        assert_eq!(page.get(c), Some(&[3; 1000][..]));
+        assert_eq!(
+            page.free_space(),
+            PAGE_SIZE - HEADER_SIZE - 3 * SLOT_SIZE - 8000
+        );
+
+        // The page survives a trip through its byte representation
+        let copy = Page::from_bytes(0, page.as_bytes()).unwrap();
+        assert_eq!(copy.get(b), Some(&[2; 3000][..]));
+        assert!(Page::from_bytes(0, &[0xff; PAGE_SIZE]).is_err());
+    }
+
+    #[test]
+    fn test_record_ids_survive_updates_and_compaction() {
+        let path = temp_file("rids");
+        let mut pm = open(&path);
+        let rids: Vec<RecordId> = ["a", "b", "c", "d"]
+            .into_iter()
+            .map(|text| pm.insert(text_row(text), 0).unwrap())
+            .collect();
+
+        // Removing the last slots shrinks the directory; the others keep their slots
+        pm.remove(rids[3], 0).unwrap();
+        pm.remove(rids[2], 0).unwrap();
+        pm.remove(rids[0], 0).unwrap();
+        pm.update(rids[1], text_row("B"), 0).unwrap();
+        let slots = Page::wrap(0, pm.lock().unwrap().read(pm.file, 0).unwrap()).slot_count();
+        assert_eq!(slots, 2);
+
+        // Changed pages reach the file when they are flushed
+        pm.sync().unwrap();
+        let pm = open(&path);
+        assert_eq!(pm.get(rids[1]).unwrap(), Some(text_row("B")));
+        assert_eq!(pm.get(rids[0]).unwrap(), None);
+        let scanned = pm.scan().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(scanned, vec![(rids[1], text_row("B").header())]);
+
+        // Replaying a log record may recreate a slot past the end of the directory
+        let mut pm = pm;
+        pm.put(rids[3], text_row("D"), 7).unwrap();
+        assert_eq!(pm.page_lsn(0).unwrap(), 7);
+        assert_eq!(pm.get(rids[3]).unwrap(), Some(text_row("D")));
+        assert_eq!(pm.get(rids[2]).unwrap(), None);
+
+        remove_files(&path);
     }
 }
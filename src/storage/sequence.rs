@@ -0,0 +1,242 @@
+// Sequences
+// A sequence hands out numbers: 1, 2, 3, ... A SERIAL (or GENERATED ... AS
+// IDENTITY) column takes its default from a sequence of its own, and
+// CREATE SEQUENCE makes a standalone one, used with nextval('name').
+//
+// Like in PostgreSQL, sequences ignore transactions: a number is never handed
+// out twice, even if the transaction that got it rolls back. That leaves gaps,
+// but two transactions can never get the same number, and none of them has
+// to wait for another one to finish to get its number.
+//
+// Saving the sequence after every number would be slow, so numbers are
+// reserved in batches: the catalog records the last number of the current
+// batch. After a restart we continue after it, skipping whatever was left of
+// the batch (PostgreSQL does the same, 32 numbers at a time).
+
+use super::Value;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// How many numbers are reserved at a time
+const BATCH: i64 = 32;
+
+/// One sequence of numbers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sequence {
+    pub name: String,
+    /// The first number handed out
+    pub start: i64,
+    /// The step between numbers (negative to count down)
+    pub increment: i64,
+    /// The last number that may have been handed out; this is what the
+    /// catalog remembers
+    pub reserved: Option<i64>,
+    /// The last number handed out since the database was opened
+    #[serde(skip)]
+    last: Option<i64>,
+}
+
+impl Sequence {
+    /// A sequence starting at `start`, counting by `increment`
+    pub fn new(name: &str, start: i64, increment: i64) -> Result<Self> {
+        if increment == 0 {
+            return Err(anyhow!(
+                "The increment of sequence '{}' cannot be zero",
+                name
+            ));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            start,
+            increment,
+            reserved: None,
+            last: None,
+        })
+    }
+
+    /// Hand out the next number
+    /// Returns the number, and whether a new batch was reserved (so the
+    /// catalog has to be saved before the number is used)
+    fn next(&mut self) -> Result<(i64, bool)> {
+        let next = match self.last.or(self.reserved) {
+            Some(previous) => previous
+                .checked_add(self.increment)
+                .ok_or_else(|| anyhow!("Sequence '{}' has reached its limit", self.name))?,
+            None => self.start,
+        };
+        self.last = Some(next);
+
+        let in_batch = self.reserved.is_some_and(|reserved| {
+            if self.increment > 0 {
+                next <= reserved
+            } else {
+                next >= reserved
+            }
+        });
+        if in_batch {
+            return Ok((next, false));
+        }
+
+        // The end of the batch, or as far as we can go
+        let batch_end = self
+            .increment
+            .checked_mul(BATCH - 1)
+            .and_then(|step| next.checked_add(step))
+            .unwrap_or(if self.increment > 0 {
+                i64::MAX
+            } else {
+                i64::MIN
+            });
+        self.reserved = Some(batch_end);
+        Ok((next, true))
+    }
+
+    /// Make the next number handed out follow `value` (setval)
+    fn set(&mut self, value: i64) {
+        // The next call reserves a new batch starting after the value, so a
+        // restart continues after it too
+        self.last = Some(value);
+        self.reserved = Some(value);
+    }
+}
+
+/// The sequences of a database
+#[derive(Debug, Default)]
+pub struct Sequences {
+    sequences: BTreeMap<String, Sequence>,
+    /// Set when a sequence changed in a way the catalog has to record
+    unsaved: bool,
+}
+
+impl Sequences {
+    /// The sequences recorded in a catalog
+    pub fn load(list: Vec<Sequence>) -> Self {
+        Self {
+            sequences: list.into_iter().map(|s| (s.name.clone(), s)).collect(),
+            unsaved: false,
+        }
+    }
+
+    /// Every sequence, to be saved in the catalog (sorted by name)
+    pub fn list(&self) -> Vec<Sequence> {
+        self.sequences.values().cloned().collect()
+    }
+
+    /// Check whether a sequence exists
+    pub fn contains(&self, name: &str) -> bool {
+        self.sequences.contains_key(name)
+    }
+
+    /// Add a new sequence
+    pub fn create(&mut self, sequence: Sequence) -> Result<()> {
+        if self.contains(&sequence.name) {
+            return Err(anyhow!("Sequence '{}' already exists", sequence.name));
+        }
+        self.sequences.insert(sequence.name.clone(), sequence);
+        self.unsaved = true;
+        Ok(())
+    }
+
+    /// Hand out the next number of a sequence
+    pub fn next(&mut self, name: &str) -> Result<i64> {
+        let (value, reserved) = self.get_mut(name)?.next()?;
+        self.unsaved |= reserved;
+        Ok(value)
+    }
+
+    /// Make the next number of a sequence follow `value`
+    pub fn set(&mut self, name: &str, value: i64) -> Result<()> {
+        self.get_mut(name)?.set(value);
+        self.unsaved = true;
+        Ok(())
+    }
+
+    /// Check (and forget) whether the catalog has to be saved
+    pub fn take_unsaved(&mut self) -> bool {
+        std::mem::take(&mut self.unsaved)
+    }
+
+    fn get_mut(&mut self, name: &str) -> Result<&mut Sequence> {
+        self.sequences
+            .get_mut(name)
+            .ok_or_else(|| anyhow!("Sequence '{}' not found", name))
+    }
+}
+
+/// What the sequence functions need while a statement runs: the database's
+/// sequences, and the last number this session got from each one (currval)
+pub struct SequenceSession<'a> {
+    pub sequences: &'a mut Sequences,
+    pub last_values: &'a mut HashMap<String, i64>,
+}
+
+impl SequenceSession<'_> {
+    /// Call nextval('s'), currval('s') or setval('s', n)
+    /// Returns None if the function is not one of them
+    pub fn call(&mut self, name: &str, args: &[Value]) -> Option<Result<Value>> {
+        let name = name.to_lowercase();
+        if !matches!(name.as_str(), "nextval" | "currval" | "setval") {
+            return None;
+        }
+        Some(self.call_sequence_function(&name, args))
+    }
+
+    fn call_sequence_function(&mut self, name: &str, args: &[Value]) -> Result<Value> {
+        match (name, args) {
+            ("nextval", [Value::Text(sequence)]) => {
+                let value = self.sequences.next(sequence)?;
+                self.last_values.insert(sequence.clone(), value);
+                Ok(Value::Integer(value))
+            }
+            // The last number *this session* got, whatever others did since
+            ("currval", [Value::Text(sequence)]) => match self.last_values.get(sequence) {
+                Some(value) => Ok(Value::Integer(*value)),
+                None if !self.sequences.contains(sequence) => {
+                    Err(anyhow!("Sequence '{}' not found", sequence))
+                }
+                None => Err(anyhow!(
+                    "currval of sequence '{}' is not yet defined in this session",
+                    sequence
+                )),
+            },
+            ("setval", [Value::Text(sequence), Value::Integer(value)]) => {
+                self.sequences.set(sequence, *value)?;
+                self.last_values.insert(sequence.clone(), *value);
+                Ok(Value::Integer(*value))
+            }
+            _ => Err(anyhow!("Wrong arguments for {}()", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequence_batches() {
+        let mut sequences = Sequences::default();
+        sequences.create(Sequence::new("s", 1, 1).unwrap()).unwrap();
+        assert!(sequences.take_unsaved());
+
+        // The first number reserves a batch; the rest of the batch needs no save
+        assert_eq!(sequences.next("s").unwrap(), 1);
+        assert!(sequences.take_unsaved());
+        assert_eq!(sequences.next("s").unwrap(), 2);
+        assert!(!sequences.take_unsaved());
+
+        // After a restart we continue after the batch
+        let saved = serde_json::to_string(&sequences.list()).unwrap();
+        let mut reopened = Sequences::load(serde_json::from_str(&saved).unwrap());
+        assert_eq!(reopened.next("s").unwrap(), BATCH + 1);
+
+        // Counting down
+        sequences
+            .create(Sequence::new("down", 10, -5).unwrap())
+            .unwrap();
+        assert_eq!(sequences.next("down").unwrap(), 10);
+        assert_eq!(sequences.next("down").unwrap(), 5);
+        assert!(Sequence::new("zero", 1, 0).is_err());
+    }
+}
@@ -1,9 +1,16 @@
 // Table implementation
 // A table combines schema, data (pages), and indexes
 
-use super::{btree::BTreeIndex, page::PageManager, Row, Schema, Value};
+use super::buffer::SharedBufferPool;
+use super::expr::{self, Expr, KeyFilter, SortKey};
+use super::json::ColumnRef;
+use super::mvcc::{RowVersion, Snapshot, TxnId};
+use super::sequence::SequenceSession;
+use super::wal::{LogEntry, LogRecord, Lsn, SharedWal};
+use super::{btree::BTreeIndex, page::PageManager, page::RecordId, Row, Schema, Value};
 use anyhow::{anyhow, Result};
 use std::collections::HashMap;
+use std::path::Path;
 
 /// Represents a database table
 /// This is the main structure that holds all table data
@@ -14,36 +21,91 @@ pub struct Table {
     pub schema: Schema,
     /// Page-based storage for rows
     page_manager: PageManager,
-    /// Indexes for fast lookups
+    /// Indexes for fast lookups, by the column (or JSON path) they index
     /// HashMap is Rust's hash table - O(1) average lookup time
-    indexes: HashMap<String, BTreeIndex>,
-    /// The next row ID to assign
-    next_row_id: usize,
+    indexes: HashMap<String, TableIndex>,
+    /// Write-ahead log that every change is recorded in first (persistent tables only)
+    wal: Option<SharedWal>,
+    /// Changes made by transactions that are still running, oldest first
+    /// Rolling a transaction back means undoing its changes in reverse order
+    pending: Vec<(TxnId, LogRecord)>,
+}
+
+/// An index, and what it indexes
+struct TableIndex {
+    /// The columns (or paths into JSON columns) whose values are indexed,
+    /// each with the position of its column in the schema
+    targets: Vec<(ColumnRef, usize)>,
+    /// Whether two live rows may share a key (false for UNIQUE and PRIMARY KEY)
+    unique: bool,
+    tree: BTreeIndex,
+}
+
+impl TableIndex {
+    /// The key of a row in this index: one value per target
+    fn key(&self, row: &Row) -> Vec<Value> {
+        self.targets
+            .iter()
+            .map(|(target, column)| target.extract(&row.values[*column]))
+            .collect()
+    }
 }
 
 impl Table {
     /// Create a new table with the given name and schema
-    pub fn new(name: String, schema: Schema) -> Self {
+    pub fn new(name: String, schema: Schema) -> Result<Self> {
+        Self::with_page_manager(name, schema, PageManager::new())
+    }
+
+    /// Open a table whose rows are stored in a page file, cached in the
+    /// database's buffer pool
+    /// Existing rows are loaded and the given indexes are rebuilt from them
+    pub fn open(
+        name: String,
+        schema: Schema,
+        path: &Path,
+        pool: SharedBufferPool,
+        indexed_columns: &[String],
+    ) -> Result<Self> {
+        let page_manager = PageManager::open(path, pool)?;
+        let mut table = Self::with_page_manager(name, schema, page_manager)?;
+
+        for column_name in indexed_columns {
+            if !table.indexes.contains_key(column_name) {
+                table.create_index(column_name)?;
+            }
+        }
+
+        Ok(table)
+    }
+
+    /// Shared constructor for in-memory and persistent tables
+    fn with_page_manager(name: String, schema: Schema, page_manager: PageManager) -> Result<Self> {
         let mut table = Self {
             name,
             schema,
-            page_manager: PageManager::new(100), // 100 rows per page
+            page_manager,
             indexes: HashMap::new(),
-            next_row_id: 0,
+            wal: None,
+            pending: Vec::new(),
         };
 
-        // Automatically create an index on the primary key column
+        // Automatically create a unique index on the primary key column, and
+        // for every UNIQUE constraint
         if let Some(pk_index) = table.schema.get_primary_key_index() {
             let pk_name = table.schema.columns[pk_index].name.clone();
-            table.create_index(&pk_name);
+            table.add_index(&[pk_name], true)?;
+        }
+        for columns in table.schema.unique.clone() {
+            table.add_index(&columns, true)?;
         }
 
-        table
+        Ok(table)
     }
 
-    /// Insert a row into the table
-    /// Returns the row ID of the inserted row
-    pub fn insert(&mut self, values: Vec<Value>) -> Result<usize> {
+    /// Insert a row into the table as part of a transaction
+    /// Returns the row as stored (its values converted to the column types)
+    pub fn insert(&mut self, values: Vec<Value>, snapshot: &Snapshot) -> Result<Row> {
         // Validate the row matches the schema
         if values.len() != self.schema.columns.len() {
             return Err(anyhow!(
@@ -53,223 +115,946 @@ impl Table {
             ));
         }
 
-        // Check primary key constraint (no duplicates)
-        if let Some(pk_index) = self.schema.get_primary_key_index() {
-            let pk_value = &values[pk_index];
-            let pk_name = &self.schema.columns[pk_index].name;
-
-            if let Some(index) = self.indexes.get(pk_name) {
-                if index.lookup(pk_value).is_some() {
-                    return Err(anyhow!("Primary key violation: duplicate value"));
-                }
-            }
-        }
+        // Convert the values to their column types (rounding decimals)
+        let values = self
+            .schema
+            .columns
+            .iter()
+            .zip(values)
+            .map(|(column, value)| column.coerce(value))
+            .collect::<Result<Vec<Value>>>()?;
 
-        // Create the row
+        // Check the CHECK constraints, then the primary key and UNIQUE
+        // constraints (no duplicates)
         let row = Row { values };
+        self.check_conditions(&row)?;
+        self.check_unique(&row, snapshot)?;
 
-        // Insert into page manager
-        let (_page_id, _row_index) = self.page_manager.insert(row.clone());
-        let row_id = self.next_row_id;
-        self.next_row_id += 1;
+        self.insert_version(snapshot.txn_id, row.clone())?;
+        Ok(row)
+    }
 
-        // Update all indexes
-        for (col_index, value) in row.values.iter().enumerate() {
-            let col_name = &self.schema.columns[col_index].name;
-            if let Some(index) = self.indexes.get_mut(col_name) {
-                index.insert(value.clone(), row_id);
+    /// Insert a row given the values of some of its columns:
+    /// INSERT INTO t (b, a) VALUES (1, DEFAULT)
+    /// Columns left out, and values given as DEFAULT (None), get the
+    /// column's default value, or NULL if it has none
+    /// The session lets defaults take numbers from sequences (SERIAL columns)
+    pub fn insert_columns(
+        &mut self,
+        columns: &[String],
+        values: Vec<Option<Value>>,
+        session: &mut SequenceSession,
+        snapshot: &Snapshot,
+    ) -> Result<Row> {
+        if columns.len() != values.len() {
+            return Err(anyhow!(
+                "Expected {} values, got {}",
+                columns.len(),
+                values.len()
+            ));
+        }
+
+        let mut given: Vec<Option<Value>> = vec![None; self.schema.columns.len()];
+        let mut named = vec![false; self.schema.columns.len()];
+        for (name, value) in columns.iter().zip(values) {
+            let index = self
+                .schema
+                .get_column_index(name)
+                .ok_or_else(|| anyhow!("Column not found: {}", name))?;
+            if named[index] {
+                return Err(anyhow!("Column '{}' is given more than once", name));
             }
+            named[index] = true;
+            given[index] = value;
         }
 
-        Ok(row_id)
+        // Defaults are evaluated for every row: DEFAULT NOW() is the time of the INSERT
+        let no_row = Row { values: Vec::new() };
+        let values = given
+            .into_iter()
+            .zip(&self.schema.columns)
+            .map(|(value, column)| match (value, &column.default) {
+                (Some(value), _) => Ok(value),
+                (None, Some(default)) => default.evaluate_with(&self.schema, &no_row, session),
+                (None, None) => Ok(Value::Null),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        self.insert(values, snapshot)
     }
 
-    /// Select rows based on a simple condition
+    /// Select the rows visible to a snapshot that meet a condition
     /// This is a simplified version - real databases have complex query planners
     ///
     /// Parameters:
-    /// - column_name: The column to filter on (None for all rows)
-    /// - value: The value to match (None for all rows)
-    pub fn select(&self, column_name: Option<&str>, value: Option<&Value>) -> Result<Vec<Row>> {
-        match (column_name, value) {
-            // If we have a column and value, try to use an index
-            (Some(col_name), Some(val)) => {
-                // Check if we have an index on this column
-                if let Some(index) = self.indexes.get(col_name) {
-                    // Index lookup - O(log n)
-                    if let Some(row_ids) = index.lookup(val) {
-                        let mut results = Vec::new();
-                        for &row_id in row_ids {
-                            if let Some(row) = self.page_manager.get(row_id) {
-                                results.push(row.clone());
-                            }
-                        }
-                        return Ok(results);
-                    } else {
-                        return Ok(Vec::new());
-                    }
-                }
+    /// - condition: The WHERE condition (None for all rows); see expr.rs
+    /// - order_by: The keys to sort the rows by (none for any order)
+    /// - snapshot: Decides which row versions the caller can see
+    ///
+    /// A WHERE that can use an index finds few rows, which are then sorted.
+    /// Otherwise, if an index covers the ORDER BY, the rows are read in
+    /// index order and need no sorting.
+    pub fn select(
+        &self,
+        condition: Option<&Expr>,
+        order_by: &[SortKey],
+        snapshot: &Snapshot,
+    ) -> Result<Vec<Row>> {
+        let in_index_order = match condition.and_then(|condition| self.index_candidates(condition))
+        {
+            Some(_) => None,
+            None => self.index_order(order_by),
+        };
+        let sorted = in_index_order.is_some() || order_by.is_empty();
+        let candidates = match in_index_order {
+            Some(candidates) => candidates,
+            None => Box::new(self.candidates(condition, snapshot)?.into_iter()),
+        };
 
-                // No index - do a full table scan
-                let col_index = self
-                    .schema
-                    .get_column_index(col_name)
-                    .ok_or_else(|| anyhow!("Column not found: {}", col_name))?;
-
-                Ok(self
-                    .page_manager
-                    .scan()
-                    .into_iter()
-                    .filter(|(_id, row)| &row.values[col_index] == val)
-                    .map(|(_id, row)| row.clone())
-                    .collect())
+        // Each visible candidate is read (and its overflow values loaded) once
+        let mut rows = Vec::new();
+        for row_id in candidates {
+            if !self.is_visible(row_id, snapshot)? {
+                continue;
+            }
+            let Some(version) = self.page_manager.get(row_id)? else {
+                continue;
+            };
+            if condition.map_or(Ok(true), |condition| self.is_match(condition, &version.row))? {
+                rows.push(version.row);
             }
-            // No filter - return all rows (full table scan)
-            _ => Ok(self
-                .page_manager
-                .scan()
-                .into_iter()
-                .map(|(_id, row)| row.clone())
-                .collect()),
         }
+        if sorted {
+            return Ok(rows);
+        }
+        expr::sort_rows(&self.schema, order_by, rows)
     }
 
     /// Update rows matching a condition
-    /// Returns the number of rows updated
+    /// Returns every updated row, before and after the change
+    ///
+    /// The old version of each row is marked as deleted and a new version is
+    /// inserted, so transactions with an older snapshot still see the old values
     pub fn update(
         &mut self,
-        where_column: &str,
-        where_value: &Value,
+        condition: &Expr,
         update_column: &str,
         update_value: Value,
-    ) -> Result<usize> {
-        let where_col_index = self
-            .schema
-            .get_column_index(where_column)
-            .ok_or_else(|| anyhow!("Column not found: {}", where_column))?;
-
+        snapshot: &Snapshot,
+    ) -> Result<Vec<(Row, Row)>> {
         let update_col_index = self
             .schema
             .get_column_index(update_column)
             .ok_or_else(|| anyhow!("Column not found: {}", update_column))?;
 
-        let mut updated_count = 0;
+        // Find the rows first, so we never revisit the new versions we create
+        let row_ids = self.find_visible(Some(condition), snapshot)?;
 
-        // Find rows to update using index if available
-        let row_ids: Vec<usize> = if let Some(index) = self.indexes.get(where_column) {
-            index
-                .lookup(where_value)
-                .map(|ids| ids.clone())
-                .unwrap_or_default()
-        } else {
-            // Full table scan
-            self.page_manager
-                .scan()
-                .into_iter()
-                .filter(|(_id, row)| &row.values[where_col_index] == where_value)
-                .map(|(id, _row)| id)
-                .collect()
-        };
+        self.update_rows(&row_ids, vec![(update_col_index, update_value)], snapshot)
+    }
+
+    /// Give columns (by position) of the given rows new values
+    /// Returns every updated row, before and after the change
+    pub fn update_rows(
+        &mut self,
+        row_ids: &[RecordId],
+        changes: Vec<(usize, Value)>,
+        snapshot: &Snapshot,
+    ) -> Result<Vec<(Row, Row)>> {
+        let changes = changes
+            .into_iter()
+            .map(|(index, value)| Ok((index, self.schema.columns[index].coerce(value)?)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut updated = Vec::with_capacity(row_ids.len());
+        for &row_id in row_ids {
+            let old_row = self.delete_version(row_id, snapshot)?;
+
+            let mut new_row = old_row.clone();
+            for (index, value) in &changes {
+                new_row.values[*index] = value.clone();
+            }
+            self.check_conditions(&new_row)?;
+            self.check_unique(&new_row, snapshot)?;
+            self.insert_version(snapshot.txn_id, new_row.clone())?;
+            updated.push((old_row, new_row));
+        }
+
+        Ok(updated)
+    }
+
+    /// Delete rows matching a condition
+    /// Returns the deleted rows
+    ///
+    /// Like real MVCC databases, we don't remove the rows right away: we mark
+    /// them as deleted by our transaction. Once the transaction has committed
+    /// and no snapshot can see them anymore, prune() or VACUUM frees their slots
+    pub fn delete(&mut self, condition: &Expr, snapshot: &Snapshot) -> Result<Vec<Row>> {
+        let row_ids = self.find_visible(Some(condition), snapshot)?;
+        self.delete_rows(&row_ids, snapshot)
+    }
+
+    /// Delete the given rows
+    /// Returns the deleted rows
+    pub fn delete_rows(&mut self, row_ids: &[RecordId], snapshot: &Snapshot) -> Result<Vec<Row>> {
+        row_ids
+            .iter()
+            .map(|&row_id| self.delete_version(row_id, snapshot))
+            .collect()
+    }
+
+    /// Remove row versions that no transaction can see anymore
+    /// A version is dead once the transaction that deleted it is older than
+    /// the vacuum horizon (see TransactionManager::vacuum_horizon)
+    /// Returns the number of versions removed
+    pub fn vacuum(&mut self, horizon: TxnId) -> Result<usize> {
+        let row_ids = self
+            .page_manager
+            .scan()
+            .map(|entry| entry.map(|(id, _header)| id))
+            .collect::<Result<Vec<_>>>()?;
 
-        // Update each row
-        for row_id in row_ids {
-            if let Some(row) = self.page_manager.get_mut(row_id) {
-                // Remove old value from indexes
-                let old_value = row.values[update_col_index].clone();
-                if let Some(index) = self.indexes.get_mut(update_column) {
-                    index.remove(&old_value, row_id);
+        self.prune(&row_ids, horizon)
+    }
+
+    /// Remove the dead versions among the given rows, freeing their slots
+    /// (and index entries) so new rows can reuse them
+    /// Returns the number of versions removed
+    pub fn prune(&mut self, row_ids: &[RecordId], horizon: TxnId) -> Result<usize> {
+        let mut removed = 0;
+
+        for &row_id in row_ids {
+            let dead = self
+                .page_manager
+                .header(row_id)?
+                .is_some_and(|header| header.xmax.is_some_and(|xmax| xmax < horizon));
+            if !dead {
+                continue;
+            }
+
+            // Pruning isn't part of any transaction, so it is logged under
+            // transaction 0 (a real transaction ID is never 0)
+            let record = LogRecord::Remove {
+                table: self.name.clone(),
+                row_id,
+            };
+            let lsn = self.log(0, &record)?;
+            self.apply(0, &record, lsn)?;
+            removed += 1;
+        }
+
+        Ok(removed)
+    }
+
+    /// Re-apply a logged change during crash recovery
+    /// The change is skipped if its page has already seen it (the page LSN is
+    /// at least the record's LSN), so the pages end up exactly as they were
+    ///
+    /// Changes of transactions that haven't finished are tracked again, so
+    /// they can be rolled back once the whole log has been replayed
+    pub fn redo(&mut self, entry: &LogEntry) -> Result<()> {
+        let txn_id = entry.txn_id;
+        let record = &entry.record;
+
+        match record {
+            LogRecord::Insert { .. } | LogRecord::Delete { .. } => {
+                self.pending.push((txn_id, record.clone()));
+            }
+            LogRecord::Remove { .. } | LogRecord::Undelete { .. } => {
+                // A compensation record reverses the transaction's latest change
+                if let Some(pos) = self.pending.iter().rposition(|(id, _)| *id == txn_id) {
+                    self.pending.remove(pos);
                 }
+            }
+            LogRecord::Commit | LogRecord::Abort => {}
+        }
 
-                // Update the value
-                row.values[update_col_index] = update_value.clone();
+        let Some(row_id) = record.row_id() else {
+            return Ok(());
+        };
+        if self.page_manager.page_lsn(row_id.page_id)? >= entry.lsn {
+            return Ok(());
+        }
+        self.apply(txn_id, record, entry.lsn)
+    }
 
-                // Add new value to indexes
-                if let Some(index) = self.indexes.get_mut(update_column) {
-                    index.insert(update_value.clone(), row_id);
+    /// Apply a logged change to the pages and the indexes
+    /// lsn is the change's log record, stamped onto the page it modifies
+    fn apply(&mut self, txn_id: TxnId, record: &LogRecord, lsn: Lsn) -> Result<()> {
+        match record {
+            LogRecord::Insert { row_id, row, .. } => {
+                let version = RowVersion {
+                    xmin: txn_id,
+                    xmax: None,
+                    row: row.clone(),
+                };
+                if let Some(previous) = self.page_manager.put(*row_id, version, lsn)? {
+                    self.unindex_row(&previous.row, *row_id);
+                }
+                self.index_row(row, *row_id);
+            }
+            LogRecord::Delete { row_id, .. } => {
+                self.set_xmax(*row_id, Some(txn_id), lsn)?;
+            }
+            LogRecord::Remove { row_id, .. } => {
+                if let Some(version) = self.page_manager.remove(*row_id, lsn)? {
+                    self.unindex_row(&version.row, *row_id);
                 }
+            }
+            LogRecord::Undelete { row_id, .. } => {
+                self.set_xmax(*row_id, None, lsn)?;
+            }
+            LogRecord::Commit | LogRecord::Abort => {}
+        }
+        Ok(())
+    }
+
+    /// Make a transaction's changes permanent
+    /// Returns the rows the transaction deleted, so they can be pruned
+    pub fn commit(&mut self, txn_id: TxnId) -> Vec<RecordId> {
+        let mut deleted = Vec::new();
 
-                updated_count += 1;
+        self.pending.retain(|(id, record)| {
+            if *id != txn_id {
+                return true;
+            }
+            if let LogRecord::Delete { row_id, .. } = record {
+                deleted.push(*row_id);
+            }
+            false
+        });
+
+        deleted
+    }
+
+    /// Undo every change made by a transaction, newest first
+    /// Each undo is logged as a compensation record before it is applied, so a
+    /// crash in the middle of a rollback can finish it during recovery
+    pub fn rollback(&mut self, txn_id: TxnId) -> Result<()> {
+        let (mine, others): (Vec<_>, Vec<_>) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|(id, _record)| *id == txn_id);
+        self.pending = others;
+
+        for (_id, record) in mine.iter().rev() {
+            if let Some(compensation) = record.compensation() {
+                let lsn = self.log(txn_id, &compensation)?;
+                self.apply(txn_id, &compensation, lsn)?;
             }
         }
+        Ok(())
+    }
 
-        Ok(updated_count)
+    /// The transactions that have changed this table and haven't finished
+    pub fn pending_transactions(&self) -> Vec<TxnId> {
+        self.pending.iter().map(|(id, _record)| *id).collect()
     }
 
-    /// Delete rows matching a condition
-    /// Note: This is simplified - real databases don't actually delete immediately
-    /// They mark rows as deleted and clean up later (MVCC - Multi-Version Concurrency Control)
-    pub fn delete(&mut self, column_name: &str, value: &Value) -> Result<usize> {
-        let col_index = self
-            .schema
-            .get_column_index(column_name)
-            .ok_or_else(|| anyhow!("Column not found: {}", column_name))?;
-
-        // Find rows to delete
-        let row_ids: Vec<usize> = if let Some(index) = self.indexes.get(column_name) {
-            index
-                .lookup(value)
-                .map(|ids| ids.clone())
-                .unwrap_or_default()
+    /// Check whether a transaction has changed this table
+    pub fn has_pending_changes(&self, txn_id: TxnId) -> bool {
+        self.pending.iter().any(|(id, _record)| *id == txn_id)
+    }
+
+    /// Find the IDs of the row versions a snapshot can see, optionally
+    /// only those meeting a condition (the rows for which it is true)
+    fn find_visible(&self, condition: Option<&Expr>, snapshot: &Snapshot) -> Result<Vec<RecordId>> {
+        let mut matching = Vec::new();
+        for row_id in self.candidates(condition, snapshot)? {
+            if !self.is_visible(row_id, snapshot)? {
+                continue;
+            }
+            let Some(condition) = condition else {
+                matching.push(row_id);
+                continue;
+            };
+            let Some(version) = self.page_manager.get(row_id)? else {
+                continue;
+            };
+            if self.is_match(condition, &version.row)? {
+                matching.push(row_id);
+            }
+        }
+        Ok(matching)
+    }
+
+    /// The row versions that may meet a condition: the ones an index finds,
+    /// or else every version the snapshot can see (full table scan, going
+    /// by the version headers)
+    fn candidates(&self, condition: Option<&Expr>, snapshot: &Snapshot) -> Result<Vec<RecordId>> {
+        if let Some(candidates) = condition.and_then(|condition| self.index_candidates(condition)) {
+            return Ok(candidates);
+        }
+
+        let mut candidates = Vec::new();
+        for entry in self.page_manager.scan() {
+            let (row_id, header) = entry?;
+            if snapshot.can_see(header) {
+                candidates.push(row_id);
+            }
+        }
+        Ok(candidates)
+    }
+
+    /// Check whether the version stored under a record ID is visible to a snapshot
+    fn is_visible(&self, row_id: RecordId, snapshot: &Snapshot) -> Result<bool> {
+        Ok(self
+            .page_manager
+            .header(row_id)?
+            .is_some_and(|header| snapshot.can_see(header)))
+    }
+
+    /// Check whether a row meets a condition; like in SQL, a condition
+    /// that is NULL ("unknown") leaves the row out
+    fn is_match(&self, condition: &Expr, row: &Row) -> Result<bool> {
+        match condition.evaluate(&self.schema, row)? {
+            Value::Boolean(b) => Ok(b),
+            Value::Null => Ok(false),
+            other => Err(anyhow!("WHERE must be true or false, got {}", other)),
+        }
+    }
+
+    /// Use an index to find the rows that may meet a condition, if it limits
+    /// an indexed column (or path) to some values or a range:
+    ///   WHERE email = 'a@b.c' AND age > 30    looks up 'a@b.c' in the index on email
+    ///   WHERE city IN ('Oslo', 'Rome')        looks up both cities
+    ///   WHERE age BETWEEN 20 AND 30           walks the index from 20 to 30
+    /// The candidates still have to be checked against the whole condition
+    fn index_candidates(&self, condition: &Expr) -> Option<Vec<RecordId>> {
+        condition
+            .key_filters()
+            .into_iter()
+            .find_map(|(target, filter)| {
+                let index = self.indexes.get(&target.to_string())?;
+
+                // Look up values as the column's type: 5 finds 5.0 in a FLOAT
+                // column. A value the column can't hold is left to the scan,
+                // which compares it with each row (and reports 'a' for an
+                // INTEGER column the same way with or without an index).
+                let data_type = target.data_type(&self.schema).ok()?;
+                let key = |value: &Value| {
+                    let key = value.convert_to(&data_type).ok()?;
+                    key.has_type(&data_type).then_some(key)
+                };
+                match filter {
+                    KeyFilter::Values(values) => {
+                        let mut row_ids = Vec::new();
+                        for value in values {
+                            row_ids.extend(index.tree.lookup(&key(value)?).into_iter().flatten());
+                        }
+                        // IN (1, 1) must not return a row twice
+                        row_ids.sort();
+                        row_ids.dedup();
+                        Some(row_ids)
+                    }
+                    KeyFilter::Range(low, high) => {
+                        Some(index.tree.range_query(&key(low)?, &key(high)?))
+                    }
+                }
+            })
+    }
+
+    /// Walk an index in the order an ORDER BY asks for, if one covers its keys:
+    ///   ORDER BY id DESC               walks the primary key index backwards
+    ///   ORDER BY data->>'type'         walks the index on that JSON path
+    ///   ORDER BY last_name, first_name walks an index on (last_name, first_name)
+    /// Every key has to go the same way. The index keeps NULLs below every
+    /// value: the NULLs of the first key are moved to where the ORDER BY
+    /// wants them, but the later keys must either want them there too or
+    /// be columns that can't be NULL.
+    /// The record IDs come from the index as they are read, not collected first.
+    fn index_order(&self, order_by: &[SortKey]) -> Option<Box<dyn Iterator<Item = RecordId> + '_>> {
+        let first = order_by.first()?;
+        let targets = order_by
+            .iter()
+            .map(|key| match &key.expr {
+                Expr::Column(target) => Some(target),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let name = targets
+            .iter()
+            .map(|target| target.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let index = self.indexes.get(&name)?;
+
+        for (key, target) in order_by.iter().zip(&targets).skip(1) {
+            let nulls_match = key.nulls_first != key.descending;
+            let never_null = target.is_column()
+                && target
+                    .index_in(&self.schema)
+                    .is_ok_and(|i| self.schema.columns[i].is_not_null());
+            if key.descending != first.descending || !(nulls_match || never_null) {
+                return None;
+            }
+        }
+
+        // The NULLs of the first key are where a walk up starts, or a walk
+        // down ends: the walk is split there, and its halves put in order
+        type Entries<'a> = Box<dyn Iterator<Item = (&'a [Value], &'a [RecordId])> + 'a>;
+        let is_null = |(key, _): &(&[Value], &[RecordId])| matches!(key.first(), Some(Value::Null));
+        let entries = || index.tree.entries();
+        let (nulls, values): (Entries, Entries) = if first.descending {
+            (
+                Box::new(entries().rev().skip_while(move |entry| !is_null(entry))),
+                Box::new(entries().rev().take_while(move |entry| !is_null(entry))),
+            )
         } else {
-            self.page_manager
-                .scan()
-                .into_iter()
-                .filter(|(_id, row)| &row.values[col_index] == value)
-                .map(|(id, _row)| id)
-                .collect()
+            (
+                Box::new(entries().take_while(is_null)),
+                Box::new(entries().skip_while(is_null)),
+            )
         };
+        let ordered = if first.nulls_first {
+            nulls.chain(values)
+        } else {
+            values.chain(nulls)
+        };
+        Some(Box::new(
+            ordered.flat_map(|(_, row_ids)| row_ids.iter().copied()),
+        ))
+    }
 
-        let delete_count = row_ids.len();
+    /// Find the IDs of the row versions a snapshot can see whose columns (by
+    /// position) hold the given key - used to follow foreign keys
+    /// Like in a WHERE clause, NULL equals nothing, so a key with a NULL finds no rows
+    pub fn find_key(
+        &self,
+        columns: &[usize],
+        key: &[Value],
+        snapshot: &Snapshot,
+    ) -> Result<Vec<RecordId>> {
+        if key.iter().any(|value| matches!(value, Value::Null)) {
+            return Ok(Vec::new());
+        }
 
-        // Remove from indexes
-        for row_id in &row_ids {
-            if let Some(row) = self.page_manager.get(*row_id) {
-                for (col_idx, val) in row.values.iter().enumerate() {
-                    let col_name = &self.schema.columns[col_idx].name;
-                    if let Some(index) = self.indexes.get_mut(col_name) {
-                        index.remove(val, *row_id);
-                    }
+        let name = columns
+            .iter()
+            .map(|&index| self.schema.columns[index].name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        // The index and the pages hold every version - keep the visible ones
+        let mut found = Vec::new();
+        if let Some(index) = self.indexes.get(&name) {
+            for &row_id in index.tree.lookup_key(key).into_iter().flatten() {
+                if self.is_visible(row_id, snapshot)? {
+                    found.push(row_id);
+                }
+            }
+            return Ok(found);
+        }
+
+        for row_id in self.candidates(None, snapshot)? {
+            let Some(version) = self.page_manager.get(row_id)? else {
+                continue;
+            };
+            if columns
+                .iter()
+                .zip(key)
+                .all(|(&index, value)| &version.row.values[index] == value)
+            {
+                found.push(row_id);
+            }
+        }
+        Ok(found)
+    }
+
+    /// Check that a new row version meets every CHECK constraint
+    /// Like in SQL, only a condition that is false fails: NULL means
+    /// "unknown", and lets the row through
+    fn check_conditions(&self, row: &Row) -> Result<()> {
+        for check in &self.schema.checks {
+            match check.condition.evaluate(&self.schema, row)? {
+                Value::Boolean(true) | Value::Null => {}
+                Value::Boolean(false) => {
+                    return Err(anyhow!(
+                        "New row for table '{}' violates check constraint '{}'",
+                        self.name,
+                        check.name
+                    ));
+                }
+                other => {
+                    return Err(anyhow!(
+                        "Check constraint '{}' must be true or false, got {}",
+                        check.name,
+                        other
+                    ));
                 }
             }
         }
+        Ok(())
+    }
+
+    /// Check that a new row version breaks neither the primary key nor a
+    /// UNIQUE constraint
+    ///
+    /// Like the SQL standard says, NULL is never equal to anything: any number
+    /// of rows can have NULL in a UNIQUE column (or in one of the columns of
+    /// a UNIQUE (a, b) constraint)
+    fn check_unique(&self, row: &Row, snapshot: &Snapshot) -> Result<()> {
+        let pk_name = self
+            .schema
+            .get_primary_key_index()
+            .map(|pk_index| self.schema.columns[pk_index].name.as_str());
+
+        for (name, index) in &self.indexes {
+            if !index.unique {
+                continue;
+            }
+            let key = index.key(row);
+            if key.iter().any(|value| matches!(value, Value::Null)) {
+                continue;
+            }
+            if !self.is_key_taken(&index.tree, &key, snapshot)? {
+                continue;
+            }
+
+            if Some(name.as_str()) == pk_name {
+                return Err(anyhow!("Primary key violation: duplicate value"));
+            }
+            let values: Vec<String> = key.iter().map(|value| value.to_string()).collect();
+            return Err(anyhow!(
+                "Unique constraint violation: ({}) = ({}) already exists",
+                name,
+                values.join(", ")
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check whether a key is already used in an index by a version that is
+    /// (or may become) live - used for primary key and UNIQUE checks
+    ///
+    /// A version only frees its key once its deletion is visible to us;
+    /// a row inserted by a transaction that is still running counts as taken
+    fn is_key_taken(&self, tree: &BTreeIndex, key: &[Value], snapshot: &Snapshot) -> Result<bool> {
+        let Some(row_ids) = tree.lookup_key(key) else {
+            return Ok(false);
+        };
+
+        for &row_id in row_ids {
+            if let Some(header) = self.page_manager.header(row_id)? {
+                if !header.xmax.is_some_and(|xmax| snapshot.sees(xmax)) {
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// Store a brand-new row version created by a transaction
+    fn insert_version(&mut self, txn_id: TxnId, row: Row) -> Result<RecordId> {
+        let version = RowVersion {
+            xmin: txn_id,
+            xmax: None,
+            row,
+        };
+
+        // Decide where the row goes and log it before touching the page
+        let row_id = self.page_manager.next_record_id(&version)?;
+        let record = LogRecord::Insert {
+            table: self.name.clone(),
+            row_id,
+            row: version.row,
+        };
+        self.change(txn_id, record)?;
+
+        Ok(row_id)
+    }
+
+    /// Mark a visible row version as deleted by the snapshot's transaction
+    /// Returns the row's values
+    ///
+    /// If another transaction already deleted (or updated) this version, we
+    /// have a write-write conflict. Under snapshot isolation the first writer
+    /// wins, so this transaction has to give up.
+    fn delete_version(&mut self, row_id: RecordId, snapshot: &Snapshot) -> Result<Row> {
+        let version = self
+            .page_manager
+            .get(row_id)?
+            .ok_or_else(|| anyhow!("Row {} does not exist", row_id))?;
+
+        if version.xmax.is_some() {
+            return Err(anyhow!(
+                "Could not serialize access to table '{}' due to a concurrent update",
+                self.name
+            ));
+        }
+
+        let record = LogRecord::Delete {
+            table: self.name.clone(),
+            row_id,
+            row: version.row.clone(),
+        };
+        self.change(snapshot.txn_id, record)?;
+
+        Ok(version.row)
+    }
+
+    /// Set (or clear) the deleting transaction of a row version
+    fn set_xmax(&mut self, row_id: RecordId, xmax: Option<TxnId>, lsn: Lsn) -> Result<()> {
+        self.page_manager.set_xmax(row_id, xmax, lsn)
+    }
+
+    /// Attach the write-ahead log that this table's changes are recorded in
+    pub fn set_wal(&mut self, wal: SharedWal) {
+        self.wal = Some(wal);
+    }
+
+    /// Force the table's pages to stable storage
+    pub fn sync(&mut self) -> Result<()> {
+        self.page_manager.sync()
+    }
+
+    /// Make a change on behalf of a transaction: log it, remember it so the
+    /// transaction can be rolled back, and only then apply it
+    fn change(&mut self, txn_id: TxnId, record: LogRecord) -> Result<()> {
+        let lsn = self.log(txn_id, &record)?;
+        self.pending.push((txn_id, record.clone()));
+        self.apply(txn_id, &record, lsn)
+    }
+
+    /// Append a record to the write-ahead log (if any)
+    /// Returns its LSN, or 0 when the table is not logged
+    fn log(&mut self, txn_id: TxnId, record: &LogRecord) -> Result<Lsn> {
+        match &self.wal {
+            Some(wal) => wal
+                .lock()
+                .map_err(|_| anyhow!("WAL lock poisoned"))?
+                .append(txn_id, record),
+            None => Ok(0),
+        }
+    }
+
+    /// Add a row's values to every index
+    fn index_row(&mut self, row: &Row, row_id: RecordId) {
+        for index in self.indexes.values_mut() {
+            let key = index.key(row);
+            index.tree.insert_key(key, row_id);
+        }
+    }
 
-        Ok(delete_count)
+    /// Remove a row's values from every index
+    fn unindex_row(&mut self, row: &Row, row_id: RecordId) {
+        for index in self.indexes.values_mut() {
+            let key = index.key(row);
+            index.tree.remove_key(&key, row_id);
+        }
     }
 
-    /// Create an index on a column
+    /// Create an index on a column, or on a path into a JSON column
+    /// (data->>'type' indexes the "type" member of every document)
     /// Indexes speed up queries but slow down inserts/updates
     pub fn create_index(&mut self, column_name: &str) -> Result<()> {
-        // Check if column exists
-        let col_index = self
-            .schema
-            .get_column_index(column_name)
-            .ok_or_else(|| anyhow!("Column not found: {}", column_name))?;
+        self.add_index(&[column_name.to_string()], false)?;
+        Ok(())
+    }
+
+    /// Add a UNIQUE constraint on one or more columns (CREATE UNIQUE INDEX)
+    /// Fails if the rows already stored break it
+    pub fn add_unique(&mut self, columns: &[String]) -> Result<()> {
+        let columns = self.add_index(columns, true)?;
+        self.schema.unique.push(columns);
+        Ok(())
+    }
+
+    /// Build an index on the given columns (or JSON paths) from the stored rows
+    /// A unique index replaces a plain index on the same columns
+    /// Returns the indexed columns, written the canonical way
+    fn add_index(&mut self, columns: &[String], unique: bool) -> Result<Vec<String>> {
+        // Check if the columns exist
+        let targets = columns
+            .iter()
+            .map(|column| {
+                let target: ColumnRef = column.parse()?;
+                let col_index = target.index_in(&self.schema)?;
+                Ok((target, col_index))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let columns: Vec<String> = targets
+            .iter()
+            .map(|(target, _)| target.to_string())
+            .collect();
+        let name = columns.join(", ");
 
         // Check if index already exists
-        if self.indexes.contains_key(column_name) {
-            return Err(anyhow!("Index already exists on column: {}", column_name));
+        if let Some(existing) = self.indexes.get(&name) {
+            if existing.unique || !unique {
+                return Err(anyhow!("Index already exists on column: {}", name));
+            }
         }
 
         // Create the index
-        let mut index = BTreeIndex::new(column_name.to_string());
+        let mut index = TableIndex {
+            targets,
+            unique,
+            tree: BTreeIndex::new(name.clone()),
+        };
 
-        // Index all existing rows
-        for (row_id, row) in self.page_manager.scan() {
-            let value = &row.values[col_index];
-            index.insert(value.clone(), row_id);
+        // Index all existing row versions (lookups filter out invisible ones),
+        // reading one row at a time
+        for entry in self.page_manager.scan() {
+            let (row_id, _header) = entry?;
+            let Some(version) = self.page_manager.get(row_id)? else {
+                continue;
+            };
+            let key = index.key(&version.row);
+
+            // Rows that are not deleted must not share a key
+            let live = version.xmax.is_none();
+            let has_null = key.iter().any(|value| matches!(value, Value::Null));
+            if unique && live && !has_null {
+                if let Some(row_ids) = index.tree.lookup_key(&key) {
+                    for &other in row_ids {
+                        if self
+                            .page_manager
+                            .header(other)?
+                            .is_some_and(|h| h.xmax.is_none())
+                        {
+                            return Err(anyhow!(
+                                "Cannot create unique index on ({}): some rows share a key",
+                                name
+                            ));
+                        }
+                    }
+                }
+            }
+
+            index.tree.insert_key(key, row_id);
         }
 
-        self.indexes.insert(column_name.to_string(), index);
-        Ok(())
+        self.indexes.insert(name, index);
+        Ok(columns)
+    }
+
+    /// Get the number of rows in the table (versions that are not deleted,
+    /// including ones inserted by transactions that haven't committed yet)
+    pub fn row_count(&self) -> Result<usize> {
+        let mut count = 0;
+        for entry in self.page_manager.scan() {
+            let (_id, header) = entry?;
+            if header.xmax.is_none() {
+                count += 1;
+            }
+        }
+        Ok(count)
     }
 
-    /// Get the number of rows in the table
-    pub fn row_count(&self) -> usize {
+    /// Get the number of row versions stored, including dead ones
+    /// that VACUUM has not removed yet
+    pub fn version_count(&self) -> Result<usize> {
         self.page_manager.total_rows()
     }
 
+    /// The highest LSN stamped on any page of the table
+    pub fn max_lsn(&self) -> Lsn {
+        self.page_manager.max_lsn()
+    }
+
+    /// The highest transaction ID mentioned by any stored row version
+    pub fn max_txn_id(&self) -> Result<TxnId> {
+        let mut max = 0;
+        for entry in self.page_manager.scan() {
+            let (_id, header) = entry?;
+            max = max.max(header.xmin).max(header.xmax.unwrap_or(0));
+        }
+        Ok(max)
+    }
+
     /// Get the schema of the table
     pub fn get_schema(&self) -> &Schema {
         &self.schema
     }
+
+    /// Get the names of all indexed columns and JSON paths (sorted, so the
+    /// catalog is stable)
+    pub fn indexed_columns(&self) -> Vec<String> {
+        let mut columns: Vec<String> = self.indexes.keys().cloned().collect();
+        columns.sort();
+        columns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::mvcc::TransactionManager;
+    use crate::storage::{Column, DataType};
+
+    fn sort_key(column: &str, descending: bool, nulls_first: bool) -> SortKey {
+        SortKey {
+            expr: Expr::Column(ColumnRef::column(column)),
+            descending,
+            nulls_first,
+            name: column.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_index_order() {
+        let column = |name: &str, primary_key: bool| Column {
+            name: name.to_string(),
+            data_type: DataType::Integer,
+            primary_key,
+            nullable: !primary_key,
+            default: None,
+        };
+        let schema = Schema::new(vec![column("id", true), column("age", false)]);
+        let mut table = Table::new("people".to_string(), schema).unwrap();
+        let snapshot = TransactionManager::new(0).begin();
+        for (id, age) in [
+            (1, Value::Integer(30)),
+            (2, Value::Null),
+            (3, Value::Integer(20)),
+        ] {
+            table
+                .insert(vec![Value::Integer(id), age], &snapshot)
+                .unwrap();
+        }
+        table.create_index("age").unwrap();
+
+        let ids = |table: &Table, order_by: &[SortKey]| {
+            table.index_order(order_by).map(|row_ids| {
+                row_ids
+                    .map(|row_id| {
+                        table.page_manager.get(row_id).unwrap().unwrap().row.values[0].clone()
+                    })
+                    .collect::<Vec<_>>()
+            })
+        };
+        let expect = |ids: &[i64]| Some(ids.iter().map(|&id| Value::Integer(id)).collect());
+
+        // A single indexed key, either way, with its NULLs on either side
+        assert_eq!(
+            ids(&table, &[sort_key("age", false, false)]),
+            expect(&[3, 1, 2])
+        );
+        assert_eq!(
+            ids(&table, &[sort_key("age", false, true)]),
+            expect(&[2, 3, 1])
+        );
+        assert_eq!(
+            ids(&table, &[sort_key("age", true, true)]),
+            expect(&[2, 1, 3])
+        );
+        assert_eq!(
+            ids(&table, &[sort_key("age", true, false)]),
+            expect(&[1, 3, 2])
+        );
+        assert_eq!(
+            ids(&table, &[sort_key("id", true, true)]),
+            expect(&[3, 2, 1])
+        );
+
+        // No index, or keys going different ways, sort in memory instead
+        assert_eq!(ids(&table, &[]), None);
+        let mixed = [sort_key("age", false, false), sort_key("id", true, true)];
+        assert_eq!(ids(&table, &mixed), None);
+        table
+            .add_unique(&["age".to_string(), "id".to_string()])
+            .unwrap();
+        assert_eq!(ids(&table, &mixed), None);
+        let both_up = [sort_key("age", false, false), sort_key("id", false, false)];
+        assert_eq!(ids(&table, &both_up), expect(&[3, 1, 2]));
+    }
 }
@@ -0,0 +1,797 @@
+// Dates and times (DATE, TIME, TIMESTAMP, INTERVAL)
+// Storing a timestamp as TEXT works until you want to sort or compare it:
+// '2026-10-02' > '2026-9-30' is false for strings. These types store numbers
+// instead, so ordering, index ranges and arithmetic all come for free:
+//
+//   Date       days since 1970-01-01
+//   Time       microseconds since midnight
+//   Timestamp  microseconds since 1970-01-01 00:00:00 (no time zone)
+//   Interval   months + days + microseconds, kept apart like PostgreSQL does,
+//              because a month has no fixed length in days and (with
+//              daylight saving time) a day has no fixed length in hours
+//
+// Calendar math uses the proleptic Gregorian calendar, via the well-known
+// days-from-civil algorithm (https://howardhinnant.github.io/date_algorithms.html).
+
+use super::decimal::Decimal;
+use super::Value;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MICROS_PER_SECOND: i64 = 1_000_000;
+const MICROS_PER_MINUTE: i64 = 60 * MICROS_PER_SECOND;
+const MICROS_PER_HOUR: i64 = 60 * MICROS_PER_MINUTE;
+const MICROS_PER_DAY: i64 = 24 * MICROS_PER_HOUR;
+
+/// Intervals are compared as if every month had 30 days (like PostgreSQL)
+const DAYS_PER_MONTH: i64 = 30;
+
+/// A calendar date, as the number of days since 1970-01-01
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Date(pub i32);
+
+/// A time of day, as the number of microseconds since midnight
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Time(pub i64);
+
+/// A date and time of day, as microseconds since 1970-01-01 00:00:00
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Timestamp(pub i64);
+
+/// A length of time: '1 month 2 days 03:00:00'
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Interval {
+    pub months: i32,
+    pub days: i32,
+    pub micros: i64,
+}
+
+fn out_of_range() -> anyhow::Error {
+    anyhow!("Date/time value out of range")
+}
+
+/// Days since 1970-01-01 of a year/month/day
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Year/month/day of a number of days since 1970-01-01
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Parse a number made of digits only (no sign)
+fn digits<T: FromStr>(text: &str) -> Option<T> {
+    if text.is_empty() || !text.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok()
+}
+
+/// Format microseconds as HH:MM:SS, with a fraction only when there is one
+fn format_clock(f: &mut fmt::Formatter<'_>, micros: i64) -> fmt::Result {
+    let (hours, rest) = (micros / MICROS_PER_HOUR, micros % MICROS_PER_HOUR);
+    let (minutes, rest) = (rest / MICROS_PER_MINUTE, rest % MICROS_PER_MINUTE);
+    let (seconds, fraction) = (rest / MICROS_PER_SECOND, rest % MICROS_PER_SECOND);
+    write!(f, "{:02}:{:02}:{:02}", hours, minutes, seconds)?;
+    if fraction != 0 {
+        let fraction = format!("{:06}", fraction);
+        write!(f, ".{}", fraction.trim_end_matches('0'))?;
+    }
+    Ok(())
+}
+
+impl Date {
+    /// Build a date from a year, month (1-12) and day (1-31)
+    pub fn from_ymd(year: i64, month: u32, day: u32) -> Result<Self> {
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            return Err(anyhow!("Invalid date: {}-{:02}-{:02}", year, month, day));
+        }
+        let days = i32::try_from(days_from_civil(year, month, day)).map_err(|_| out_of_range())?;
+        Ok(Date(days))
+    }
+
+    /// The year, month and day of the date
+    pub fn ymd(&self) -> (i64, u32, u32) {
+        civil_from_days(self.0 as i64)
+    }
+
+    /// Day of the week, 0 = Sunday ... 6 = Saturday (1970-01-01 was a Thursday)
+    pub fn weekday(&self) -> i64 {
+        (self.0 as i64 + 4).rem_euclid(7)
+    }
+
+    /// Add a number of days
+    pub fn add_days(&self, days: i64) -> Result<Self> {
+        i32::try_from(self.0 as i64 + days)
+            .map(Date)
+            .map_err(|_| out_of_range())
+    }
+
+    /// Add a number of months, keeping the day of the month where possible:
+    /// 2026-01-31 plus one month is 2026-02-28
+    pub fn add_months(&self, months: i64) -> Result<Self> {
+        let (year, month, day) = self.ymd();
+        let month_index = year * 12 + (month as i64 - 1) + months;
+        let (year, month) = (
+            month_index.div_euclid(12),
+            month_index.rem_euclid(12) as u32 + 1,
+        );
+        Self::from_ymd(year, month, day.min(days_in_month(year, month)))
+    }
+
+    /// Midnight at the start of the date
+    pub fn to_timestamp(&self) -> Timestamp {
+        Timestamp(self.0 as i64 * MICROS_PER_DAY)
+    }
+}
+
+/// Parse 'YYYY-MM-DD'
+impl FromStr for Date {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid date: '{}', expected YYYY-MM-DD", text);
+        let mut parts = text.trim().splitn(3, '-');
+        let year: i64 = parts.next().and_then(digits).ok_or_else(invalid)?;
+        let month: u32 = parts.next().and_then(digits).ok_or_else(invalid)?;
+        let day: u32 = parts.next().and_then(digits).ok_or_else(invalid)?;
+        if !(1..=9999).contains(&year) {
+            return Err(anyhow!("Year {} is out of range in '{}'", year, text));
+        }
+        Self::from_ymd(year, month, day)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (year, month, day) = self.ymd();
+        write!(f, "{:04}-{:02}-{:02}", year, month, day)
+    }
+}
+
+impl Time {
+    /// Add a length of time, wrapping around midnight
+    /// Months and days don't change a time of day
+    pub fn add(&self, interval: &Interval) -> Time {
+        Time((self.0 + interval.micros.rem_euclid(MICROS_PER_DAY)).rem_euclid(MICROS_PER_DAY))
+    }
+}
+
+/// Parse 'HH:MM', 'HH:MM:SS' or 'HH:MM:SS.ffffff'
+impl FromStr for Time {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid time: '{}', expected HH:MM:SS", text);
+        let mut parts = text.trim().splitn(3, ':');
+        let hours: i64 = parts.next().and_then(digits).ok_or_else(invalid)?;
+        let minutes: i64 = parts.next().and_then(digits).ok_or_else(invalid)?;
+        let (seconds, fraction) = match parts.next() {
+            Some(seconds) => seconds.split_once('.').unwrap_or((seconds, "")),
+            None => ("0", ""),
+        };
+        let seconds: i64 = digits(seconds).ok_or_else(invalid)?;
+        if fraction.len() > 6 || (!fraction.is_empty() && digits::<i64>(fraction).is_none()) {
+            return Err(invalid());
+        }
+        let fraction: i64 = format!("{:0<6}", fraction).parse().map_err(|_| invalid())?;
+
+        if hours > 23 || minutes > 59 || seconds > 59 {
+            return Err(invalid());
+        }
+        Ok(Time(
+            hours * MICROS_PER_HOUR
+                + minutes * MICROS_PER_MINUTE
+                + seconds * MICROS_PER_SECOND
+                + fraction,
+        ))
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        format_clock(f, self.0)
+    }
+}
+
+impl Timestamp {
+    /// The current time (UTC)
+    pub fn now() -> Timestamp {
+        let since_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Timestamp(since_epoch.as_micros() as i64)
+    }
+
+    /// The date part
+    pub fn date(&self) -> Date {
+        Date(self.0.div_euclid(MICROS_PER_DAY) as i32)
+    }
+
+    /// The time of day part
+    pub fn time(&self) -> Time {
+        Time(self.0.rem_euclid(MICROS_PER_DAY))
+    }
+
+    /// Add a length of time: first the months (see Date::add_months), then
+    /// the days, then the rest
+    pub fn add(&self, interval: &Interval) -> Result<Timestamp> {
+        let date = self.date().add_months(interval.months as i64)?;
+        let micros = date.to_timestamp().0 + self.time().0;
+        (interval.days as i64)
+            .checked_mul(MICROS_PER_DAY)
+            .and_then(|days| micros.checked_add(days))
+            .and_then(|micros| micros.checked_add(interval.micros))
+            .map(Timestamp)
+            .ok_or_else(out_of_range)
+    }
+
+    /// The time between two timestamps, as days and microseconds
+    pub fn since(&self, earlier: &Timestamp) -> Result<Interval> {
+        let micros = self.0.checked_sub(earlier.0).ok_or_else(out_of_range)?;
+        Ok(Interval {
+            months: 0,
+            days: (micros / MICROS_PER_DAY) as i32,
+            micros: micros % MICROS_PER_DAY,
+        })
+    }
+}
+
+/// Parse 'YYYY-MM-DD HH:MM:SS[.ffffff]' (a 'T' may separate date and time),
+/// or just 'YYYY-MM-DD' for midnight
+impl FromStr for Timestamp {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let text = text.trim();
+        let (date, time) = match text.split_once([' ', 'T']) {
+            Some((date, time)) => (date, time.parse::<Time>()?),
+            None => (text, Time(0)),
+        };
+        let date: Date = date.parse()?;
+        Ok(Timestamp(date.to_timestamp().0 + time.0))
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.date(), self.time())
+    }
+}
+
+impl Interval {
+    /// Parse an interval, like '1 day 02:00:00' or '3 months ago'
+    /// A bare number is in `unit` if one is given (INTERVAL '5' DAY), and
+    /// in seconds otherwise
+    pub fn parse(text: &str, unit: Option<&str>) -> Result<Self> {
+        let invalid = || anyhow!("Invalid interval: '{}'", text);
+        let mut interval = Interval::default();
+        let mut tokens = text.split_whitespace().peekable();
+
+        while let Some(token) = tokens.next() {
+            if token.eq_ignore_ascii_case("ago") && tokens.peek().is_none() {
+                interval = interval.negate()?;
+            } else if token.contains(':') {
+                let (negative, clock) = match token.strip_prefix('-') {
+                    Some(clock) => (true, clock),
+                    None => (false, token.strip_prefix('+').unwrap_or(token)),
+                };
+                let time: Time = clock.parse().map_err(|_| invalid())?;
+                interval = interval.plus(&Interval {
+                    micros: if negative { -time.0 } else { time.0 },
+                    ..Default::default()
+                })?;
+            } else {
+                let amount: f64 = token.parse().map_err(|_| invalid())?;
+                let unit = match tokens.peek() {
+                    Some(next)
+                        if !next.eq_ignore_ascii_case("ago")
+                            && next.parse::<f64>().is_err()
+                            && !next.contains(':') =>
+                    {
+                        tokens.next().unwrap_or_default()
+                    }
+                    _ => unit.unwrap_or("second"),
+                };
+                interval = interval.plus(&Self::of(amount, unit)?)?;
+            }
+        }
+
+        Ok(interval)
+    }
+
+    /// An amount of some unit, like 1.5 days
+    /// Fractions spill into the smaller fields: 1.5 months is 1 month 15 days
+    fn of(amount: f64, unit: &str) -> Result<Self> {
+        if !amount.is_finite() || amount.abs() * 12.0 > i32::MAX as f64 {
+            return Err(out_of_range());
+        }
+
+        let unit = unit.to_ascii_lowercase();
+        let micros = |per: i64| Interval {
+            micros: (amount * per as f64).round() as i64,
+            ..Default::default()
+        };
+        let days = |per: f64| {
+            let days = amount * per;
+            Interval {
+                months: 0,
+                days: days.trunc() as i32,
+                micros: (days.fract() * MICROS_PER_DAY as f64).round() as i64,
+            }
+        };
+        let months = |per: f64| {
+            let months = amount * per;
+            let days = months.fract() * DAYS_PER_MONTH as f64;
+            Interval {
+                months: months.trunc() as i32,
+                days: days.trunc() as i32,
+                micros: (days.fract() * MICROS_PER_DAY as f64).round() as i64,
+            }
+        };
+
+        let interval = match unit.as_str() {
+            "microsecond" | "microseconds" | "us" => micros(1),
+            "millisecond" | "milliseconds" | "ms" => micros(1000),
+            "second" | "seconds" | "sec" | "secs" | "s" => micros(MICROS_PER_SECOND),
+            "minute" | "minutes" | "min" | "mins" | "m" => micros(MICROS_PER_MINUTE),
+            "hour" | "hours" | "hr" | "hrs" | "h" => micros(MICROS_PER_HOUR),
+            "day" | "days" | "d" => days(1.0),
+            "week" | "weeks" | "w" => days(7.0),
+            "month" | "months" | "mon" | "mons" => months(1.0),
+            "year" | "years" | "yr" | "yrs" | "y" => months(12.0),
+            "decade" | "decades" => months(120.0),
+            "century" | "centuries" => months(1200.0),
+            _ => return Err(anyhow!("Unknown interval unit '{}'", unit)),
+        };
+        Ok(interval)
+    }
+
+    /// The sum of two intervals
+    pub fn plus(&self, other: &Interval) -> Result<Interval> {
+        Ok(Interval {
+            months: self
+                .months
+                .checked_add(other.months)
+                .ok_or_else(out_of_range)?,
+            days: self.days.checked_add(other.days).ok_or_else(out_of_range)?,
+            micros: self
+                .micros
+                .checked_add(other.micros)
+                .ok_or_else(out_of_range)?,
+        })
+    }
+
+    /// The interval pointing the other way
+    pub fn negate(&self) -> Result<Interval> {
+        Ok(Interval {
+            months: self.months.checked_neg().ok_or_else(out_of_range)?,
+            days: self.days.checked_neg().ok_or_else(out_of_range)?,
+            micros: self.micros.checked_neg().ok_or_else(out_of_range)?,
+        })
+    }
+
+    /// The whole interval in microseconds, counting a month as 30 days
+    pub fn total_micros(&self) -> i128 {
+        (self.months as i128 * DAYS_PER_MONTH as i128 + self.days as i128) * MICROS_PER_DAY as i128
+            + self.micros as i128
+    }
+}
+
+impl FromStr for Interval {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        Self::parse(text, None)
+    }
+}
+
+/// Print like PostgreSQL: '1 year 2 mons 3 days 04:05:06'
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        let mut push = |amount: i64, unit: &str| {
+            if amount != 0 {
+                let plural = if amount == 1 { "" } else { "s" };
+                parts.push(format!("{} {}{}", amount, unit, plural));
+            }
+        };
+        push(self.months as i64 / 12, "year");
+        push(self.months as i64 % 12, "mon");
+        push(self.days as i64, "day");
+        write!(f, "{}", parts.join(" "))?;
+
+        if self.micros != 0 || parts.is_empty() {
+            if !parts.is_empty() {
+                write!(f, " ")?;
+            }
+            if self.micros < 0 {
+                write!(f, "-")?;
+            }
+            format_clock(f, self.micros.abs())?;
+        }
+        Ok(())
+    }
+}
+
+/// Intervals compare by their total length, so '1 day' equals '24 hours'
+impl Ord for Interval {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.total_micros().cmp(&other.total_micros())
+    }
+}
+
+impl PartialOrd for Interval {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Interval {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Interval {}
+
+/// left + right, for the combinations that involve dates and times
+pub fn add(left: &Value, right: &Value) -> Result<Value> {
+    match (left, right) {
+        (Value::Date(d), Value::Integer(days)) | (Value::Integer(days), Value::Date(d)) => {
+            Ok(Value::Date(d.add_days(*days)?))
+        }
+        (Value::Date(d), Value::Interval(i)) | (Value::Interval(i), Value::Date(d)) => {
+            Ok(Value::Timestamp(d.to_timestamp().add(i)?))
+        }
+        (Value::Date(d), Value::Time(t)) | (Value::Time(t), Value::Date(d)) => {
+            Ok(Value::Timestamp(Timestamp(d.to_timestamp().0 + t.0)))
+        }
+        (Value::Timestamp(ts), Value::Interval(i)) | (Value::Interval(i), Value::Timestamp(ts)) => {
+            Ok(Value::Timestamp(ts.add(i)?))
+        }
+        (Value::Time(t), Value::Interval(i)) | (Value::Interval(i), Value::Time(t)) => {
+            Ok(Value::Time(t.add(i)))
+        }
+        (Value::Interval(a), Value::Interval(b)) => Ok(Value::Interval(a.plus(b)?)),
+        _ => Err(anyhow!("Cannot add {} and {}", left, right)),
+    }
+}
+
+/// left - right, for the combinations that involve dates and times
+pub fn subtract(left: &Value, right: &Value) -> Result<Value> {
+    match (left, right) {
+        (Value::Date(d), Value::Integer(days)) => Ok(Value::Date(d.add_days(-days)?)),
+        (Value::Date(a), Value::Date(b)) => Ok(Value::Integer(a.0 as i64 - b.0 as i64)),
+        (Value::Timestamp(a), Value::Timestamp(b)) => Ok(Value::Interval(a.since(b)?)),
+        (Value::Time(a), Value::Time(b)) => Ok(Value::Interval(Interval {
+            micros: a.0 - b.0,
+            ..Default::default()
+        })),
+        (_, Value::Interval(i)) => add(left, &Value::Interval(i.negate()?)),
+        _ => Err(anyhow!("Cannot subtract {} from {}", right, left)),
+    }
+}
+
+/// EXTRACT(field FROM value): one field of a date, time, timestamp or interval
+/// Whole fields come back as integers; seconds and epoch keep their
+/// microseconds as a decimal
+pub fn extract(field: &str, value: &Value) -> Result<Value> {
+    let field = field.to_ascii_lowercase();
+    let unsupported = || anyhow!("Cannot extract '{}' from {}", field, value);
+    let seconds = |micros: i64| Decimal::new(micros as i128, 6).map(Value::Decimal);
+
+    // Time fields shared by everything that has a time of day
+    let clock = |micros: i64| -> Option<Result<Value>> {
+        Some(match field.as_str() {
+            "hour" => Ok(Value::Integer(micros / MICROS_PER_HOUR)),
+            "minute" => Ok(Value::Integer(micros % MICROS_PER_HOUR / MICROS_PER_MINUTE)),
+            "second" => seconds(micros % MICROS_PER_MINUTE),
+            "milliseconds" | "millisecond" => {
+                Decimal::new((micros % MICROS_PER_MINUTE) as i128, 3).map(Value::Decimal)
+            }
+            "microseconds" | "microsecond" => Ok(Value::Integer(micros % MICROS_PER_MINUTE)),
+            _ => return None,
+        })
+    };
+
+    let timestamp = match value {
+        Value::Date(d) => d.to_timestamp(),
+        Value::Timestamp(ts) => *ts,
+        Value::Time(t) => {
+            return match field.as_str() {
+                "epoch" => seconds(t.0),
+                _ => clock(t.0).unwrap_or_else(|| Err(unsupported())),
+            };
+        }
+        Value::Interval(i) => {
+            return match field.as_str() {
+                "year" => Ok(Value::Integer(i.months as i64 / 12)),
+                "month" => Ok(Value::Integer(i.months as i64 % 12)),
+                "day" => Ok(Value::Integer(i.days as i64)),
+                "epoch" => seconds(i64::try_from(i.total_micros()).map_err(|_| out_of_range())?),
+                _ => clock(i.micros).unwrap_or_else(|| Err(unsupported())),
+            };
+        }
+        _ => {
+            return Err(anyhow!(
+                "EXTRACT needs a date, time, timestamp or interval, got {}",
+                value
+            ))
+        }
+    };
+
+    let date = timestamp.date();
+    let (year, month, day) = date.ymd();
+    let weekday = date.weekday();
+    let field_value = match field.as_str() {
+        "millennium" => (year + 999) / 1000,
+        "century" => (year + 99) / 100,
+        "decade" => year / 10,
+        "year" => year,
+        "quarter" => (month as i64 - 1) / 3 + 1,
+        "month" => month as i64,
+        "week" => {
+            // ISO 8601: weeks start on Monday, and week 1 is the one
+            // containing the year's first Thursday
+            let thursday = date.add_days(3 - (weekday + 6) % 7)?;
+            let first_day = Date::from_ymd(thursday.ymd().0, 1, 1)?;
+            (thursday.0 - first_day.0) as i64 / 7 + 1
+        }
+        "day" => day as i64,
+        "dow" => weekday,
+        "isodow" => (weekday + 6) % 7 + 1,
+        "doy" => (date.0 - Date::from_ymd(year, 1, 1)?.0) as i64 + 1,
+        "epoch" => return seconds(timestamp.0),
+        _ => return clock(timestamp.time().0).unwrap_or_else(|| Err(unsupported())),
+    };
+    Ok(Value::Integer(field_value))
+}
+
+/// DATE_TRUNC(unit, value): round a timestamp (or date, or interval) down to
+/// the start of its second, minute, hour, day, week, month, quarter, year,
+/// decade or century
+pub fn date_trunc(unit: &str, value: &Value) -> Result<Value> {
+    let unit = unit.to_ascii_lowercase();
+    let unknown = || anyhow!("Unknown DATE_TRUNC unit '{}'", unit);
+
+    let timestamp = match value {
+        Value::Timestamp(ts) => *ts,
+        Value::Date(d) => d.to_timestamp(),
+        Value::Interval(i) => {
+            let clock = |per: i64| Interval {
+                micros: i.micros / per * per,
+                ..*i
+            };
+            let months = |per: i32| Interval {
+                months: i.months / per * per,
+                days: 0,
+                micros: 0,
+            };
+            return Ok(Value::Interval(match unit.as_str() {
+                "second" => clock(MICROS_PER_SECOND),
+                "minute" => clock(MICROS_PER_MINUTE),
+                "hour" => clock(MICROS_PER_HOUR),
+                "day" => Interval { micros: 0, ..*i },
+                "month" => months(1),
+                "quarter" => months(3),
+                "year" => months(12),
+                "decade" => months(120),
+                "century" => months(1200),
+                _ => return Err(unknown()),
+            }));
+        }
+        _ => {
+            return Err(anyhow!(
+                "DATE_TRUNC needs a timestamp, date or interval, got {}",
+                value
+            ))
+        }
+    };
+
+    let clock = |per: i64| Ok(Timestamp(timestamp.0.div_euclid(per) * per));
+    let date = timestamp.date();
+    let (year, month, _) = date.ymd();
+    let start_of = |year: i64, month: u32| Date::from_ymd(year, month, 1).map(|d| d.to_timestamp());
+
+    let truncated = match unit.as_str() {
+        "second" => clock(MICROS_PER_SECOND),
+        "minute" => clock(MICROS_PER_MINUTE),
+        "hour" => clock(MICROS_PER_HOUR),
+        "day" => clock(MICROS_PER_DAY),
+        // Weeks start on Monday
+        "week" => date
+            .add_days(-((date.weekday() + 6) % 7))
+            .map(|d| d.to_timestamp()),
+        "month" => start_of(year, month),
+        "quarter" => start_of(year, (month - 1) / 3 * 3 + 1),
+        "year" => start_of(year, 1),
+        "decade" => start_of(year.div_euclid(10) * 10, 1),
+        "century" => start_of((year - 1).div_euclid(100) * 100 + 1, 1),
+        _ => Err(unknown()),
+    }?;
+    Ok(Value::Timestamp(truncated))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(text: &str) -> Value {
+        Value::Timestamp(text.parse().unwrap())
+    }
+
+    fn interval(text: &str) -> Value {
+        Value::Interval(text.parse().unwrap())
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        for text in [
+            "2026-01-01",
+            "1970-01-01",
+            "1969-12-31",
+            "2000-02-29",
+            "0001-01-01",
+        ] {
+            assert_eq!(text.parse::<Date>().unwrap().to_string(), text);
+        }
+        assert_eq!("1970-01-02".parse::<Date>().unwrap(), Date(1));
+        assert!("2026-02-29".parse::<Date>().is_err());
+        assert!("2026-13-01".parse::<Date>().is_err());
+        assert!("yesterday".parse::<Date>().is_err());
+
+        assert_eq!("07:30".parse::<Time>().unwrap().to_string(), "07:30:00");
+        assert_eq!(
+            "23:59:59.25".parse::<Time>().unwrap().to_string(),
+            "23:59:59.25"
+        );
+        assert!("24:00:00".parse::<Time>().is_err());
+
+        assert_eq!(ts("2026-03-04T05:06:07").to_string(), "2026-03-04 05:06:07");
+        assert_eq!(ts("2026-03-04").to_string(), "2026-03-04 00:00:00");
+        assert_eq!(
+            ts("1969-12-31 23:00:00"),
+            Value::Timestamp(Timestamp(-MICROS_PER_HOUR))
+        );
+
+        assert_eq!(
+            interval("1 year 2 months 3 days 04:05:06").to_string(),
+            "1 year 2 mons 3 days 04:05:06"
+        );
+        assert_eq!(interval("90 minutes").to_string(), "01:30:00");
+        assert_eq!(interval("1.5 days").to_string(), "1 day 12:00:00");
+        assert_eq!(interval("2 weeks ago").to_string(), "-14 days");
+        assert_eq!(interval("0 days").to_string(), "00:00:00");
+        assert_eq!(
+            Interval::parse("5", Some("day")).unwrap().to_string(),
+            "5 days"
+        );
+        assert!("3 fortnights".parse::<Interval>().is_err());
+
+        // '1 day' and '24 hours' are the same length
+        assert_eq!(interval("1 day"), interval("24 hours"));
+        assert!("1 month".parse::<Interval>().unwrap() > "29 days".parse().unwrap());
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let date = |text: &str| Value::Date(text.parse().unwrap());
+
+        assert_eq!(
+            add(&date("2026-01-31"), &interval("1 month")).unwrap(),
+            ts("2026-02-28")
+        );
+        assert_eq!(
+            add(&date("2024-12-31"), &Value::Integer(60)).unwrap(),
+            date("2025-03-01")
+        );
+        assert_eq!(
+            subtract(&date("2026-03-01"), &date("2026-02-01")).unwrap(),
+            Value::Integer(28)
+        );
+        assert_eq!(
+            add(&ts("2026-12-31 23:00:00"), &interval("2 hours")).unwrap(),
+            ts("2027-01-01 01:00:00")
+        );
+        assert_eq!(
+            subtract(&ts("2026-01-02 06:00:00"), &ts("2026-01-01"))
+                .unwrap()
+                .to_string(),
+            "1 day 06:00:00"
+        );
+        assert_eq!(
+            subtract(&ts("2026-01-01"), &interval("1 day")).unwrap(),
+            ts("2025-12-31")
+        );
+        let time = Value::Time("23:00".parse().unwrap());
+        assert_eq!(
+            add(&time, &interval("2 hours")).unwrap().to_string(),
+            "01:00:00"
+        );
+        assert!(add(&date("2026-01-01"), &Value::Text("x".into())).is_err());
+    }
+
+    #[test]
+    fn test_extract_and_date_trunc() {
+        let moment = ts("2026-02-16 20:38:40.5");
+        let field = |name: &str| extract(name, &moment).unwrap().to_string();
+
+        assert_eq!(field("YEAR"), "2026");
+        assert_eq!(field("quarter"), "1");
+        assert_eq!(field("month"), "2");
+        assert_eq!(field("day"), "16");
+        assert_eq!(field("hour"), "20");
+        assert_eq!(field("minute"), "38");
+        assert_eq!(field("second"), "40.500000");
+        assert_eq!(field("dow"), "1"); // a Monday
+        assert_eq!(field("isodow"), "1");
+        assert_eq!(field("doy"), "47");
+        assert_eq!(field("week"), "8");
+        assert_eq!(field("epoch"), "1771274320.500000");
+        assert_eq!(
+            extract("week", &ts("2027-01-01")).unwrap(),
+            Value::Integer(53)
+        );
+        assert_eq!(
+            extract("hour", &interval("1 day 05:00:00")).unwrap(),
+            Value::Integer(5)
+        );
+        assert!(extract("hour", &Value::Integer(1)).is_err());
+
+        let trunc = |unit: &str| date_trunc(unit, &moment).unwrap().to_string();
+        assert_eq!(trunc("hour"), "2026-02-16 20:00:00");
+        assert_eq!(trunc("day"), "2026-02-16 00:00:00");
+        assert_eq!(trunc("week"), "2026-02-16 00:00:00");
+        assert_eq!(trunc("month"), "2026-02-01 00:00:00");
+        assert_eq!(trunc("quarter"), "2026-01-01 00:00:00");
+        assert_eq!(trunc("YEAR"), "2026-01-01 00:00:00");
+        assert_eq!(trunc("century"), "2001-01-01 00:00:00");
+        assert_eq!(
+            date_trunc("month", &interval("1 year 5 months 3 days"))
+                .unwrap()
+                .to_string(),
+            "1 year 5 mons"
+        );
+        assert!(date_trunc("fortnight", &moment).is_err());
+    }
+}
@@ -0,0 +1,498 @@
+// Tuple encoding
+// Before a row version can go into a page it has to become plain bytes.
+// We use a compact binary format instead of JSON, so a row costs roughly what
+// its values are worth: an integer takes 8 bytes, a short string a few more.
+//
+// Layout of one encoded row version (all numbers little-endian):
+//   [xmin: u64][xmax present: u8][xmax: u64][column count: u16][values...]
+//
+// xmax always takes its 8 bytes, even when it is not set. That way a DELETE
+// only rewrites the header of a tuple in place, and never changes its size.
+//
+// Every value starts with a one-byte tag saying which type follows:
+//   Null     tag 0, no payload
+//   Integer  tag 1, i64
+//   Text     tag 3, u32 length + UTF-8 bytes
+//   Boolean  tag 4, u8 (0 or 1)
+//   Overflow tag 5, u32 length + u32 first page: a TEXT value too large to
+//            keep in the tuple, stored in overflow pages (see overflow.rs)
+//   Float    tag 6, the 8 bytes of an IEEE-754 f64
+//   Decimal  tag 7, u8 scale + i128 digits
+//   Date     tag 8, i32 days since 1970-01-01
+//   Time     tag 9, i64 microseconds since midnight
+//   Timestamp tag 10, i64 microseconds since 1970-01-01 00:00:00
+//   Interval tag 11, i32 months + i32 days + i64 microseconds
+//   Bytes    tag 12, u32 length + the bytes
+//   Overflow tag 13, like tag 5, but for a BLOB value
+//   Json     tag 14, u32 length + the document as JSON text
+//   Overflow tag 15, like tag 5, but for a JSON document
+//   Uuid     tag 16, the 16 bytes of the UUID
+//
+// Tag 2 is what floats used to be: an i64 holding the value times 1000.
+// It is still understood when reading, and migrate_floats() rewrites it as
+// tag 6 in place (both payloads are 8 bytes).
+
+use super::decimal::Decimal;
+use super::mvcc::{RowVersion, TxnId, VersionHeader};
+use super::temporal::{Date, Interval, Time, Timestamp};
+use super::uuid::Uuid;
+use super::{Row, Value};
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+
+const TAG_NULL: u8 = 0;
+const TAG_INTEGER: u8 = 1;
+const TAG_FIXED_POINT_FLOAT: u8 = 2;
+const TAG_TEXT: u8 = 3;
+const TAG_BOOLEAN: u8 = 4;
+const TAG_OVERFLOW: u8 = 5;
+const TAG_FLOAT: u8 = 6;
+const TAG_DECIMAL: u8 = 7;
+const TAG_DATE: u8 = 8;
+const TAG_TIME: u8 = 9;
+const TAG_TIMESTAMP: u8 = 10;
+const TAG_INTERVAL: u8 = 11;
+const TAG_BYTES: u8 = 12;
+const TAG_OVERFLOW_BYTES: u8 = 13;
+const TAG_JSON: u8 = 14;
+const TAG_OVERFLOW_JSON: u8 = 15;
+const TAG_UUID: u8 = 16;
+
+/// Fixed-point floats (tag 2) were stored as the value times this
+const FIXED_POINT_SCALE: f64 = 1000.0;
+
+/// Bytes a TEXT, BLOB or JSON value takes in the tuple once it is moved to overflow pages
+pub const OVERFLOW_REF_SIZE: usize = 9;
+
+/// Points to a TEXT, BLOB or JSON value stored in overflow pages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverflowRef {
+    /// Length of the value in bytes
+    pub len: u32,
+    /// The first page of the chain holding the value
+    pub first_page: u32,
+}
+
+/// Encode a row version into bytes, keeping every value in the tuple
+pub fn encode(version: &RowVersion) -> Vec<u8> {
+    encode_with(version, &HashMap::new())
+}
+
+/// Encode a row version into bytes
+/// The columns found in `overflow` are written as pointers to their overflow
+/// pages instead of their values
+pub fn encode_with(version: &RowVersion, overflow: &HashMap<usize, OverflowRef>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+
+    bytes.extend_from_slice(&version.xmin.to_le_bytes());
+    bytes.push(version.xmax.is_some() as u8);
+    bytes.extend_from_slice(&version.xmax.unwrap_or(0).to_le_bytes());
+    bytes.extend_from_slice(&(version.row.values.len() as u16).to_le_bytes());
+
+    for (column, value) in version.row.values.iter().enumerate() {
+        match overflow.get(&column) {
+            Some(pointer) => {
+                bytes.push(match value {
+                    Value::Bytes(_) => TAG_OVERFLOW_BYTES,
+                    Value::Json(_) => TAG_OVERFLOW_JSON,
+                    _ => TAG_OVERFLOW,
+                });
+                bytes.extend_from_slice(&pointer.len.to_le_bytes());
+                bytes.extend_from_slice(&pointer.first_page.to_le_bytes());
+            }
+            None => encode_value(value, &mut bytes),
+        }
+    }
+
+    bytes
+}
+
+/// How many bytes a value takes in a tuple when it is stored inline
+pub fn encoded_size(value: &Value) -> usize {
+    let mut bytes = Vec::new();
+    encode_value(value, &mut bytes);
+    bytes.len()
+}
+
+/// Append one value to the buffer
+fn encode_value(value: &Value, bytes: &mut Vec<u8>) {
+    match value {
+        Value::Null => bytes.push(TAG_NULL),
+        Value::Integer(i) => {
+            bytes.push(TAG_INTEGER);
+            bytes.extend_from_slice(&i.to_le_bytes());
+        }
+        Value::Float(f) => {
+            bytes.push(TAG_FLOAT);
+            bytes.extend_from_slice(&f.to_bits().to_le_bytes());
+        }
+        Value::Decimal(d) => {
+            bytes.push(TAG_DECIMAL);
+            bytes.push(d.scale());
+            bytes.extend_from_slice(&d.digits().to_le_bytes());
+        }
+        Value::Text(s) => {
+            bytes.push(TAG_TEXT);
+            bytes.extend_from_slice(&(s.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(s.as_bytes());
+        }
+        Value::Boolean(b) => {
+            bytes.push(TAG_BOOLEAN);
+            bytes.push(*b as u8);
+        }
+        Value::Bytes(b) => {
+            bytes.push(TAG_BYTES);
+            bytes.extend_from_slice(&(b.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(b);
+        }
+        Value::Uuid(u) => {
+            bytes.push(TAG_UUID);
+            bytes.extend_from_slice(&u.to_bytes());
+        }
+        Value::Json(doc) => {
+            let text = doc.to_string();
+            bytes.push(TAG_JSON);
+            bytes.extend_from_slice(&(text.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(text.as_bytes());
+        }
+        Value::Date(d) => {
+            bytes.push(TAG_DATE);
+            bytes.extend_from_slice(&d.0.to_le_bytes());
+        }
+        Value::Time(t) => {
+            bytes.push(TAG_TIME);
+            bytes.extend_from_slice(&t.0.to_le_bytes());
+        }
+        Value::Timestamp(ts) => {
+            bytes.push(TAG_TIMESTAMP);
+            bytes.extend_from_slice(&ts.0.to_le_bytes());
+        }
+        Value::Interval(i) => {
+            bytes.push(TAG_INTERVAL);
+            bytes.extend_from_slice(&i.months.to_le_bytes());
+            bytes.extend_from_slice(&i.days.to_le_bytes());
+            bytes.extend_from_slice(&i.micros.to_le_bytes());
+        }
+    }
+}
+
+/// Decode a row version previously written with encode()
+/// Fails if a value was moved to overflow pages
+pub fn decode(bytes: &[u8]) -> Result<RowVersion> {
+    decode_with(bytes, &mut |_| {
+        Err(anyhow!("Tuple refers to overflow pages"))
+    })
+}
+
+/// Decode a row version, reading values stored in overflow pages with `load`
+pub fn decode_with(
+    bytes: &[u8],
+    load: &mut dyn FnMut(OverflowRef) -> Result<Vec<u8>>,
+) -> Result<RowVersion> {
+    let mut reader = Reader { bytes, pos: 0 };
+
+    let xmin = reader.u64()?;
+    let has_xmax = reader.u8()? != 0;
+    let xmax = reader.u64()?;
+    let column_count = reader.u16()? as usize;
+
+    let mut values = Vec::with_capacity(column_count);
+    for _ in 0..column_count {
+        values.push(reader.value(load)?);
+    }
+
+    if reader.pos != bytes.len() {
+        return Err(anyhow!(
+            "Tuple has {} unexpected trailing bytes",
+            bytes.len() - reader.pos
+        ));
+    }
+
+    Ok(RowVersion {
+        xmin,
+        xmax: has_xmax.then_some(xmax),
+        row: Row { values },
+    })
+}
+
+/// Read the xmin and xmax of an encoded tuple, leaving its values alone
+pub fn decode_header(bytes: &[u8]) -> Result<VersionHeader> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let xmin = reader.u64()?;
+    let has_xmax = reader.u8()? != 0;
+    let xmax = reader.u64()?;
+    Ok(VersionHeader {
+        xmin,
+        xmax: has_xmax.then_some(xmax),
+    })
+}
+
+/// Find where each value of an encoded tuple starts, without decoding them
+/// This checks that the values are laid out right (known tags, lengths
+/// that stay inside the tuple), but not what is in them
+fn value_positions(bytes: &[u8]) -> Result<Vec<usize>> {
+    let mut reader = Reader { bytes, pos: 0 };
+    reader.take(17)?; // xmin + xmax
+    let column_count = reader.u16()?;
+
+    let mut positions = Vec::with_capacity(column_count as usize);
+    for _ in 0..column_count {
+        positions.push(reader.pos);
+        reader.skip_value()?;
+    }
+
+    if reader.pos != bytes.len() {
+        return Err(anyhow!(
+            "Tuple has {} unexpected trailing bytes",
+            bytes.len() - reader.pos
+        ));
+    }
+    Ok(positions)
+}
+
+/// List the overflow chains a tuple points to
+pub fn overflow_refs(bytes: &[u8]) -> Result<Vec<OverflowRef>> {
+    let mut refs = Vec::new();
+    for pos in value_positions(bytes)? {
+        if matches!(
+            bytes[pos],
+            TAG_OVERFLOW | TAG_OVERFLOW_BYTES | TAG_OVERFLOW_JSON
+        ) {
+            let mut reader = Reader {
+                bytes,
+                pos: pos + 1,
+            };
+            refs.push(OverflowRef {
+                len: reader.u32()?,
+                first_page: reader.u32()?,
+            });
+        }
+    }
+    Ok(refs)
+}
+
+/// Change the xmax of an encoded tuple in place
+/// xmax has a fixed width, so this never changes the size of the tuple
+pub fn set_xmax(bytes: &mut [u8], xmax: Option<TxnId>) {
+    bytes[8] = xmax.is_some() as u8;
+    bytes[9..17].copy_from_slice(&xmax.unwrap_or(0).to_le_bytes());
+}
+
+/// Rewrite the fixed-point floats (tag 2) of a tuple as f64 floats, in place
+/// The payload keeps its size, so the tuple does too
+/// Returns whether anything was rewritten
+pub fn migrate_floats(bytes: &mut [u8]) -> Result<bool> {
+    let positions: Vec<usize> = value_positions(bytes)?
+        .into_iter()
+        .filter(|&pos| bytes[pos] == TAG_FIXED_POINT_FLOAT)
+        .collect();
+
+    for &pos in &positions {
+        let fixed_point = i64::from_le_bytes(bytes[pos + 1..pos + 9].try_into()?);
+        let value = fixed_point as f64 / FIXED_POINT_SCALE;
+        bytes[pos] = TAG_FLOAT;
+        bytes[pos + 1..pos + 9].copy_from_slice(&value.to_bits().to_le_bytes());
+    }
+    Ok(!positions.is_empty())
+}
+
+/// Reads values one after another from a byte slice
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    /// Take the next n bytes, failing if the tuple ends too early
+    fn take(&mut self, n: usize) -> Result<&[u8]> {
+        let slice = self
+            .bytes
+            .get(self.pos..self.pos + n)
+            .ok_or_else(|| anyhow!("Tuple is truncated at byte {}", self.pos))?;
+        self.pos += n;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+
+    fn i64(&mut self) -> Result<i64> {
+        Ok(i64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+
+    /// Step over the next value, checking only its tag and length
+    fn skip_value(&mut self) -> Result<()> {
+        let len = match self.u8()? {
+            TAG_NULL => 0,
+            TAG_BOOLEAN => 1,
+            TAG_DATE => 4,
+            TAG_INTEGER | TAG_FIXED_POINT_FLOAT | TAG_FLOAT | TAG_TIME | TAG_TIMESTAMP => 8,
+            // u32 length + u32 first page
+            TAG_OVERFLOW | TAG_OVERFLOW_BYTES | TAG_OVERFLOW_JSON => 8,
+            TAG_INTERVAL | TAG_UUID => 16,
+            TAG_DECIMAL => 17,
+            TAG_TEXT | TAG_BYTES | TAG_JSON => self.u32()? as usize,
+            tag => return Err(anyhow!("Unknown value tag {} in tuple", tag)),
+        };
+        self.take(len)?;
+        Ok(())
+    }
+
+    fn value(&mut self, load: &mut dyn FnMut(OverflowRef) -> Result<Vec<u8>>) -> Result<Value> {
+        match self.u8()? {
+            TAG_NULL => Ok(Value::Null),
+            TAG_INTEGER => Ok(Value::Integer(self.i64()?)),
+            TAG_FLOAT => Ok(Value::Float(f64::from_bits(self.u64()?))),
+            TAG_DECIMAL => {
+                let scale = self.u8()?;
+                let digits = i128::from_le_bytes(self.take(16)?.try_into()?);
+                Ok(Value::Decimal(Decimal::new(digits, scale)?))
+            }
+            TAG_DATE => Ok(Value::Date(Date(self.u32()? as i32))),
+            TAG_TIME => Ok(Value::Time(Time(self.i64()?))),
+            TAG_TIMESTAMP => Ok(Value::Timestamp(Timestamp(self.i64()?))),
+            TAG_INTERVAL => Ok(Value::Interval(Interval {
+                months: self.u32()? as i32,
+                days: self.u32()? as i32,
+                micros: self.i64()?,
+            })),
+            TAG_FIXED_POINT_FLOAT => Ok(Value::Float(self.i64()? as f64 / FIXED_POINT_SCALE)),
+            TAG_TEXT => {
+                let len = self.u32()? as usize;
+                let text = std::str::from_utf8(self.take(len)?)?;
+                Ok(Value::Text(text.to_string()))
+            }
+            TAG_BOOLEAN => Ok(Value::Boolean(self.u8()? != 0)),
+            TAG_BYTES => {
+                let len = self.u32()? as usize;
+                Ok(Value::Bytes(self.take(len)?.to_vec()))
+            }
+            TAG_UUID => Ok(Value::Uuid(Uuid::from_bytes(self.take(16)?.try_into()?))),
+            TAG_JSON => {
+                let len = self.u32()? as usize;
+                Ok(Value::Json(serde_json::from_slice(self.take(len)?)?))
+            }
+            tag @ (TAG_OVERFLOW | TAG_OVERFLOW_BYTES | TAG_OVERFLOW_JSON) => {
+                let len = self.u32()?;
+                let first_page = self.u32()?;
+                let bytes = load(OverflowRef { len, first_page })?;
+                match tag {
+                    TAG_OVERFLOW_BYTES => Ok(Value::Bytes(bytes)),
+                    TAG_OVERFLOW_JSON => Ok(Value::Json(serde_json::from_slice(&bytes)?)),
+                    _ => Ok(Value::Text(String::from_utf8(bytes)?)),
+                }
+            }
+            tag => Err(anyhow!("Unknown value tag {} in tuple", tag)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(values: Vec<Value>) -> RowVersion {
+        RowVersion {
+            xmin: 3,
+            xmax: Some(9),
+            row: Row { values },
+        }
+    }
+
+    #[test]
+    fn test_tuple_roundtrip() {
+        let original = version(vec![
+            Value::Integer(-42),
+            Value::Float(2.75),
+            Value::Decimal("-1234.500".parse().unwrap()),
+            Value::Date("1969-07-20".parse().unwrap()),
+            Value::Time("20:17:40".parse().unwrap()),
+            Value::Timestamp("2026-01-01 12:00:00.5".parse().unwrap()),
+            Value::Interval("-1 year 2 days 03:00:00".parse().unwrap()),
+            Value::Bytes(vec![0xde, 0xad, 0x00, 0xef]),
+            Value::Json(serde_json::json!({"a": [1, 2.5, null], "b": "é"})),
+            Value::Uuid("a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11".parse().unwrap()),
+            Value::Text("héllo".to_string()),
+            Value::Boolean(true),
+            Value::Null,
+        ]);
+
+        let bytes = encode(&original);
+        assert_eq!(decode(&bytes).unwrap(), original);
+
+        // Deleting a version doesn't change its size
+        let live = RowVersion {
+            xmax: None,
+            ..original.clone()
+        };
+        assert_eq!(encode(&live).len(), bytes.len());
+
+        assert!(decode(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_tuple_size_follows_values() {
+        let small = encode(&version(vec![Value::Text("a".to_string())]));
+        let wide = encode(&version(vec![Value::Text("a".repeat(1000))]));
+        assert_eq!(wide.len() - small.len(), 999);
+    }
+
+    #[test]
+    fn test_tuple_overflow_pointers() {
+        let original = version(vec![Value::Integer(1), Value::Text("z".repeat(50_000))]);
+        let pointer = OverflowRef {
+            len: 50_000,
+            first_page: 7,
+        };
+        let mut bytes = encode_with(&original, &HashMap::from([(1, pointer)]));
+        assert_eq!(
+            bytes.len(),
+            encode(&version(vec![Value::Integer(1)])).len() + OVERFLOW_REF_SIZE
+        );
+
+        assert_eq!(overflow_refs(&bytes).unwrap(), vec![pointer]);
+        assert_eq!(decode_header(&bytes).unwrap(), original.header());
+        assert!(overflow_refs(&bytes[..bytes.len() - 1]).is_err());
+        assert!(decode(&bytes).is_err());
+        let loaded = decode_with(&bytes, &mut |p| Ok(vec![b'z'; p.len as usize])).unwrap();
+        assert_eq!(loaded, original);
+
+        set_xmax(&mut bytes, None);
+        assert_eq!(overflow_refs(&bytes).unwrap(), vec![pointer]);
+        let live = decode_with(&bytes, &mut |p| Ok(vec![b'z'; p.len as usize])).unwrap();
+        assert_eq!(live.xmax, None);
+    }
+
+    #[test]
+    fn test_tuple_migrates_fixed_point_floats() {
+        let current = version(vec![
+            Value::Integer(7),
+            Value::Float(-2.5),
+            Value::Float(0.0005),
+        ]);
+        let mut bytes = encode(&current);
+
+        // Turn the first float into the old format: tag 2, value times 1000
+        let pos = 17 + 2 + 9;
+        bytes[pos] = TAG_FIXED_POINT_FLOAT;
+        bytes[pos + 1..pos + 9].copy_from_slice(&(-2500i64).to_le_bytes());
+        assert_eq!(decode(&bytes).unwrap(), current);
+
+        assert!(migrate_floats(&mut bytes).unwrap());
+        assert_eq!(bytes, encode(&current));
+        assert!(!migrate_floats(&mut bytes).unwrap());
+    }
+}
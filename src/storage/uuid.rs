@@ -0,0 +1,178 @@
+// UUIDs
+// A UUID is a 128-bit identifier, usually written as 32 hex digits in groups:
+// 123e4567-e89b-12d3-a456-426614174000. Stored as text it takes 36 bytes and
+// compares character by character, so 'ABC...' and 'abc...' are different keys.
+// Stored as a number it takes 16 bytes, and every way of writing the same UUID
+// is the same value.
+//
+// Like PostgreSQL, we accept upper or lower case, with or without hyphens or
+// braces, and always print the standard lowercase form. UUIDs sort by their
+// bytes, the way PostgreSQL sorts them.
+//
+// gen_random_uuid() makes a version 4 UUID: 122 random bits, plus 6 bits
+// saying which kind of UUID it is.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::fs::File;
+use std::hash::BuildHasher;
+use std::io::Read;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A 128-bit UUID; the number's bytes, most significant first, are the UUID's bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Uuid(pub u128);
+
+impl Uuid {
+    /// A new random (version 4) UUID
+    pub fn new_v4() -> Self {
+        let random = random_u128();
+        // The version (4) goes in the top 4 bits of byte 6, the variant
+        // (binary 10) in the top 2 bits of byte 8
+        let version = (random & !(0xf << 76)) | (0x4 << 76);
+        Uuid((version & !(0x3 << 62)) | (0x2 << 62))
+    }
+
+    /// The 16 bytes of the UUID
+    pub fn to_bytes(&self) -> [u8; 16] {
+        self.0.to_be_bytes()
+    }
+
+    /// A UUID from its 16 bytes
+    pub fn from_bytes(bytes: [u8; 16]) -> Self {
+        Uuid(u128::from_be_bytes(bytes))
+    }
+
+    /// The version number (4 for random UUIDs)
+    pub fn version(&self) -> u8 {
+        ((self.0 >> 76) & 0xf) as u8
+    }
+}
+
+/// 128 random bits
+/// The operating system's random number generator is used when there is one;
+/// otherwise we hash the time and a counter with randomly seeded hashers
+fn random_u128() -> u128 {
+    let mut bytes = [0u8; 16];
+    if File::open("/dev/urandom")
+        .and_then(|mut file| file.read_exact(&mut bytes))
+        .is_ok()
+    {
+        return u128::from_ne_bytes(bytes);
+    }
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let high = RandomState::new().hash_one((nanos, count)) as u128;
+    let low = RandomState::new().hash_one((count, nanos)) as u128;
+    (high << 64) | low
+}
+
+/// Parse a UUID: a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11, A0EEBC999C0B4EF8BB6D6BB9BD380A11,
+/// {a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11}, ...
+impl FromStr for Uuid {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid UUID: '{}'", text);
+
+        let trimmed = text.trim();
+        let inner = match trimmed.strip_prefix('{') {
+            Some(rest) => rest.strip_suffix('}').ok_or_else(invalid)?,
+            None => trimmed,
+        };
+        // Hyphens may separate groups of 4 digits, as long as they don't start
+        // or end the UUID (like PostgreSQL)
+        if inner.starts_with('-') || inner.ends_with('-') || inner.contains("--") {
+            return Err(invalid());
+        }
+
+        let digits: String = inner.chars().filter(|&c| c != '-').collect();
+        if digits.len() != 32 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        u128::from_str_radix(&digits, 16)
+            .map(Uuid)
+            .map_err(|_| invalid())
+    }
+}
+
+/// The standard form: 8-4-4-4-12 lowercase hex digits
+impl fmt::Display for Uuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex = format!("{:032x}", self.0);
+        write!(
+            f,
+            "{}-{}-{}-{}-{}",
+            &hex[0..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..32]
+        )
+    }
+}
+
+/// UUIDs are written to JSON (the WAL) in their standard form
+impl Serialize for Uuid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Uuid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uuid_formats() {
+        let uuid: Uuid = "a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11".parse().unwrap();
+        for text in [
+            "A0EEBC99-9C0B-4EF8-BB6D-6BB9BD380A11",
+            "{a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11}",
+            "a0eebc999c0b4ef8bb6d6bb9bd380a11",
+            "a0ee-bc99-9c0b-4ef8-bb6d-6bb9-bd38-0a11",
+        ] {
+            assert_eq!(text.parse::<Uuid>().unwrap(), uuid, "{}", text);
+        }
+        assert_eq!(uuid.to_string(), "a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11");
+        assert_eq!(Uuid::from_bytes(uuid.to_bytes()), uuid);
+
+        for text in [
+            "",
+            "a0eebc99",
+            "-a0eebc999c0b4ef8bb6d6bb9bd380a11",
+            "g0eebc999c0b4ef8bb6d6bb9bd380a11",
+        ] {
+            assert!(text.parse::<Uuid>().is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_random_uuids() {
+        let a = Uuid::new_v4();
+        let b = Uuid::new_v4();
+        assert_ne!(a, b);
+        assert_eq!(a.version(), 4);
+        // The variant: the first digit of the fourth group is 8, 9, a or b
+        assert!(matches!(
+            a.to_string().as_bytes()[19],
+            b'8' | b'9' | b'a' | b'b'
+        ));
+    }
+}
@@ -0,0 +1,354 @@
+// Write-Ahead Log (WAL)
+// The golden rule of durable databases: describe a change in the log, and make
+// sure the log is on disk, BEFORE touching the data pages.
+//
+// If we crash half-way through writing pages, the log still says what we meant
+// to do, so on the next start we "replay" it and the pages catch up.
+// Once every page has been safely synced we no longer need the old log records,
+// so we throw them away - this is called a "checkpoint".
+//
+// Every record belongs to a transaction. A transaction's changes only count
+// once its Commit record is in the log; recovery undoes everything else.
+//
+// Every record gets a log sequence number (LSN), and every page remembers the
+// LSN of the last change applied to it. Pages are cached in the buffer pool
+// (buffer.rs) and written back lazily: a dirty page only reaches the disk when
+// it is evicted or when a checkpoint flushes every page. Each record is synced
+// before its change is applied, so a page on disk never holds a change the log
+// doesn't have. After a crash a page may be older than the log (its changes
+// were never written back), or newer than some of it (it was evicted after
+// them). So during recovery a record is only replayed on pages whose LSN is
+// older than the record's, and the changes of a transaction that never
+// committed are undone whether or not its pages were written back.
+//
+// Each record on disk looks like this:
+//   [4 bytes: payload length][8 bytes: checksum][payload (JSON)]
+// A crash while appending can leave a half-written ("torn") record at the end.
+// The length and checksum let us detect that and ignore everything from there on.
+
+use super::mvcc::TxnId;
+use super::page::RecordId;
+use super::Row;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Size of the record header (length + checksum)
+const HEADER_SIZE: usize = 12;
+
+/// Log sequence number: identifies a record, and increases with every record
+pub type Lsn = u64;
+
+/// A WAL shared by every table of a database
+/// Arc = shared ownership, Mutex = one writer at a time
+pub type SharedWal = Arc<Mutex<Wal>>;
+
+/// One change to a table, or the end of a transaction
+/// Records carry the record ID, so a change is always replayed on exactly the
+/// slot it was originally applied to.
+/// With MVCC an UPDATE is logged as a Delete of the old version followed by
+/// an Insert of the new one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LogRecord {
+    /// A new row version was stored under row_id
+    Insert {
+        table: String,
+        row_id: RecordId,
+        row: Row,
+    },
+    /// The row version under row_id was marked as deleted
+    Delete {
+        table: String,
+        row_id: RecordId,
+        row: Row,
+    },
+    /// The row version under row_id was physically removed
+    /// Logged when dead versions are pruned, and when an Insert is rolled back
+    Remove { table: String, row_id: RecordId },
+    /// The deletion of the row version under row_id was rolled back
+    Undelete { table: String, row_id: RecordId },
+    /// The transaction committed - its changes are permanent
+    Commit,
+    /// The transaction rolled back - its changes were undone
+    Abort,
+}
+
+impl LogRecord {
+    /// The table this record applies to (None for Commit/Abort)
+    pub fn table(&self) -> Option<&str> {
+        match self {
+            LogRecord::Insert { table, .. }
+            | LogRecord::Delete { table, .. }
+            | LogRecord::Remove { table, .. }
+            | LogRecord::Undelete { table, .. } => Some(table),
+            LogRecord::Commit | LogRecord::Abort => None,
+        }
+    }
+
+    /// The row this record applies to (None for Commit/Abort)
+    pub fn row_id(&self) -> Option<RecordId> {
+        match self {
+            LogRecord::Insert { row_id, .. }
+            | LogRecord::Delete { row_id, .. }
+            | LogRecord::Remove { row_id, .. }
+            | LogRecord::Undelete { row_id, .. } => Some(*row_id),
+            LogRecord::Commit | LogRecord::Abort => None,
+        }
+    }
+
+    /// The record that reverses this one when a transaction rolls back
+    /// (a "compensation record"), if the record changes a row
+    pub fn compensation(&self) -> Option<LogRecord> {
+        match self {
+            LogRecord::Insert { table, row_id, .. } => Some(LogRecord::Remove {
+                table: table.clone(),
+                row_id: *row_id,
+            }),
+            LogRecord::Delete { table, row_id, .. } => Some(LogRecord::Undelete {
+                table: table.clone(),
+                row_id: *row_id,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// A record together with its LSN and the transaction that wrote it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub lsn: Lsn,
+    pub txn_id: TxnId,
+    pub record: LogRecord,
+}
+
+/// The write-ahead log file
+#[derive(Debug)]
+pub struct Wal {
+    file: File,
+    /// Current size of the log in bytes
+    size: u64,
+    /// The LSN the next record will get
+    next_lsn: Lsn,
+}
+
+impl Wal {
+    /// Open (or create) a log file
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(|e| anyhow!("Cannot open WAL '{}': {}", path.display(), e))?;
+        let size = file.metadata()?.len();
+
+        Ok(Self {
+            file,
+            size,
+            next_lsn: 1,
+        })
+    }
+
+    /// Append a record for a transaction and force it to disk
+    /// Only after this returns may the change be applied to the pages
+    /// Returns the LSN of the new record
+    pub fn append(&mut self, txn_id: TxnId, record: &LogRecord) -> Result<Lsn> {
+        let entry = LogEntry {
+            lsn: self.next_lsn,
+            txn_id,
+            record: record.clone(),
+        };
+        let payload = serde_json::to_vec(&entry)?;
+
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + payload.len());
+        bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&seahash::hash(&payload).to_le_bytes());
+        bytes.extend_from_slice(&payload);
+
+        self.file.seek(SeekFrom::Start(self.size))?;
+        self.file.write_all(&bytes)?;
+        self.file.sync_data()?;
+        self.size += bytes.len() as u64;
+        self.next_lsn += 1;
+        Ok(entry.lsn)
+    }
+
+    /// Read every complete record from the start of the log
+    /// A torn or corrupted tail is cut off so that new records are appended
+    /// right after the last good one
+    pub fn recover(&mut self) -> Result<Vec<LogEntry>> {
+        let mut data = Vec::new();
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.read_to_end(&mut data)?;
+
+        let mut records = Vec::new();
+        let mut offset = 0;
+
+        while let Some((entry, next)) = Self::decode_at(&data, offset) {
+            self.next_lsn = self.next_lsn.max(entry.lsn + 1);
+            records.push(entry);
+            offset = next;
+        }
+
+        if offset < data.len() {
+            self.file.set_len(offset as u64)?;
+            self.file.sync_data()?;
+        }
+        self.size = offset as u64;
+
+        Ok(records)
+    }
+
+    /// Decode the record starting at offset
+    /// Returns None if the record is incomplete or fails its checksum
+    fn decode_at(data: &[u8], offset: usize) -> Option<(LogEntry, usize)> {
+        let header = data.get(offset..offset + HEADER_SIZE)?;
+        let len = u32::from_le_bytes(header[0..4].try_into().ok()?) as usize;
+        let checksum = u64::from_le_bytes(header[4..12].try_into().ok()?);
+
+        let start = offset + HEADER_SIZE;
+        let payload = data.get(start..start + len)?;
+        if seahash::hash(payload) != checksum {
+            return None;
+        }
+
+        let record = serde_json::from_slice(payload).ok()?;
+        Some((record, start + len))
+    }
+
+    /// Discard all records (called at a checkpoint, once the pages are synced)
+    pub fn truncate(&mut self) -> Result<()> {
+        self.file.set_len(0)?;
+        self.file.sync_data()?;
+        self.size = 0;
+        Ok(())
+    }
+
+    /// Make sure new records get LSNs above the given one
+    /// After a checkpoint the log is empty, but the pages still carry the
+    /// LSNs of the records that were applied to them
+    pub fn advance_lsn(&mut self, lsn: Lsn) {
+        self.next_lsn = self.next_lsn.max(lsn + 1);
+    }
+
+    /// Get the current size of the log in bytes
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Value;
+
+    fn temp_file(name: &str) -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("rustydb-wal-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn insert_record(slot: usize) -> LogRecord {
+        LogRecord::Insert {
+            table: "t".to_string(),
+            row_id: RecordId::new(0, slot),
+            row: Row {
+                values: vec![Value::Integer(slot as i64)],
+            },
+        }
+    }
+
+    fn records(entries: Vec<LogEntry>) -> Vec<LogRecord> {
+        entries.into_iter().map(|e| e.record).collect()
+    }
+
+    #[test]
+    fn test_wal_append_and_recover() {
+        let path = temp_file("roundtrip");
+        let mut wal = Wal::open(&path).unwrap();
+        assert_eq!(wal.append(7, &insert_record(0)).unwrap(), 1);
+        assert_eq!(wal.append(7, &LogRecord::Commit).unwrap(), 2);
+
+        let mut wal = Wal::open(&path).unwrap();
+        assert_eq!(
+            wal.recover().unwrap(),
+            vec![
+                LogEntry {
+                    lsn: 1,
+                    txn_id: 7,
+                    record: insert_record(0)
+                },
+                LogEntry {
+                    lsn: 2,
+                    txn_id: 7,
+                    record: LogRecord::Commit
+                },
+            ]
+        );
+
+        // LSNs keep increasing after the log is emptied
+        wal.truncate().unwrap();
+        assert!(wal.recover().unwrap().is_empty());
+        assert_eq!(wal.append(8, &LogRecord::Commit).unwrap(), 3);
+
+        let mut wal = Wal::open(&path).unwrap();
+        wal.truncate().unwrap();
+        wal.advance_lsn(41);
+        assert_eq!(wal.append(9, &LogRecord::Commit).unwrap(), 42);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_wal_ignores_truncated_tail() {
+        let path = temp_file("truncated");
+        let mut wal = Wal::open(&path).unwrap();
+        wal.append(1, &insert_record(0)).unwrap();
+        let good_size = wal.size();
+        wal.append(1, &insert_record(1)).unwrap();
+
+        // Simulate a crash half-way through writing the second record
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(wal.size() - 5).unwrap();
+
+        let mut wal = Wal::open(&path).unwrap();
+        assert_eq!(records(wal.recover().unwrap()), vec![insert_record(0)]);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), good_size);
+
+        // New records go right after the last good one
+        wal.append(1, &insert_record(2)).unwrap();
+        let mut wal = Wal::open(&path).unwrap();
+        assert_eq!(
+            records(wal.recover().unwrap()),
+            vec![insert_record(0), insert_record(2)]
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_wal_ignores_torn_tail() {
+        let path = temp_file("torn");
+        let mut wal = Wal::open(&path).unwrap();
+        wal.append(1, &insert_record(0)).unwrap();
+        let good_size = wal.size();
+        wal.append(1, &insert_record(1)).unwrap();
+
+        // Simulate a torn write: the record has its full length but the last
+        // bytes never made it to disk (they read back as zeros)
+        let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(wal.size() - 4)).unwrap();
+        file.write_all(&[0, 0, 0, 0]).unwrap();
+
+        let mut wal = Wal::open(&path).unwrap();
+        assert_eq!(records(wal.recover().unwrap()), vec![insert_record(0)]);
+        assert_eq!(wal.size(), good_size);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}